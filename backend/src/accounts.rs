@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::reminders;
+use crate::telegram::TelegramManager;
+
+pub type Account = Arc<RwLock<TelegramManager>>;
+
+/// How long a client started for a sign-in is kept without a login session being issued.
+const PENDING_LOGIN_TTL: Duration = Duration::from_secs(15 * 60);
+/// Sign-ins that may be in progress at once, since each one starts a Telegram client.
+const MAX_PENDING_LOGINS: usize = 8;
+
+/// Every Telegram account the backend is signed in to, each with its own session
+/// file, client, update loop and reminder scheduler, plus the login sessions bound to them.
+pub struct AccountRegistry {
+    session_base: PathBuf,
+    logins_path: PathBuf,
+    accounts: RwLock<HashMap<String, Account>>,
+    /// Login session id -> account phone.
+    sessions: RwLock<HashMap<String, String>>,
+    /// Account phone -> when its client was started, for accounts no session is bound to yet.
+    pending: RwLock<HashMap<String, Instant>>,
+    /// Media token -> account phone. Each WebSocket is issued one, so media URLs never
+    /// carry the login session and stop working once the socket closes.
+    media_tokens: RwLock<HashMap<String, String>>,
+}
+
+/// Phone numbers are keyed by their digits so "+1 234" and "1234" are one account.
+pub fn normalize_phone(phone: &str) -> String {
+    phone.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+/// `wgram.session` + `15551234` -> `wgram-15551234.session`, next to the base file.
+fn session_path_for(base: &Path, phone: &str) -> PathBuf {
+    let stem = base
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "wgram".to_string());
+    let file_name = match base.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, phone, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, phone),
+    };
+    base.with_file_name(file_name)
+}
+
+impl AccountRegistry {
+    /// Restores login sessions from disk and starts a client for every account they use.
+    pub async fn load() -> Result<Self, anyhow::Error> {
+        let session_base = PathBuf::from(
+            std::env::var("TELEGRAM_SESSION_FILE").unwrap_or_else(|_| "wgram.session".to_string()),
+        );
+        let logins_path = PathBuf::from(format!("{}.logins.json", session_base.display()));
+
+        let sessions: HashMap<String, String> = match std::fs::read(&logins_path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        info!("Loaded {} login sessions", sessions.len());
+
+        let registry = Self {
+            session_base,
+            logins_path,
+            accounts: RwLock::new(HashMap::new()),
+            sessions: RwLock::new(sessions),
+            pending: RwLock::new(HashMap::new()),
+            media_tokens: RwLock::new(HashMap::new()),
+        };
+
+        let mut phones: Vec<String> = registry.sessions.read().await.values().cloned().collect();
+        phones.sort();
+        phones.dedup();
+        for phone in phones {
+            registry.account(&phone).await?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Returns the account for `phone`, starting a client for it if needed. A client
+    /// started for a sign-in is dropped if no session is issued for it in time.
+    pub async fn account(&self, phone: &str) -> Result<Account, anyhow::Error> {
+        let phone = normalize_phone(phone);
+        if phone.is_empty() {
+            return Err(anyhow::anyhow!("Invalid phone number"));
+        }
+
+        if let Some(account) = self.accounts.read().await.get(&phone) {
+            return Ok(Arc::clone(account));
+        }
+
+        let signed_in = self.sessions.read().await.values().any(|p| *p == phone);
+        if !signed_in {
+            self.expire_pending().await;
+            if self.pending.read().await.len() >= MAX_PENDING_LOGINS {
+                return Err(anyhow::anyhow!("Too many sign-ins in progress, try again later"));
+            }
+        }
+
+        // Connecting takes a while, so it happens outside the lock and whoever
+        // loses a race for the same phone throws their client away.
+        let session_path = session_path_for(&self.session_base, &phone);
+        info!("Starting Telegram client for account {}", phone);
+        let manager = TelegramManager::new(session_path.display().to_string()).await?;
+
+        let mut accounts = self.accounts.write().await;
+        if let Some(account) = accounts.get(&phone) {
+            manager.disconnect();
+            return Ok(Arc::clone(account));
+        }
+        let closed = manager.closed();
+        let account = Arc::new(RwLock::new(manager));
+        reminders::spawn(&account, closed);
+        accounts.insert(phone.clone(), Arc::clone(&account));
+        if !signed_in {
+            self.pending.write().await.insert(phone, Instant::now());
+        }
+        Ok(account)
+    }
+
+    /// Disconnects the clients of sign-ins that were started but never finished.
+    async fn expire_pending(&self) {
+        let expired: Vec<String> = {
+            let mut pending = self.pending.write().await;
+            let expired: Vec<String> = pending
+                .iter()
+                .filter(|(_, started)| started.elapsed() >= PENDING_LOGIN_TTL)
+                .map(|(phone, _)| phone.clone())
+                .collect();
+            for phone in &expired {
+                pending.remove(phone);
+            }
+            expired
+        };
+
+        for phone in expired {
+            let account = self.accounts.write().await.remove(&phone);
+            if let Some(account) = account {
+                info!("Dropping unfinished sign-in for account {}", phone);
+                account.read().await.disconnect();
+            }
+        }
+    }
+
+    pub async fn account_for_session(&self, session_id: &str) -> Option<Account> {
+        let phone = self.sessions.read().await.get(session_id)?.clone();
+        self.accounts.read().await.get(&phone).cloned()
+    }
+
+    pub async fn phone_for_session(&self, session_id: &str) -> Option<String> {
+        self.sessions.read().await.get(session_id).cloned()
+    }
+
+    /// Issues a token that can only fetch media of `phone`'s account, for URLs that
+    /// `<img>` and `<video>` load without sending headers.
+    pub async fn issue_media_token(&self, phone: &str) -> String {
+        let token = Uuid::new_v4().to_string();
+        self.media_tokens
+            .write()
+            .await
+            .insert(token.clone(), normalize_phone(phone));
+        token
+    }
+
+    pub async fn revoke_media_token(&self, token: &str) {
+        self.media_tokens.write().await.remove(token);
+    }
+
+    /// The account a media token was issued for, and its phone.
+    pub async fn account_for_media_token(&self, token: &str) -> Option<(Account, String)> {
+        let phone = self.media_tokens.read().await.get(token)?.clone();
+        let account = self.accounts.read().await.get(&phone).cloned()?;
+        Some((account, phone))
+    }
+
+    /// Issues a new login session bound to `phone` and persists it.
+    pub async fn create_session(&self, phone: &str) -> Result<String, anyhow::Error> {
+        let session_id = Uuid::new_v4().to_string();
+
+        let mut sessions = self.sessions.write().await;
+        sessions.insert(session_id.clone(), normalize_phone(phone));
+        self.save_sessions(&sessions)?;
+        self.pending.write().await.remove(&normalize_phone(phone));
+
+        info!("✅ Created session {} for account {}", session_id, phone);
+        Ok(session_id)
+    }
+
+    /// Signs the session's account out of Telegram and drops every session bound to it.
+    /// The account is dropped locally, session file included, even if Telegram cannot
+    /// be reached, and the sockets and tasks still using it are closed.
+    pub async fn logout(&self, session_id: &str) -> Result<(), anyhow::Error> {
+        let phone = self
+            .phone_for_session(session_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Unknown session"))?;
+
+        let account = self.accounts.write().await.remove(&phone);
+        if let Some(account) = account {
+            let telegram = account.read().await;
+            if let Err(e) = telegram.sign_out().await {
+                warn!(
+                    "Failed to sign account {} out of Telegram, dropping it anyway: {}",
+                    phone, e
+                );
+            }
+            telegram.close();
+        }
+        self.pending.write().await.remove(&phone);
+        self.media_tokens.write().await.retain(|_, p| *p != phone);
+
+        let session_path = session_path_for(&self.session_base, &phone);
+        match std::fs::remove_file(&session_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove session file {:?}: {}", session_path, e),
+        }
+
+        let mut sessions = self.sessions.write().await;
+        sessions.retain(|_, p| *p != phone);
+        self.save_sessions(&sessions)?;
+
+        info!("✅ Account {} logged out", phone);
+        Ok(())
+    }
+
+    fn save_sessions(&self, sessions: &HashMap<String, String>) -> Result<(), anyhow::Error> {
+        std::fs::write(&self.logins_path, serde_json::to_vec_pretty(sessions)?)?;
+        Ok(())
+    }
+}
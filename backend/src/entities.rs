@@ -0,0 +1,60 @@
+use grammers_client::grammers_tl_types as tl;
+use wgram_protocol::{WsEntity, WsEntityKind};
+
+/// Maps Telegram's message entities to their wire form. Kinds the UI has no
+/// rendering for (bank cards, unknown future kinds) are dropped.
+pub fn convert(entities: &[tl::enums::MessageEntity]) -> Vec<WsEntity> {
+    use tl::enums::MessageEntity as E;
+
+    entities
+        .iter()
+        .filter_map(|entity| {
+            let (offset, length, kind) = match entity {
+                E::Bold(e) => (e.offset, e.length, WsEntityKind::Bold),
+                E::Italic(e) => (e.offset, e.length, WsEntityKind::Italic),
+                E::Underline(e) => (e.offset, e.length, WsEntityKind::Underline),
+                E::Strike(e) => (e.offset, e.length, WsEntityKind::Strikethrough),
+                E::Spoiler(e) => (e.offset, e.length, WsEntityKind::Spoiler),
+                E::Code(e) => (e.offset, e.length, WsEntityKind::Code),
+                E::Pre(e) => (
+                    e.offset,
+                    e.length,
+                    WsEntityKind::Pre {
+                        language: Some(e.language.clone()).filter(|l| !l.is_empty()),
+                    },
+                ),
+                E::Blockquote(e) => (e.offset, e.length, WsEntityKind::Blockquote),
+                E::Url(e) => (e.offset, e.length, WsEntityKind::Url),
+                E::TextUrl(e) => (
+                    e.offset,
+                    e.length,
+                    WsEntityKind::TextUrl { url: e.url.clone() },
+                ),
+                E::Email(e) => (e.offset, e.length, WsEntityKind::Email),
+                E::Phone(e) => (e.offset, e.length, WsEntityKind::Phone),
+                E::Mention(e) => (e.offset, e.length, WsEntityKind::Mention),
+                E::MentionName(e) => (
+                    e.offset,
+                    e.length,
+                    WsEntityKind::MentionName { user_id: e.user_id },
+                ),
+                E::Hashtag(e) => (e.offset, e.length, WsEntityKind::Hashtag),
+                E::Cashtag(e) => (e.offset, e.length, WsEntityKind::Cashtag),
+                E::BotCommand(e) => (e.offset, e.length, WsEntityKind::BotCommand),
+                E::CustomEmoji(e) => (
+                    e.offset,
+                    e.length,
+                    WsEntityKind::CustomEmoji {
+                        document_id: e.document_id,
+                    },
+                ),
+                _ => return None,
+            };
+            Some(WsEntity {
+                offset,
+                length,
+                kind,
+            })
+        })
+        .collect()
+}
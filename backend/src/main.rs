@@ -1,25 +1,59 @@
 use axum::{
+    body::Body,
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        State,
+        Path, Query, State,
     },
-    http::StatusCode,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::SeekFrom;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::io::ReaderStream;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
+mod accounts;
+mod entities;
+mod media;
+mod media_cache;
+mod message_cache;
+mod reminders;
+mod tasks;
 mod telegram;
-use telegram::TelegramManager;
+mod uploads;
+use grammers_client::{
+    session::defs::PeerId,
+    types::{Dialog, Peer},
+};
+use accounts::{Account, AccountRegistry};
+use media_cache::{CachedMedia, MediaCache};
+use tasks::TaskEvent;
+use telegram::{chat_id_for_peer, peer_type, to_presence, LoginStep, TelegramManager, TelegramUpdate};
+use uploads::Uploads;
+use wgram_protocol::{
+    ErrorCode, RequestEnvelope, ResponseEnvelope, TaskChanges, WsDialog, WsMedia, WsMessage,
+    WsReplyPreview, WsRequest, WsResponse, WsSearchResult, PROTOCOL_VERSION,
+};
+
+/// Messages returned per `GetMessages` page when the client does not ask for a size.
+const MESSAGES_PAGE_SIZE: usize = 50;
+const MAX_MESSAGES_PAGE_SIZE: usize = 100;
+/// Members listed per `GetParticipants`; enough to pick an assignee from.
+const MAX_PARTICIPANTS: usize = 200;
+/// Matches returned per `SearchMessages` page.
+const SEARCH_PAGE_SIZE: usize = 30;
 
 #[derive(Clone)]
 struct AppState {
-    telegram: Arc<RwLock<TelegramManager>>,
+    accounts: Arc<AccountRegistry>,
+    media_cache: Arc<MediaCache>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,78 +67,234 @@ struct VerifyRequest {
     code: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct VerifyPasswordRequest {
+    phone: String,
+    password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionRequest {
+    session_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WsParams {
+    #[serde(default)]
+    session_id: Option<String>,
+    /// Clients that predate versioning send none and are treated as version 1.
+    #[serde(default)]
+    protocol_version: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MediaParams {
+    /// A media token from `Hello`, passed in the query since `<img>` and `<video>`
+    /// cannot send headers.
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    thumb: bool,
+}
+
+/// Login step the client should show after an auth call.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NextAuthStep {
+    Code,
+    Password,
+    Done,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct AuthResponse {
     success: bool,
     message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_step: Option<NextAuthStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password_hint: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-enum WsRequest {
-    GetDialogs,
-    GetMessages {
-        chat_id: i64,
-    },
-    SendMessage {
-        chat_id: i64,
-        text: String,
-    },
-    SendFile {
-        chat_id: i64,
-        file_name: String,
-        file_data: Vec<u8>,
-    },
+fn to_frontend_message(m: &grammers_client::types::Message) -> WsMessage {
+    let sender_name = m
+        .sender()
+        .and_then(|s| s.name())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    let media = m.media().as_ref().and_then(media::classify);
+
+    WsMessage {
+        id: m.id(),
+        text: m.text().to_string(),
+        entities: m
+            .fmt_entities()
+            .map(|entities| entities::convert(entities))
+            .unwrap_or_default(),
+        sender_name,
+        is_outgoing: m.outgoing(),
+        timestamp: m.date().timestamp(),
+        edit_date: m.edit_date().map(|date| date.timestamp()),
+        is_file: media.as_ref().is_some_and(media::is_file),
+        file_name: media.as_ref().and_then(media::file_name),
+        media,
+        reply_to_message_id: m.reply_to_message_id(),
+        reply_preview: None,
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-enum WsResponse {
-    Dialogs {
-        data: Vec<FrontendDialog>,
-    },
-    Messages {
-        chat_id: i64,
-        data: Vec<FrontendMessage>,
-    },
-    MessageSent {
-        chat_id: i64,
-        success: bool,
-        message: String,
-    },
-    FileSent {
-        chat_id: i64,
-        success: bool,
-        message: String,
-    },
-    NewMessage {
-        chat_id: i64,
-        message: FrontendMessage,
-    },
+/// Fills in `reply_preview` for messages of `chat_id` that reply to another one,
+/// looking up replied-to messages that are not part of `messages` themselves.
+/// Lookup failures leave the preview empty rather than failing the whole batch.
+async fn resolve_replies(telegram: &TelegramManager, chat_id: i64, messages: &mut [WsMessage]) {
+    let mut previews: HashMap<i32, WsReplyPreview> = messages
+        .iter()
+        .map(|m| (m.id, reply_preview(m)))
+        .collect();
+
+    let mut missing: Vec<i32> = messages
+        .iter()
+        .filter_map(|m| m.reply_to_message_id)
+        .filter(|id| !previews.contains_key(id))
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+
+    if !missing.is_empty() {
+        match telegram.get_messages_by_id(chat_id, &missing).await {
+            Ok(found) => previews.extend(
+                found
+                    .iter()
+                    .flatten()
+                    .map(|m| (m.id(), reply_preview(&to_frontend_message(m)))),
+            ),
+            Err(e) => warn!("Failed to resolve replied-to messages in chat_id {}: {}", chat_id, e),
+        }
+    }
+
+    for message in messages {
+        message.reply_preview = message
+            .reply_to_message_id
+            .and_then(|id| previews.get(&id).cloned());
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct FrontendDialog {
-    id: i64,
-    name: String,
-    last_message: String,
-    unread_count: i32,
-    is_archived: bool,
+/// [`resolve_replies`] for a single message.
+async fn with_reply(telegram: &TelegramManager, chat_id: i64, message: WsMessage) -> WsMessage {
+    if message.reply_to_message_id.is_none() {
+        return message;
+    }
+    let mut messages = [message];
+    resolve_replies(telegram, chat_id, &mut messages).await;
+    let [message] = messages;
+    message
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct FrontendMessage {
-    id: i32,
-    text: String,
-    sender_name: String,
-    is_outgoing: bool,
-    timestamp: i64,
-    #[serde(default)]
-    is_file: bool,
-    #[serde(default)]
-    file_name: Option<String>,
+fn reply_preview(message: &WsMessage) -> WsReplyPreview {
+    WsReplyPreview {
+        sender_name: message.sender_name.clone(),
+        text: preview_text(message),
+    }
+}
+
+fn to_frontend_dialog(d: &Dialog) -> WsDialog {
+    let id = chat_id_for_peer(d.peer.id());
+    let name = d.peer.name().map(|s| s.to_string()).unwrap_or_else(|| "Unknown".to_string());
+
+    let (unread_count, is_archived, read_inbox_max_id, read_outbox_max_id) = if let grammers_client::grammers_tl_types::enums::Dialog::Dialog(dialog) = &d.raw {
+        let archived = dialog.folder_id.unwrap_or(0) == 1;
+        (dialog.unread_count, archived, dialog.read_inbox_max_id, dialog.read_outbox_max_id)
+    } else {
+        (0, false, 0, 0)
+    };
+
+    let presence = match &d.peer {
+        Peer::User(user) => Some(to_presence(user.status())),
+        _ => None,
+    };
+
+    let last_message = d
+        .last_message
+        .as_ref()
+        .map(|m| preview_text(&to_frontend_message(m)))
+        .unwrap_or_default();
+
+    info!("Dialog: id={}, name={}, last_message={}, unread={}, archived={}",
+        id, name, last_message, unread_count, is_archived);
+
+    WsDialog {
+        id,
+        peer_type: peer_type(id).to_string(),
+        name,
+        last_message,
+        unread_count,
+        is_archived,
+        read_inbox_max_id,
+        read_outbox_max_id,
+        presence,
+    }
+}
+
+/// Fetches all dialogs from Telegram and replaces the cached list with them.
+async fn fetch_dialogs(telegram: &TelegramManager) -> Result<Vec<WsDialog>, anyhow::Error> {
+    let dialogs: Vec<WsDialog> = telegram.get_dialogs().await?.iter().map(to_frontend_dialog).collect();
+    info!("Successfully fetched {} dialogs", dialogs.len());
+    if let Some(cache) = telegram.message_cache() {
+        if let Err(e) = cache.store_dialogs(&dialogs) {
+            warn!("Failed to cache dialogs: {}", e);
+        }
+    }
+    Ok(dialogs)
+}
+
+/// Fetches a page of history with reply previews and stores it in the cache.
+/// Returns whether Telegram has older messages.
+async fn fetch_messages(
+    telegram: &TelegramManager,
+    chat_id: i64,
+    before_id: Option<i32>,
+    limit: usize,
+) -> Result<(Vec<WsMessage>, bool), anyhow::Error> {
+    let messages = telegram.get_messages(chat_id, before_id, limit).await?;
+    // A short page means Telegram ran out of history.
+    let has_more = messages.len() == limit;
+    let mut messages: Vec<WsMessage> = messages.iter().map(to_frontend_message).collect();
+    resolve_replies(telegram, chat_id, &mut messages).await;
+
+    if let Some(cache) = telegram.message_cache() {
+        if let Err(e) = cache.store_page(chat_id, before_id, &messages, has_more) {
+            warn!("Failed to cache messages for chat_id {}: {}", chat_id, e);
+        }
+    }
+    Ok((messages, has_more))
+}
+
+/// Keeps the cache in step with messages that were sent, edited or arrived.
+fn cache_messages(telegram: &TelegramManager, chat_id: i64, messages: &[WsMessage]) {
+    if let Some(cache) = telegram.message_cache() {
+        if let Err(e) = cache.store_messages(chat_id, messages) {
+            warn!("Failed to cache messages for chat_id {}: {}", chat_id, e);
+        }
+    }
+}
+
+fn uncache_messages(telegram: &TelegramManager, chat_id: Option<i64>, message_ids: &[i32]) {
+    if let Some(cache) = telegram.message_cache() {
+        if let Err(e) = cache.delete_messages(chat_id, message_ids) {
+            warn!("Failed to drop deleted messages from the cache: {}", e);
+        }
+    }
+}
+
+/// What the dialog list shows for a message: its text, or a label for its media.
+fn preview_text(message: &WsMessage) -> String {
+    match &message.media {
+        Some(media) if message.text.is_empty() => media.describe(),
+        _ => message.text.clone(),
+    }
 }
 
 async fn request_code(
@@ -113,13 +303,16 @@ async fn request_code(
 ) -> Result<Json<AuthResponse>, AppError> {
     info!("Requesting code for phone: {}", payload.phone);
 
-    let mut telegram = state.telegram.write().await;
+    let account = state.accounts.account(&payload.phone).await?;
+    let mut telegram = account.write().await;
     telegram.send_code(&payload.phone).await?;
 
     Ok(Json(AuthResponse {
         success: true,
         message: "Code sent! Check your SMS or email".to_string(),
         session_id: None,
+        next_step: Some(NextAuthStep::Code),
+        password_hint: None,
     }))
 }
 
@@ -129,198 +322,1019 @@ async fn verify_code(
 ) -> Result<Json<AuthResponse>, AppError> {
     info!("Verifying code for phone: {}", payload.phone);
 
-    let mut telegram = state.telegram.write().await;
-    let session_id = telegram.verify_code(&payload.phone, &payload.code).await?;
+    let account = state.accounts.account(&payload.phone).await?;
+    let mut telegram = account.write().await;
+    let response = match telegram.verify_code(&payload.phone, &payload.code).await? {
+        LoginStep::SignedIn => AuthResponse {
+            success: true,
+            message: "Authenticated successfully!".to_string(),
+            session_id: Some(state.accounts.create_session(&payload.phone).await?),
+            next_step: Some(NextAuthStep::Done),
+            password_hint: None,
+        },
+        LoginStep::Password { hint } => AuthResponse {
+            success: true,
+            message: "Two-step verification is enabled. Enter your cloud password".to_string(),
+            session_id: None,
+            next_step: Some(NextAuthStep::Password),
+            password_hint: hint,
+        },
+    };
+
+    Ok(Json(response))
+}
+
+async fn verify_password(
+    State(state): State<AppState>,
+    Json(payload): Json<VerifyPasswordRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    info!("Verifying 2FA password for phone: {}", payload.phone);
+
+    let account = state.accounts.account(&payload.phone).await?;
+    let mut telegram = account.write().await;
+    telegram
+        .verify_password(&payload.phone, &payload.password)
+        .await?;
+    let session_id = state.accounts.create_session(&payload.phone).await?;
 
     Ok(Json(AuthResponse {
         success: true,
         message: "Authenticated successfully!".to_string(),
         session_id: Some(session_id),
+        next_step: Some(NextAuthStep::Done),
+        password_hint: None,
     }))
 }
 
-async fn handle_websocket(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
-    ws.on_upgrade(|socket| websocket_handler(socket, state))
+async fn check_session(
+    State(state): State<AppState>,
+    Json(payload): Json<SessionRequest>,
+) -> Response {
+    let valid = state
+        .accounts
+        .account_for_session(&payload.session_id)
+        .await
+        .is_some();
+
+    let status = if valid {
+        StatusCode::OK
+    } else {
+        StatusCode::UNAUTHORIZED
+    };
+    (
+        status,
+        Json(AuthResponse {
+            success: valid,
+            message: if valid {
+                "Session is valid".to_string()
+            } else {
+                "Session expired, please sign in again".to_string()
+            },
+            session_id: None,
+            next_step: None,
+            password_hint: None,
+        }),
+    )
+        .into_response()
 }
 
-async fn websocket_handler(mut socket: WebSocket, state: AppState) {
-    info!("WebSocket connection established");
+async fn logout(
+    State(state): State<AppState>,
+    Json(payload): Json<SessionRequest>,
+) -> Result<Json<AuthResponse>, AppError> {
+    info!("Logging out session");
 
-    while let Some(msg) = socket.recv().await {
-        match msg {
-            Ok(Message::Text(text)) => {
-                info!("Received command: {}", text);
-                let request: WsRequest = match serde_json::from_str(&text) {
-                    Ok(req) => req,
-                    Err(e) => {
-                        error!("Failed to parse command: {}", e);
-                        continue;
-                    }
+    state.accounts.logout(&payload.session_id).await?;
+
+    Ok(Json(AuthResponse {
+        success: true,
+        message: "Logged out successfully".to_string(),
+        session_id: None,
+        next_step: Some(NextAuthStep::Code),
+        password_hint: None,
+    }))
+}
+
+/// Serves a message's media file, or its thumbnail with `?thumb=true`, downloading
+/// it into the disk cache on first request.
+async fn get_media(
+    Path((chat_id, message_id)): Path<(i64, i32)>,
+    Query(params): Query<MediaParams>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let token = params.token.unwrap_or_default();
+    let Some((account, phone)) = state.accounts.account_for_media_token(&token).await else {
+        return Ok(StatusCode::UNAUTHORIZED.into_response());
+    };
+
+    let cache = &state.media_cache;
+    let path = cache.path_for(&phone, chat_id, message_id, params.thumb);
+
+    let (len, meta) = match cache.get(&path).await {
+        Some(cached) => cached,
+        None => {
+            let telegram = account.read().await;
+            let Some(media) = telegram
+                .get_message(chat_id, message_id)
+                .await?
+                .and_then(|m| m.media())
+            else {
+                return Ok(StatusCode::NOT_FOUND.into_response());
+            };
+            let Some(description) = media::classify(&media).filter(media::is_file) else {
+                return Ok(StatusCode::NOT_FOUND.into_response());
+            };
+
+            let temp = cache.temp_path(&path);
+            let meta = if params.thumb {
+                // Sticker thumbnails keep the sticker's own WebP format.
+                let content_type = match description {
+                    WsMedia::Sticker { .. } => "image/webp",
+                    _ => "image/jpeg",
                 };
+                CachedMedia {
+                    content_type: content_type.to_string(),
+                    file_name: None,
+                }
+            } else {
+                CachedMedia {
+                    content_type: media::content_type(&description),
+                    file_name: media::file_name(&description),
+                }
+            };
 
-                let response = match request {
-                    WsRequest::GetDialogs => {
-                        let mut telegram = state.telegram.write().await;
+            let downloaded = if params.thumb {
+                telegram.download_thumbnail(&media, &temp).await
+            } else {
+                info!("Downloading media {} from chat_id: {}", message_id, chat_id);
+                telegram.download_media(media, &temp).await.map(|_| true)
+            };
+            let inserted = match downloaded {
+                Ok(true) => cache.insert(&temp, &path, &meta).await.map(Some),
+                Ok(false) => Ok(None),
+                Err(e) => Err(e),
+            };
+            match inserted {
+                Ok(Some(len)) => (len, meta),
+                Ok(None) => return Ok(StatusCode::NOT_FOUND.into_response()),
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&temp).await;
+                    return Err(e.into());
+                }
+            }
+        }
+    };
 
-                        let dialogs_data = match telegram.is_authorized().await {
-                            Ok(false) => {
-                                error!("Telegram client is not authorized!");
-                                vec![]
-                            }
-                            Err(e) => {
-                                error!("Failed to check authorization: {}", e);
-                                vec![]
-                            }
-                            Ok(true) => {
-                                info!("Telegram client is authorized, fetching dialogs...");
-                                match telegram.get_dialogs().await {
-                                    Ok(dialogs) => {
-                                        info!("Successfully fetched {} dialogs", dialogs.len());
-                                        let frontend_dialogs: Vec<FrontendDialog> = dialogs
-                                            .into_iter()
-                                            .enumerate()
-                                            .map(|(index, d)| {
-                                                let id = (index + 1) as i64;
-                                                let name = d.peer.name().map(|s| s.to_string()).unwrap_or_else(|| "Unknown".to_string());
-
-                                                let (unread_count, is_archived) = if let grammers_client::grammers_tl_types::enums::Dialog::Dialog(dialog) = &d.raw {
-                                                    let archived = dialog.folder_id.unwrap_or(0) == 1;
-                                                    (dialog.unread_count, archived)
-                                                } else {
-                                                    (0, false)
-                                                };
-
-                                                let last_message = d
-                                                    .last_message
-                                                    .as_ref()
-                                                    .map(|m| m.text().to_string())
-                                                    .unwrap_or_default();
-
-                                                info!("Dialog: id={}, name={}, last_message={}, unread={}, archived={}",
-                                                    id, name, last_message, unread_count, is_archived);
-
-                                                FrontendDialog {
-                                                    id,
-                                                    name,
-                                                    last_message,
-                                                    unread_count,
-                                                    is_archived,
-                                                }
-                                            })
-                                            .collect();
-
-                                        info!(
-                                            "Sending {} dialogs to frontend",
-                                            frontend_dialogs.len()
-                                        );
-                                        frontend_dialogs
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to get dialogs: {}", e);
-                                        vec![]
-                                    }
-                                }
-                            }
-                        };
+    serve_file(&path, len, &meta, &headers).await
+}
 
-                        WsResponse::Dialogs { data: dialogs_data }
-                    }
-                    WsRequest::GetMessages { chat_id } => {
-                        let telegram = state.telegram.read().await;
-
-                        match telegram.get_messages(chat_id, 50).await {
-                            Ok(messages) => {
-                                info!(
-                                    "Successfully fetched {} messages for chat_id: {}",
-                                    messages.len(),
-                                    chat_id
-                                );
-                                let frontend_messages: Vec<FrontendMessage> = messages
-                                    .into_iter()
-                                    .map(|m| {
-                                        let sender_name = m
-                                            .sender()
-                                            .and_then(|s| s.name())
-                                            .map(|s| s.to_string())
-                                            .unwrap_or_else(|| "Unknown".to_string());
-
-                                        FrontendMessage {
-                                            id: m.id(),
-                                            text: m.text().to_string(),
-                                            sender_name,
-                                            is_outgoing: m.outgoing(),
-                                            timestamp: m.date().timestamp(),
-                                            is_file: false,
-                                            file_name: None,
-                                        }
-                                    })
-                                    .collect();
-
-                                WsResponse::Messages {
-                                    chat_id,
-                                    data: frontend_messages,
-                                }
+/// Streams the file, honouring a single `Range: bytes=...` so audio and video can seek.
+async fn serve_file(
+    path: &std::path::Path,
+    len: u64,
+    meta: &CachedMedia,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| parse_range(v, len));
+
+    let (status, start, end) = match range {
+        None => (StatusCode::OK, 0, len.saturating_sub(1)),
+        Some(Some((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(None) => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+            )
+                .into_response());
+        }
+    };
+    let length = if len == 0 { 0 } else { end - start + 1 };
+
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+
+    // The sender picks the MIME type, so only known media types are shown in place.
+    let inline = media::is_inline(&meta.content_type);
+    let content_type = if inline {
+        meta.content_type.as_str()
+    } else {
+        "application/octet-stream"
+    };
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, length)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CACHE_CONTROL, "private, max-age=86400")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CONTENT_SECURITY_POLICY, "sandbox");
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, len),
+        );
+    }
+    // A name that cannot go in a header is left out rather than failing the download.
+    let disposition = if inline { "inline" } else { "attachment" };
+    let disposition = meta
+        .file_name
+        .as_ref()
+        .map(|name| {
+            let name = name.replace(['"', '\\', '\r', '\n'], "_");
+            format!("{}; filename=\"{}\"", disposition, name)
+        })
+        .and_then(|value| HeaderValue::from_str(&value).ok())
+        .unwrap_or(HeaderValue::from_static(disposition));
+    response = response.header(header::CONTENT_DISPOSITION, disposition);
+
+    let body = Body::from_stream(ReaderStream::new(file.take(length)));
+    Ok(response.body(body)?)
+}
+
+/// Parses `bytes=start-end`, `bytes=start-` or `bytes=-suffix` into an inclusive
+/// range within `len`. Only the first of several ranges is honoured.
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = value.strip_prefix("bytes=")?.split(',').next()?.trim();
+    let (start, end) = spec.split_once('-')?;
+
+    let (start, end) = if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        (len.saturating_sub(suffix), len.checked_sub(1)?)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            len.checked_sub(1)?
+        } else {
+            end.parse::<u64>().ok()?.min(len.checked_sub(1)?)
+        };
+        (start, end)
+    };
+
+    (start <= end && end < len).then_some((start, end))
+}
+
+async fn handle_websocket(
+    ws: WebSocketUpgrade,
+    Query(params): Query<WsParams>,
+    State(state): State<AppState>,
+) -> Response {
+    let session_id = params.session_id.unwrap_or_default();
+    let (Some(account), Some(phone)) = (
+        state.accounts.account_for_session(&session_id).await,
+        state.accounts.phone_for_session(&session_id).await,
+    ) else {
+        warn!("Rejected WebSocket connection without a valid session");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let protocol_version = params.protocol_version.unwrap_or(1);
+    let accounts = state.accounts;
+    ws.on_upgrade(move |socket| async move {
+        // Media URLs carry a token that lives as long as this socket, never the session.
+        let media_token = accounts.issue_media_token(&phone).await;
+        websocket_handler(socket, account, protocol_version, media_token.clone()).await;
+        accounts.revoke_media_token(&media_token).await;
+    })
+}
+
+async fn websocket_handler(
+    mut socket: WebSocket,
+    account: Account,
+    protocol_version: u32,
+    media_token: String,
+) {
+    info!("WebSocket connection established");
+
+    // The upgrade already succeeded, so a mismatch is reported in-band where the
+    // client can read it, and the connection is then closed.
+    let compatible = protocol_version == PROTOCOL_VERSION;
+    let greeting = if compatible {
+        WsResponse::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            media_token: Some(media_token),
+        }
+    } else {
+        warn!(
+            "Rejected client speaking protocol {} (backend speaks {})",
+            protocol_version, PROTOCOL_VERSION
+        );
+        WsResponse::Error {
+            code: ErrorCode::UnsupportedProtocol,
+            message: format!(
+                "Client speaks protocol {}, backend speaks {}",
+                protocol_version, PROTOCOL_VERSION
+            ),
+        }
+    };
+    let greeting = ResponseEnvelope {
+        id: None,
+        response: greeting,
+    };
+    if let Err(e) = socket
+        .send(Message::Text(serde_json::to_string(&greeting).unwrap()))
+        .await
+    {
+        error!("Failed to send greeting: {}", e);
+        return;
+    }
+    if !compatible {
+        let _ = socket.close().await;
+        return;
+    }
+
+    let (mut updates, mut task_events, closed) = {
+        let telegram = account.read().await;
+        (telegram.subscribe(), telegram.tasks().subscribe(), telegram.closed())
+    };
+    let mut uploads = Uploads::default();
+    // Fresh data found after a request was already answered from the cache.
+    let (push_tx, mut push_rx) = mpsc::unbounded_channel();
+
+    loop {
+        let (id, response) = tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<RequestEnvelope>(&text) {
+                        Ok(RequestEnvelope { id, request }) => {
+                            // File payloads are far too large to log.
+                            if !matches!(request, WsRequest::SendFile { .. } | WsRequest::FileChunk { .. }) {
+                                info!("Received command: {}", text);
                             }
-                            Err(e) => {
-                                error!("Failed to get messages: {}", e);
-                                WsResponse::Messages {
-                                    chat_id,
-                                    data: vec![],
-                                }
+                            match handle_request(request, &account, &mut uploads, &push_tx).await {
+                                Some(response) => (id, response),
+                                None => continue,
                             }
                         }
+                        Err(e) => {
+                            error!("Failed to parse command: {}", e);
+                            let id = serde_json::from_str::<serde_json::Value>(&text)
+                                .ok()
+                                .and_then(|value| value.get("id")?.as_u64());
+                            let response = WsResponse::Error {
+                                code: ErrorCode::BadRequest,
+                                message: format!("Invalid command: {}", e),
+                            };
+                            (id, response)
+                        }
                     }
-                    WsRequest::SendMessage { chat_id, text } => {
-                        let telegram = state.telegram.read().await;
-
-                        match telegram.send_message(chat_id, &text).await {
-                            Ok(()) => {
-                                info!("Message sent successfully to chat_id: {}", chat_id);
-                                WsResponse::MessageSent {
-                                    chat_id,
-                                    success: true,
-                                    message: "Message sent successfully".to_string(),
-                                }
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(e)) => {
+                    error!("WebSocket error: {}", e);
+                    break;
+                }
+                None => break,
+            },
+            update = updates.recv() => match update {
+                Ok(update) => (None, handle_update(update, &account).await),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket lagged behind, skipped {} Telegram updates", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            event = task_events.recv() => match event {
+                Ok(TaskEvent::Saved(task)) => (None, WsResponse::TaskSaved { task: *task }),
+                Ok(TaskEvent::Deleted(task_id)) => (None, WsResponse::TaskDeleted { task_id }),
+                Ok(TaskEvent::Reminder(task)) => (None, WsResponse::TaskReminder { task: *task }),
+                // Cheaper to resend the whole list than to work out what was missed.
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket lagged behind, skipped {} task changes", skipped);
+                    (None, list_tasks(&*account.read().await))
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            Some(response) = push_rx.recv() => (None, response),
+            _ = closed.cancelled() => {
+                info!("Account logged out, closing WebSocket");
+                let _ = socket.close().await;
+                break;
+            }
+        };
+
+        let response_text = serde_json::to_string(&ResponseEnvelope { id, response }).unwrap();
+        if let Err(e) = socket.send(Message::Text(response_text)).await {
+            error!("Failed to send response: {}", e);
+            break;
+        }
+    }
+
+    info!("WebSocket connection closed");
+}
+
+async fn handle_request(
+    request: WsRequest,
+    account: &Account,
+    uploads: &mut Uploads,
+    push: &mpsc::UnboundedSender<WsResponse>,
+) -> Option<WsResponse> {
+    let response = match request {
+        WsRequest::GetDialogs => {
+            let telegram = account.read().await;
+
+            let authorized = telegram.is_authorized().await;
+            if let Ok(false) = authorized {
+                error!("Telegram client is not authorized!");
+                return Some(WsResponse::Error {
+                    code: ErrorCode::NotAuthorized,
+                    message: "Telegram client is not authorized".to_string(),
+                });
+            }
+            let cached = telegram
+                .message_cache()
+                .and_then(|cache| cache.dialogs().map_err(|e| warn!("Failed to read cached dialogs: {}", e)).ok())
+                .filter(|dialogs| !dialogs.is_empty());
+
+            match (authorized, cached) {
+                (Ok(_), Some(data)) => {
+                    info!("Serving {} cached dialogs, refreshing from Telegram", data.len());
+                    let account = account.clone();
+                    let push = push.clone();
+                    tokio::spawn(async move {
+                        let telegram = account.read().await;
+                        match fetch_dialogs(&telegram).await {
+                            Ok(data) => {
+                                let _ = push.send(WsResponse::Dialogs { data });
                             }
-                            Err(e) => {
-                                error!("Failed to send message: {}", e);
-                                WsResponse::MessageSent {
-                                    chat_id,
-                                    success: false,
-                                    message: format!("Failed to send message: {}", e),
-                                }
+                            Err(e) => warn!("Failed to refresh dialogs, keeping cached ones: {}", e),
+                        }
+                    });
+                    WsResponse::Dialogs { data }
+                }
+                (Err(e), Some(data)) => {
+                    warn!("Telegram unreachable ({}), serving {} cached dialogs", e, data.len());
+                    WsResponse::Dialogs { data }
+                }
+                (Ok(_), None) => {
+                    info!("Telegram client is authorized, fetching dialogs...");
+                    match fetch_dialogs(&telegram).await {
+                        Ok(data) => {
+                            info!("Sending {} dialogs to frontend", data.len());
+                            WsResponse::Dialogs { data }
+                        }
+                        Err(e) => {
+                            error!("Failed to get dialogs: {}", e);
+                            WsResponse::Error {
+                                code: ErrorCode::Telegram,
+                                message: format!("Failed to get dialogs: {}", e),
                             }
                         }
                     }
-                    WsRequest::SendFile {
+                }
+                (Err(e), None) => {
+                    error!("Failed to check authorization: {}", e);
+                    WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to check authorization: {}", e),
+                    }
+                }
+            }
+        }
+        WsRequest::GetMessages {
+            chat_id,
+            before_id,
+            limit,
+        } => {
+            let telegram = account.read().await;
+            let limit = limit
+                .unwrap_or(MESSAGES_PAGE_SIZE)
+                .clamp(1, MAX_MESSAGES_PAGE_SIZE);
+            let cached = |before_id| {
+                telegram
+                    .message_cache()
+                    .and_then(|cache| {
+                        cache
+                            .messages(chat_id, before_id, limit)
+                            .map_err(|e| warn!("Failed to read cached messages: {}", e))
+                            .ok()
+                    })
+                    .filter(|messages| !messages.is_empty())
+            };
+
+            // Opening a chat shows the cached tail at once; older pages are fetched
+            // live since the cache may have gaps there.
+            if let Some(data) = before_id.is_none().then(|| cached(None)).flatten() {
+                info!("Serving {} cached messages for chat_id: {}, refreshing", data.len(), chat_id);
+                let account = account.clone();
+                let push = push.clone();
+                tokio::spawn(async move {
+                    let telegram = account.read().await;
+                    match fetch_messages(&telegram, chat_id, None, limit).await {
+                        Ok((data, has_more)) => {
+                            let _ = push.send(WsResponse::Messages {
+                                chat_id,
+                                data,
+                                before_id: None,
+                                has_more,
+                            });
+                        }
+                        Err(e) => warn!("Failed to refresh messages for chat_id {}: {}", chat_id, e),
+                    }
+                });
+                return Some(WsResponse::Messages {
+                    chat_id,
+                    data,
+                    before_id: None,
+                    has_more: true,
+                });
+            }
+
+            match fetch_messages(&telegram, chat_id, before_id, limit).await {
+                Ok((data, has_more)) => {
+                    info!(
+                        "Successfully fetched {} messages for chat_id: {}",
+                        data.len(),
+                        chat_id
+                    );
+                    WsResponse::Messages {
                         chat_id,
-                        file_name: _,
-                        file_data: _,
-                    } => {
-                        error!("File sending not yet implemented");
-                        WsResponse::FileSent {
+                        data,
+                        before_id,
+                        has_more,
+                    }
+                }
+                Err(e) => match cached(before_id) {
+                    Some(data) => {
+                        warn!("Failed to get messages ({}), serving {} cached ones", e, data.len());
+                        WsResponse::Messages {
                             chat_id,
-                            success: false,
-                            message: "File sending not yet implemented".to_string(),
+                            data,
+                            before_id,
+                            has_more: true,
                         }
                     }
-                };
+                    None => {
+                        error!("Failed to get messages: {}", e);
+                        WsResponse::Error {
+                            code: ErrorCode::Telegram,
+                            message: format!("Failed to get messages: {}", e),
+                        }
+                    }
+                },
+            }
+        }
+        WsRequest::SendMessage {
+            chat_id,
+            text,
+            client_id,
+            reply_to,
+            parse_mode,
+        } => {
+            let telegram = account.read().await;
 
-                let response_text = serde_json::to_string(&response).unwrap();
-                if let Err(e) = socket.send(Message::Text(response_text)).await {
-                    error!("Failed to send response: {}", e);
-                    break;
+            match telegram
+                .send_message(chat_id, &text, reply_to, parse_mode)
+                .await
+            {
+                Ok(sent) => {
+                    info!("Message sent successfully to chat_id: {}", chat_id);
+                    let sent = with_reply(&telegram, chat_id, to_frontend_message(&sent)).await;
+                    cache_messages(&telegram, chat_id, std::slice::from_ref(&sent));
+                    WsResponse::MessageSent {
+                        chat_id,
+                        success: true,
+                        message: "Message sent successfully".to_string(),
+                        client_id,
+                        sent_message: Some(sent),
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to send message: {}", e);
+                    WsResponse::MessageSent {
+                        chat_id,
+                        success: false,
+                        message: format!("Failed to send message: {}", e),
+                        client_id,
+                        sent_message: None,
+                    }
                 }
             }
-            Ok(_) => {}
-            Err(e) => {
-                error!("WebSocket error: {}", e);
-                break;
+        }
+        WsRequest::SendFile {
+            chat_id,
+            file_name,
+            file_data,
+            caption,
+        } => {
+            let telegram = account.read().await;
+            let size = file_data.len();
+            let mut stream = std::io::Cursor::new(file_data);
+
+            let result = telegram
+                .send_file(chat_id, &mut stream, size, &file_name, &caption)
+                .await;
+            file_sent_response(chat_id, None, result)
+        }
+        WsRequest::StartFileUpload {
+            chat_id,
+            upload_id,
+            file_name,
+            file_size,
+            caption,
+        } => match uploads
+            .start(&upload_id, chat_id, file_name, file_size, caption)
+            .await
+        {
+            Ok(()) => WsResponse::FileUploadProgress {
+                chat_id,
+                upload_id,
+                received: 0,
+            },
+            Err(e) => file_sent_response(chat_id, Some(upload_id), Err(e)),
+        },
+        WsRequest::FileChunk {
+            chat_id,
+            upload_id,
+            data,
+        } => match uploads.write_chunk(&upload_id, &data).await {
+            Ok(received) => WsResponse::FileUploadProgress {
+                chat_id,
+                upload_id,
+                received,
+            },
+            Err(e) => file_sent_response(chat_id, Some(upload_id), Err(e)),
+        },
+        WsRequest::FinishFileUpload { chat_id, upload_id } => {
+            let upload = match uploads.finish(&upload_id).await {
+                Ok(upload) => upload,
+                Err(e) => return Some(file_sent_response(chat_id, Some(upload_id), Err(e))),
+            };
+
+            let result = match tokio::fs::File::open(&upload.path).await {
+                Ok(mut file) => {
+                    let telegram = account.read().await;
+                    telegram
+                        .send_file(
+                            upload.chat_id,
+                            &mut file,
+                            upload.file_size as usize,
+                            &upload.file_name,
+                            &upload.caption,
+                        )
+                        .await
+                }
+                Err(e) => Err(e.into()),
+            };
+            file_sent_response(upload.chat_id, Some(upload_id), result)
+        }
+        WsRequest::ForwardMessages {
+            from_chat,
+            ids,
+            to_chat,
+        } => {
+            let telegram = account.read().await;
+
+            match telegram.forward_messages(from_chat, &ids, to_chat).await {
+                Ok(forwarded) => {
+                    let messages: Vec<WsMessage> = forwarded.iter().map(to_frontend_message).collect();
+                    cache_messages(&telegram, to_chat, &messages);
+                    WsResponse::MessagesForwarded {
+                        from_chat,
+                        to_chat,
+                        messages,
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to forward messages: {}", e);
+                    WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to forward messages: {}", e),
+                    }
+                }
+            }
+        }
+        WsRequest::MarkRead { chat_id, max_id } => {
+            let telegram = account.read().await;
+
+            match telegram.mark_read(chat_id, max_id).await {
+                Ok(()) => WsResponse::ReadInbox {
+                    chat_id,
+                    max_id,
+                    unread_count: 0,
+                },
+                Err(e) => {
+                    error!("Failed to mark chat as read: {}", e);
+                    WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to mark chat as read: {}", e),
+                    }
+                }
+            }
+        }
+        WsRequest::EditMessage {
+            chat_id,
+            message_id,
+            text,
+            parse_mode,
+        } => {
+            let telegram = account.read().await;
+
+            match telegram
+                .edit_message(chat_id, message_id, &text, parse_mode)
+                .await
+            {
+                Ok(edited) => {
+                    let message = with_reply(&telegram, chat_id, to_frontend_message(&edited)).await;
+                    cache_messages(&telegram, chat_id, std::slice::from_ref(&message));
+                    WsResponse::MessageEdited { chat_id, message }
+                }
+                Err(e) => {
+                    error!("Failed to edit message: {}", e);
+                    WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to edit message: {}", e),
+                    }
+                }
             }
         }
+        WsRequest::DeleteMessages {
+            chat_id,
+            ids,
+            revoke,
+        } => {
+            let telegram = account.read().await;
+
+            match telegram.delete_messages(chat_id, &ids, revoke).await {
+                Ok(()) => {
+                    uncache_messages(&telegram, Some(chat_id), &ids);
+                    WsResponse::MessagesDeleted {
+                        chat_id: Some(chat_id),
+                        message_ids: ids,
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to delete messages: {}", e);
+                    WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to delete messages: {}", e),
+                    }
+                }
+            }
+        }
+        WsRequest::SearchMessages {
+            query,
+            chat_id,
+            filter,
+            offset,
+        } => {
+            if query.trim().is_empty() {
+                return Some(WsResponse::Error {
+                    code: ErrorCode::BadRequest,
+                    message: "Search query is empty".to_string(),
+                });
+            }
+            let telegram = account.read().await;
+
+            match telegram
+                .search_messages(&query, chat_id, filter, offset as usize, SEARCH_PAGE_SIZE)
+                .await
+            {
+                Ok(messages) => {
+                    let next_offset = (messages.len() == SEARCH_PAGE_SIZE)
+                        .then_some(offset + SEARCH_PAGE_SIZE as u32);
+                    let results = messages
+                        .iter()
+                        .map(|m| WsSearchResult {
+                            chat_id: chat_id_for_peer(m.peer_id()),
+                            message: to_frontend_message(m),
+                        })
+                        .collect();
+
+                    WsResponse::SearchResults {
+                        query,
+                        chat_id,
+                        offset,
+                        results,
+                        next_offset,
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to search messages: {}", e);
+                    WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to search messages: {}", e),
+                    }
+                }
+            }
+        }
+        WsRequest::SetTyping { chat_id, typing } => {
+            let telegram = account.read().await;
+
+            // Typing is best effort, only a failure is worth a reply.
+            return match telegram.set_typing(chat_id, typing).await {
+                Ok(()) => None,
+                Err(e) => {
+                    warn!("Failed to send typing action: {}", e);
+                    Some(WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to send typing action: {}", e),
+                    })
+                }
+            };
+        }
+        WsRequest::ListTasks => list_tasks(&*account.read().await),
+        WsRequest::CreateTask {
+            text,
+            user_name,
+            chat_id,
+            message_id,
+        } => {
+            if text.trim().is_empty() {
+                return Some(WsResponse::Error {
+                    code: ErrorCode::BadRequest,
+                    message: "Task text is empty".to_string(),
+                });
+            }
+            let telegram = account.read().await;
+
+            // Other clients hear about it through the task store's broadcast.
+            match telegram.tasks().create(&text, &user_name, chat_id, message_id) {
+                Ok(task) => {
+                    info!("✅ Created task {}", task.id);
+                    WsResponse::TaskSaved { task }
+                }
+                Err(e) => task_store_error("create task", e),
+            }
+        }
+        WsRequest::UpdateTask { task_id, changes } => {
+            if changes.text.as_deref().is_some_and(|text| text.trim().is_empty()) {
+                return Some(WsResponse::Error {
+                    code: ErrorCode::BadRequest,
+                    message: "Task text is empty".to_string(),
+                });
+            }
+            let telegram = account.read().await;
+
+            match telegram.tasks().update(task_id, changes) {
+                Ok(Some(task)) => WsResponse::TaskSaved { task },
+                Ok(None) => unknown_task(task_id),
+                Err(e) => task_store_error("update task", e),
+            }
+        }
+        WsRequest::DeleteTask { task_id } => {
+            let telegram = account.read().await;
+
+            match telegram.tasks().delete(task_id) {
+                Ok(true) => WsResponse::TaskDeleted { task_id },
+                Ok(false) => unknown_task(task_id),
+                Err(e) => task_store_error("delete task", e),
+            }
+        }
+        WsRequest::SnoozeTask { task_id, minutes } => {
+            if minutes == 0 {
+                return Some(WsResponse::Error {
+                    code: ErrorCode::BadRequest,
+                    message: "Snooze needs at least one minute".to_string(),
+                });
+            }
+            let telegram = account.read().await;
+            let due_at = chrono::Utc::now().timestamp() + i64::from(minutes) * 60;
+            let changes = TaskChanges {
+                due_at: Some(Some(due_at)),
+                ..TaskChanges::default()
+            };
+
+            match telegram.tasks().update(task_id, changes) {
+                Ok(Some(task)) => {
+                    info!("⏰ Snoozed task {} for {} minutes", task_id, minutes);
+                    WsResponse::TaskSaved { task }
+                }
+                Ok(None) => unknown_task(task_id),
+                Err(e) => task_store_error("snooze task", e),
+            }
+        }
+        WsRequest::GetParticipants { chat_id } => {
+            let telegram = account.read().await;
+
+            match telegram.get_participants(chat_id, MAX_PARTICIPANTS).await {
+                Ok(data) => WsResponse::Participants { chat_id, data },
+                Err(e) => {
+                    error!("Failed to get participants: {}", e);
+                    WsResponse::Error {
+                        code: ErrorCode::Telegram,
+                        message: format!("Failed to get participants: {}", e),
+                    }
+                }
+            }
+        }
+    };
+    Some(response)
+}
+
+fn list_tasks(telegram: &TelegramManager) -> WsResponse {
+    match telegram.tasks().list() {
+        Ok(data) => WsResponse::Tasks { data },
+        Err(e) => task_store_error("list tasks", e),
     }
+}
 
-    info!("WebSocket connection closed");
+fn unknown_task(task_id: i64) -> WsResponse {
+    WsResponse::Error {
+        code: ErrorCode::BadRequest,
+        message: format!("No task with id {}", task_id),
+    }
+}
+
+fn task_store_error(action: &str, e: anyhow::Error) -> WsResponse {
+    error!("Failed to {}: {}", action, e);
+    WsResponse::Error {
+        code: ErrorCode::Storage,
+        message: format!("Failed to {}: {}", action, e),
+    }
+}
+
+fn file_sent_response(
+    chat_id: i64,
+    upload_id: Option<String>,
+    result: Result<grammers_client::types::Message, anyhow::Error>,
+) -> WsResponse {
+    match result {
+        Ok(sent) => {
+            info!("File sent successfully to chat_id: {}", chat_id);
+            WsResponse::FileSent {
+                chat_id,
+                success: true,
+                message: "File sent successfully".to_string(),
+                upload_id,
+                sent_message: Some(to_frontend_message(&sent)),
+            }
+        }
+        Err(e) => {
+            error!("Failed to send file: {}", e);
+            WsResponse::FileSent {
+                chat_id,
+                success: false,
+                message: format!("Failed to send file: {}", e),
+                upload_id,
+                sent_message: None,
+            }
+        }
+    }
+}
+
+async fn handle_update(update: TelegramUpdate, account: &Account) -> WsResponse {
+    match update {
+        TelegramUpdate::NewMessage(message) => {
+            let chat_id = chat_id_for_peer(message.peer_id());
+            info!("New message {} in chat_id: {}", message.id(), chat_id);
+            let telegram = account.read().await;
+            let message = with_reply(&telegram, chat_id, to_frontend_message(&message)).await;
+            cache_messages(&telegram, chat_id, std::slice::from_ref(&message));
+            WsResponse::NewMessage { chat_id, message }
+        }
+        TelegramUpdate::MessageEdited(message) => {
+            let chat_id = chat_id_for_peer(message.peer_id());
+            info!("Message {} edited in chat_id: {}", message.id(), chat_id);
+            let telegram = account.read().await;
+            let message = with_reply(&telegram, chat_id, to_frontend_message(&message)).await;
+            cache_messages(&telegram, chat_id, std::slice::from_ref(&message));
+            WsResponse::MessageEdited { chat_id, message }
+        }
+        TelegramUpdate::ReadInbox {
+            chat_id,
+            max_id,
+            unread_count,
+        } => WsResponse::ReadInbox {
+            chat_id,
+            max_id,
+            unread_count,
+        },
+        TelegramUpdate::ReadOutbox { chat_id, max_id } => {
+            WsResponse::ReadOutbox { chat_id, max_id }
+        }
+        TelegramUpdate::UserTyping {
+            chat_id,
+            user_id,
+            action,
+        } => WsResponse::UserTyping {
+            chat_id,
+            user_id,
+            action,
+        },
+        TelegramUpdate::UserStatus { user_id, presence } => {
+            WsResponse::UserStatus { user_id, presence }
+        }
+        TelegramUpdate::MessagesDeleted {
+            channel_id,
+            message_ids,
+        } => {
+            // Deletions outside channels carry no chat, the ids are unique per account.
+            let chat_id = channel_id.map(|id| chat_id_for_peer(PeerId::channel(id)));
+            info!("{} messages deleted in chat_id: {:?}", message_ids.len(), chat_id);
+            uncache_messages(&*account.read().await, chat_id, &message_ids);
+            WsResponse::MessagesDeleted {
+                chat_id,
+                message_ids,
+            }
+        }
+    }
 }
 
 #[tokio::main]
@@ -337,12 +1351,19 @@ async fn main() {
         Err(_) => info!("No .env file found, using system environment variables"),
     }
 
-    let telegram = TelegramManager::new()
+    // Checked up front so a missing key stops the backend instead of every account
+    // quietly running without its message cache.
+    message_cache::secret_from_env().expect("Failed to initialize message cache");
+
+    let accounts = AccountRegistry::load()
         .await
-        .expect("Failed to initialize Telegram client");
+        .expect("Failed to initialize Telegram accounts");
+
+    let media_cache = MediaCache::from_env().expect("Failed to initialize media cache");
 
     let app_state = AppState {
-        telegram: Arc::new(RwLock::new(telegram)),
+        accounts: Arc::new(accounts),
+        media_cache: Arc::new(media_cache),
     };
 
     let cors = CorsLayer::new()
@@ -354,7 +1375,11 @@ async fn main() {
         .route("/health", get(|| async { "OK" }))
         .route("/auth/request-code", post(request_code))
         .route("/auth/verify-code", post(verify_code))
+        .route("/auth/verify-password", post(verify_password))
+        .route("/auth/session", post(check_session))
+        .route("/auth/logout", post(logout))
         .route("/ws", get(handle_websocket))
+        .route("/media/:chat_id/:message_id", get(get_media))
         .layer(cors)
         .with_state(app_state);
 
@@ -379,6 +1404,8 @@ impl IntoResponse for AppError {
                 success: false,
                 message: self.0.to_string(),
                 session_id: None,
+                next_step: None,
+                password_hint: None,
             }),
         )
             .into_response()
@@ -393,3 +1420,121 @@ where
         Self(err.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_closed_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-499999", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=10-20, 30-40", 1000), Some((10, 20)));
+    }
+
+    #[test]
+    fn parses_open_ended_ranges() {
+        assert_eq!(parse_range("bytes=100-", 1000), Some((100, 999)));
+        assert_eq!(parse_range("bytes=999-", 1000), Some((999, 999)));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=2000-3000", 1000), None);
+        assert_eq!(parse_range("bytes=20-10", 1000), None);
+        assert_eq!(parse_range("items=0-10", 1000), None);
+        assert_eq!(parse_range("bytes=abc", 1000), None);
+    }
+
+    #[test]
+    fn zero_length_files_have_no_ranges() {
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("bytes=0-0", 0), None);
+        assert_eq!(parse_range("bytes=-1", 0), None);
+    }
+
+    async fn serve(contents: &[u8], range: Option<&str>) -> Response {
+        let meta = CachedMedia {
+            content_type: "application/octet-stream".to_string(),
+            file_name: None,
+        };
+        serve_as(&meta, contents, range).await
+    }
+
+    async fn serve_as(meta: &CachedMedia, contents: &[u8], range: Option<&str>) -> Response {
+        let path = std::env::temp_dir().join(format!("wgram-range-{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, contents).await.unwrap();
+        let mut headers = HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(header::RANGE, HeaderValue::from_str(range).unwrap());
+        }
+        let response = serve_file(&path, contents.len() as u64, meta, &headers)
+            .await
+            .unwrap();
+        let _ = tokio::fs::remove_file(&path).await;
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_requested_range() {
+        let response = serve(b"0123456789", Some("bytes=2-4")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-4/10");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "3");
+    }
+
+    #[tokio::test]
+    async fn unsatisfiable_range_is_416() {
+        let response = serve(b"0123456789", Some("bytes=20-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+    }
+
+    #[tokio::test]
+    async fn zero_length_file_is_served_whole() {
+        let response = serve(b"", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "0");
+
+        let response = serve(b"", Some("bytes=0-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+
+    #[tokio::test]
+    async fn documents_are_only_offered_as_downloads() {
+        let meta = CachedMedia {
+            content_type: "text/html".to_string(),
+            file_name: Some("page.html".to_string()),
+        };
+        let response = serve_as(&meta, b"<script>alert(1)</script>", None).await;
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_TYPE], "application/octet-stream");
+        assert_eq!(
+            headers[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"page.html\""
+        );
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::CONTENT_SECURITY_POLICY], "sandbox");
+    }
+
+    #[tokio::test]
+    async fn images_are_shown_in_place() {
+        let meta = CachedMedia {
+            content_type: "image/png".to_string(),
+            file_name: None,
+        };
+        let response = serve_as(&meta, b"png", None).await;
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_TYPE], "image/png");
+        assert_eq!(headers[header::CONTENT_DISPOSITION], "inline");
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    }
+}
@@ -0,0 +1,197 @@
+use grammers_client::grammers_tl_types as tl;
+use grammers_client::types::Media;
+use wgram_protocol::WsMedia;
+
+/// Maps a message's media to its wire description. Web page previews and other
+/// kinds the UI has no rendering for are left out.
+pub fn classify(media: &Media) -> Option<WsMedia> {
+    match media {
+        Media::Photo(photo) => {
+            let largest = largest_photo_size(&photo.raw);
+            Some(WsMedia::Photo {
+                width: largest.map(|(w, _, _)| w),
+                height: largest.map(|(_, h, _)| h),
+                size: largest.map(|(_, _, size)| size),
+            })
+        }
+        Media::Document(document) => classify_document(&document.raw),
+        Media::Sticker(sticker) => classify_document(&sticker.document.raw),
+        Media::Geo(geo) => Some(WsMedia::Geo {
+            latitude: geo.raw.lat,
+            longitude: geo.raw.long,
+        }),
+        Media::Contact(contact) => Some(WsMedia::Contact {
+            first_name: contact.raw.first_name.clone(),
+            last_name: contact.raw.last_name.clone(),
+            phone_number: contact.raw.phone_number.clone(),
+        }),
+        Media::Poll(poll) => {
+            let poll = &poll.raw;
+            Some(WsMedia::Poll {
+                question: text_of(&poll.question),
+                answers: poll
+                    .answers
+                    .iter()
+                    .map(|tl::enums::PollAnswer::Answer(answer)| text_of(&answer.text))
+                    .collect(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Whether the media is a file the user can download.
+pub fn is_file(media: &WsMedia) -> bool {
+    matches!(
+        media,
+        WsMedia::Photo { .. }
+            | WsMedia::Document { .. }
+            | WsMedia::Audio { .. }
+            | WsMedia::Video { .. }
+            | WsMedia::Sticker { .. }
+    )
+}
+
+pub fn file_name(media: &WsMedia) -> Option<String> {
+    match media {
+        WsMedia::Document { file_name, .. }
+        | WsMedia::Audio { file_name, .. }
+        | WsMedia::Video { file_name, .. } => file_name.clone(),
+        _ => None,
+    }
+}
+
+/// MIME type to serve the full file with.
+pub fn content_type(media: &WsMedia) -> String {
+    match media {
+        WsMedia::Photo { .. } => Some("image/jpeg".to_string()),
+        WsMedia::Document { mime_type, .. }
+        | WsMedia::Audio { mime_type, .. }
+        | WsMedia::Video { mime_type, .. }
+        | WsMedia::Sticker { mime_type, .. } => mime_type.clone(),
+        _ => None,
+    }
+    .unwrap_or_else(|| "application/octet-stream".to_string())
+}
+
+/// MIME types a browser may render in place. Anything else, HTML and SVG included,
+/// could run script on the backend's origin and is only offered as a download.
+const INLINE_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "audio/mpeg",
+    "audio/mp4",
+    "audio/aac",
+    "audio/ogg",
+    "audio/wav",
+    "audio/webm",
+    "audio/flac",
+    "video/mp4",
+    "video/webm",
+    "video/ogg",
+    "video/quicktime",
+];
+
+/// Whether a file of this MIME type is served for display rather than as a download.
+pub fn is_inline(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    INLINE_TYPES
+        .iter()
+        .any(|inline| inline.eq_ignore_ascii_case(essence))
+}
+
+/// Telegram marks a document as audio, video or sticker through its attributes;
+/// everything else is a plain document.
+fn classify_document(raw: &tl::types::MessageMediaDocument) -> Option<WsMedia> {
+    let Some(tl::enums::Document::Document(document)) = &raw.document else {
+        return None;
+    };
+
+    let mime_type = Some(document.mime_type.clone()).filter(|m| !m.is_empty());
+    let size = u64::try_from(document.size).ok();
+    let mut file_name = None;
+    let mut audio = None;
+    let mut video = None;
+    let mut sticker = None;
+    let mut image_size = None;
+    let mut animated = false;
+
+    for attribute in &document.attributes {
+        match attribute {
+            tl::enums::DocumentAttribute::Filename(a) => file_name = Some(a.file_name.clone()),
+            tl::enums::DocumentAttribute::Audio(a) => audio = Some(a),
+            tl::enums::DocumentAttribute::Video(a) => video = Some(a),
+            tl::enums::DocumentAttribute::Sticker(a) => sticker = Some(a),
+            tl::enums::DocumentAttribute::ImageSize(a) => image_size = Some((a.w, a.h)),
+            tl::enums::DocumentAttribute::Animated => animated = true,
+            _ => {}
+        }
+    }
+
+    if let Some(sticker) = sticker {
+        let (width, height) = image_size.or(video.map(|v| (v.w, v.h))).unzip();
+        return Some(WsMedia::Sticker {
+            emoji: Some(sticker.alt.clone()).filter(|e| !e.is_empty()),
+            // Animated (.tgs) and video (.webm) stickers cannot be shown as an image.
+            animated: animated || document.mime_type != "image/webp",
+            mime_type,
+            width,
+            height,
+        });
+    }
+
+    if let Some(video) = video {
+        return Some(WsMedia::Video {
+            file_name,
+            mime_type,
+            size,
+            duration: Some(video.duration),
+            width: Some(video.w),
+            height: Some(video.h),
+            round: video.round_message,
+        });
+    }
+
+    if let Some(audio) = audio {
+        return Some(WsMedia::Audio {
+            file_name,
+            mime_type,
+            size,
+            duration: Some(audio.duration as f64),
+            title: audio.title.clone(),
+            performer: audio.performer.clone(),
+            voice: audio.voice,
+        });
+    }
+
+    Some(WsMedia::Document {
+        file_name,
+        mime_type,
+        size,
+    })
+}
+
+fn largest_photo_size(raw: &tl::types::MessageMediaPhoto) -> Option<(i32, i32, u64)> {
+    let Some(tl::enums::Photo::Photo(photo)) = &raw.photo else {
+        return None;
+    };
+
+    photo
+        .sizes
+        .iter()
+        .filter_map(|size| match size {
+            tl::enums::PhotoSize::Size(s) => Some((s.w, s.h, s.size as u64)),
+            tl::enums::PhotoSize::Progressive(s) => {
+                Some((s.w, s.h, s.sizes.iter().copied().max().unwrap_or(0) as u64))
+            }
+            _ => None,
+        })
+        .max_by_key(|(w, h, _)| i64::from(*w) * i64::from(*h))
+}
+
+fn text_of(text: &tl::enums::TextWithEntities) -> String {
+    let tl::enums::TextWithEntities::Entities(text) = text;
+    text.text.clone()
+}
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+const DEFAULT_MAX_CACHE_MB: u64 = 1024;
+
+/// How to serve a cached file, stored next to it since the Telegram metadata is
+/// not at hand on a cache hit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedMedia {
+    pub content_type: String,
+    pub file_name: Option<String>,
+}
+
+/// Downloaded media files on disk, evicted least-recently-used first once the
+/// total size passes the limit.
+pub struct MediaCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl MediaCache {
+    /// `MEDIA_CACHE_DIR` (default `wgram-media-cache`) and `MEDIA_CACHE_MAX_MB`
+    /// (default 1024) configure where files go and how much space they may use.
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let dir = PathBuf::from(
+            std::env::var("MEDIA_CACHE_DIR").unwrap_or_else(|_| "wgram-media-cache".to_string()),
+        );
+        let max_mb = std::env::var("MEDIA_CACHE_MAX_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_CACHE_MB);
+
+        std::fs::create_dir_all(&dir)?;
+        info!("Media cache at {:?}, limited to {} MB", dir, max_mb);
+
+        Ok(Self {
+            dir,
+            max_bytes: max_mb * 1024 * 1024,
+        })
+    }
+
+    /// Chat and message ids are only unique per account, so the account is part of the key.
+    pub fn path_for(&self, account: &str, chat_id: i64, message_id: i32, thumb: bool) -> PathBuf {
+        let suffix = if thumb { "-thumb" } else { "" };
+        self.dir
+            .join(format!("{}_{}_{}{}.bin", account, chat_id, message_id, suffix))
+    }
+
+    /// A unique file to download into, renamed into place by [`MediaCache::insert`].
+    pub fn temp_path(&self, path: &Path) -> PathBuf {
+        path.with_extension(format!("part-{}", uuid::Uuid::new_v4()))
+    }
+
+    /// Returns the file's size and metadata if cached, marking it as recently used.
+    pub async fn get(&self, path: &Path) -> Option<(u64, CachedMedia)> {
+        let meta = tokio::fs::read(meta_path(path)).await.ok()?;
+        let meta: CachedMedia = serde_json::from_slice(&meta).ok()?;
+        let len = tokio::fs::metadata(path).await.ok()?.len();
+
+        let path = path.to_path_buf();
+        let _ = tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()))
+        })
+        .await;
+
+        Some((len, meta))
+    }
+
+    pub async fn insert(
+        &self,
+        temp: &Path,
+        path: &Path,
+        meta: &CachedMedia,
+    ) -> Result<u64, anyhow::Error> {
+        tokio::fs::write(meta_path(path), serde_json::to_vec(meta)?).await?;
+        tokio::fs::rename(temp, path).await?;
+        let len = tokio::fs::metadata(path).await?.len();
+
+        let dir = self.dir.clone();
+        let max_bytes = self.max_bytes;
+        let inserted = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = evict(&dir, max_bytes, &inserted) {
+                warn!("Failed to evict media cache: {}", e);
+            }
+        });
+
+        Ok(len)
+    }
+}
+
+fn meta_path(path: &Path) -> PathBuf {
+    path.with_extension("json")
+}
+
+/// Removes the least recently used files until the cache fits in `max_bytes`. The
+/// file just inserted is kept even if it alone is larger, since it is about to be served.
+fn evict(dir: &Path, max_bytes: u64, inserted: &Path) -> Result<(), std::io::Error> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "bin") && path != inserted {
+            let metadata = std::fs::metadata(&path)?;
+            files.push((metadata.modified()?, metadata.len(), path));
+        }
+    }
+
+    let inserted_len = std::fs::metadata(inserted).map_or(0, |m| m.len());
+    let mut total: u64 = inserted_len + files.iter().map(|(_, len, _)| len).sum::<u64>();
+    if total <= max_bytes {
+        return Ok(());
+    }
+
+    files.sort_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in files {
+        if total <= max_bytes {
+            break;
+        }
+        std::fs::remove_file(&path)?;
+        let _ = std::fs::remove_file(meta_path(&path));
+        total -= len;
+        info!("Evicted {:?} from media cache", path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wgram-media-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn put(dir: &Path, name: &str, len: usize, age_secs: u64) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, vec![0; len]).unwrap();
+        let modified = SystemTime::now() - std::time::Duration::from_secs(age_secs);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let dir = cache_dir();
+        let old = put(&dir, "old.bin", 40, 300);
+        let recent = put(&dir, "recent.bin", 40, 200);
+        let inserted = put(&dir, "new.bin", 40, 0);
+
+        evict(&dir, 100, &inserted).unwrap();
+        let (old, recent, inserted) = (old.exists(), recent.exists(), inserted.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(!old);
+        assert!(recent);
+        assert!(inserted);
+    }
+
+    #[test]
+    fn keeps_an_inserted_file_larger_than_the_cache() {
+        let dir = cache_dir();
+        let other = put(&dir, "other.bin", 10, 100);
+        let inserted = put(&dir, "huge.bin", 500, 0);
+
+        evict(&dir, 100, &inserted).unwrap();
+        let (other, inserted) = (other.exists(), inserted.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(!other);
+        assert!(inserted);
+    }
+}
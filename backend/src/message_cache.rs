@@ -0,0 +1,422 @@
+use aes::Aes256;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlite::State;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::{info, warn};
+use wgram_protocol::{WsDialog, WsMessage};
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+
+const IV_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+
+/// Bot API ids at or below this are channels, whose message ids are per channel.
+const CHANNEL_ID_LIMIT: i64 = -1_000_000_000_000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS dialogs (
+        id INTEGER PRIMARY KEY,
+        position INTEGER NOT NULL,
+        data BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        chat_id INTEGER NOT NULL,
+        id INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (chat_id, id)
+    );
+";
+
+/// Dialogs and messages of one account, kept in SQLite next to its session so chats
+/// open instantly and stay readable while Telegram is unreachable.
+///
+/// Rows are stored as JSON encrypted with AES-256-CTR and authenticated with
+/// HMAC-SHA256; only chat and message ids are left in the clear for lookups.
+pub struct MessageCache {
+    connection: Mutex<sqlite::Connection>,
+    enc_key: [u8; 32],
+    mac_key: [u8; 32],
+}
+
+impl MessageCache {
+    /// Opens or creates the cache at `path`, keyed from `MESSAGE_CACHE_KEY`.
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        Self::open_with_secret(path, secret_from_env()?.as_bytes())
+    }
+
+    fn open_with_secret(path: &Path, secret: &[u8]) -> Result<Self, anyhow::Error> {
+        let connection = sqlite::open(path)?;
+        connection.execute(SCHEMA)?;
+        info!("Message cache at {:?}", path);
+
+        let (enc_key, mac_key) = derive_keys(secret);
+        Ok(Self {
+            connection: Mutex::new(connection),
+            enc_key,
+            mac_key,
+        })
+    }
+
+    /// Cached dialogs in the order Telegram last listed them.
+    pub fn dialogs(&self) -> Result<Vec<WsDialog>, anyhow::Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT id, data FROM dialogs ORDER BY position")?;
+
+        let mut dialogs = Vec::new();
+        while let State::Row = statement.next()? {
+            let id = statement.read::<i64, _>(0)?;
+            let data = statement.read::<Vec<u8>, _>(1)?;
+            match self.open_row(&dialog_aad(id), &data) {
+                Ok(dialog) => dialogs.push(dialog),
+                Err(e) => warn!("Skipping unreadable cached dialog {}: {}", id, e),
+            }
+        }
+        Ok(dialogs)
+    }
+
+    /// Replaces the cached dialog list with a fresh one from Telegram.
+    pub fn store_dialogs(&self, dialogs: &[WsDialog]) -> Result<(), anyhow::Error> {
+        let rows = dialogs
+            .iter()
+            .map(|dialog| Ok((dialog.id, self.seal_row(&dialog_aad(dialog.id), dialog)?)))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        let connection = self.connection.lock().unwrap();
+        transaction(&connection, |connection| {
+            connection.execute("DELETE FROM dialogs")?;
+            let mut statement =
+                connection.prepare("INSERT INTO dialogs (id, position, data) VALUES (?, ?, ?)")?;
+            for (position, (id, data)) in rows.iter().enumerate() {
+                statement.reset()?;
+                statement.bind((1, *id))?;
+                statement.bind((2, position as i64))?;
+                statement.bind((3, &data[..]))?;
+                statement.next()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Up to `limit` cached messages older than `before_id` (or the newest ones when
+    /// `None`), oldest first.
+    pub fn messages(
+        &self,
+        chat_id: i64,
+        before_id: Option<i32>,
+        limit: usize,
+    ) -> Result<Vec<WsMessage>, anyhow::Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT id, data FROM messages WHERE chat_id = ? AND id < ? ORDER BY id DESC LIMIT ?",
+        )?;
+        statement.bind((1, chat_id))?;
+        statement.bind((2, before_id.map_or(i64::MAX, i64::from)))?;
+        statement.bind((3, limit as i64))?;
+
+        let mut messages = Vec::new();
+        while let State::Row = statement.next()? {
+            let id = statement.read::<i64, _>(0)?;
+            let data = statement.read::<Vec<u8>, _>(1)?;
+            match self.open_row(&message_aad(chat_id, id), &data) {
+                Ok(message) => messages.push(message),
+                Err(e) => warn!(
+                    "Skipping unreadable cached message {}/{}: {}",
+                    chat_id, id, e
+                ),
+            }
+        }
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// Stores a page fetched from Telegram and drops cached messages in the range it
+    /// covers that are no longer there, i.e. deleted while nobody was listening.
+    pub fn store_page(
+        &self,
+        chat_id: i64,
+        before_id: Option<i32>,
+        page: &[WsMessage],
+        has_more: bool,
+    ) -> Result<(), anyhow::Error> {
+        let upper = before_id.map_or(i64::MAX, |id| i64::from(id) - 1);
+        let lower = match page.first() {
+            _ if !has_more => i64::MIN,
+            Some(oldest) => i64::from(oldest.id),
+            // An empty page that claims more history says nothing about the range.
+            None => return Ok(()),
+        };
+        let rows = self.seal_messages(chat_id, page)?;
+
+        let connection = self.connection.lock().unwrap();
+        transaction(&connection, |connection| {
+            let mut statement = connection
+                .prepare("DELETE FROM messages WHERE chat_id = ? AND id BETWEEN ? AND ?")?;
+            statement.bind((1, chat_id))?;
+            statement.bind((2, lower))?;
+            statement.bind((3, upper))?;
+            statement.next()?;
+            insert_messages(connection, chat_id, &rows)
+        })
+    }
+
+    /// Adds or replaces single messages, e.g. new, sent or edited ones.
+    pub fn store_messages(
+        &self,
+        chat_id: i64,
+        messages: &[WsMessage],
+    ) -> Result<(), anyhow::Error> {
+        let rows = self.seal_messages(chat_id, messages)?;
+        let connection = self.connection.lock().unwrap();
+        transaction(&connection, |connection| {
+            insert_messages(connection, chat_id, &rows)
+        })
+    }
+
+    /// Forgets deleted messages. Without a chat the ids are account-wide, which
+    /// holds everywhere except in channels.
+    pub fn delete_messages(&self, chat_id: Option<i64>, ids: &[i32]) -> Result<(), anyhow::Error> {
+        let connection = self.connection.lock().unwrap();
+        transaction(&connection, |connection| {
+            let mut statement = match chat_id {
+                Some(_) => {
+                    connection.prepare("DELETE FROM messages WHERE chat_id = ? AND id = ?")?
+                }
+                None => connection.prepare("DELETE FROM messages WHERE chat_id > ? AND id = ?")?,
+            };
+            for &id in ids {
+                statement.reset()?;
+                statement.bind((1, chat_id.unwrap_or(CHANNEL_ID_LIMIT)))?;
+                statement.bind((2, i64::from(id)))?;
+                statement.next()?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Drops everything, e.g. when the account signs out.
+    pub fn clear(&self) -> Result<(), anyhow::Error> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute("DELETE FROM dialogs; DELETE FROM messages; VACUUM;")?;
+        Ok(())
+    }
+
+    fn seal_messages(
+        &self,
+        chat_id: i64,
+        messages: &[WsMessage],
+    ) -> Result<Vec<(i64, Vec<u8>)>, anyhow::Error> {
+        messages
+            .iter()
+            // Pending copies have no Telegram id yet.
+            .filter(|message| message.id > 0)
+            .map(|message| {
+                let id = i64::from(message.id);
+                Ok((id, self.seal_row(&message_aad(chat_id, id), message)?))
+            })
+            .collect()
+    }
+
+    /// `iv || ciphertext || tag`, with the row's identity mixed into the tag so rows
+    /// cannot be swapped around.
+    fn seal_row<T: serde::Serialize>(
+        &self,
+        aad: &str,
+        value: &T,
+    ) -> Result<Vec<u8>, anyhow::Error> {
+        let mut data = serde_json::to_vec(value)?;
+        let iv: [u8; IV_LEN] = rand::random();
+        Aes256Ctr::new(&self.enc_key.into(), &iv.into()).apply_keystream(&mut data);
+
+        let tag = self.tag(aad, &iv, &data);
+        let mut sealed = Vec::with_capacity(IV_LEN + data.len() + TAG_LEN);
+        sealed.extend_from_slice(&iv);
+        sealed.extend_from_slice(&data);
+        sealed.extend_from_slice(&tag);
+        Ok(sealed)
+    }
+
+    fn open_row<T: serde::de::DeserializeOwned>(
+        &self,
+        aad: &str,
+        sealed: &[u8],
+    ) -> Result<T, anyhow::Error> {
+        if sealed.len() < IV_LEN + TAG_LEN {
+            return Err(anyhow::anyhow!("Cache entry is truncated"));
+        }
+        let (iv, rest) = sealed.split_at(IV_LEN);
+        let (data, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+        if self.mac(aad, iv, data).verify_slice(tag).is_err() {
+            return Err(anyhow::anyhow!("Cache entry failed authentication"));
+        }
+
+        let iv: [u8; IV_LEN] = iv.try_into()?;
+        let mut data = data.to_vec();
+        Aes256Ctr::new(&self.enc_key.into(), &iv.into()).apply_keystream(&mut data);
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    fn tag(&self, aad: &str, iv: &[u8], data: &[u8]) -> Vec<u8> {
+        self.mac(aad, iv, data).finalize().into_bytes().to_vec()
+    }
+
+    fn mac(&self, aad: &str, iv: &[u8], data: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.mac_key).expect("HMAC takes any key length");
+        mac.update(aad.as_bytes());
+        mac.update(iv);
+        mac.update(data);
+        mac
+    }
+}
+
+fn insert_messages(
+    connection: &sqlite::Connection,
+    chat_id: i64,
+    rows: &[(i64, Vec<u8>)],
+) -> Result<(), anyhow::Error> {
+    let mut statement = connection
+        .prepare("INSERT OR REPLACE INTO messages (chat_id, id, data) VALUES (?, ?, ?)")?;
+    for (id, data) in rows {
+        statement.reset()?;
+        statement.bind((1, chat_id))?;
+        statement.bind((2, *id))?;
+        statement.bind((3, &data[..]))?;
+        statement.next()?;
+    }
+    Ok(())
+}
+
+/// Runs `f` in a transaction, rolling back if it fails.
+fn transaction(
+    connection: &sqlite::Connection,
+    f: impl FnOnce(&sqlite::Connection) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    connection.execute("BEGIN")?;
+    match f(connection) {
+        Ok(()) => {
+            connection.execute("COMMIT")?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = connection.execute("ROLLBACK");
+            Err(e)
+        }
+    }
+}
+
+fn dialog_aad(id: i64) -> String {
+    format!("dialog:{}", id)
+}
+
+fn message_aad(chat_id: i64, id: i64) -> String {
+    format!("message:{}:{}", chat_id, id)
+}
+
+/// The secret every account's cache is keyed from. The backend refuses to start
+/// without it, since a key stored beside the rows would protect nothing.
+pub fn secret_from_env() -> Result<String, anyhow::Error> {
+    match std::env::var("MESSAGE_CACHE_KEY") {
+        Ok(secret) if !secret.is_empty() => Ok(secret),
+        _ => Err(anyhow::anyhow!(
+            "MESSAGE_CACHE_KEY is not set; generate one with `openssl rand -hex 32`"
+        )),
+    }
+}
+
+/// Separate encryption and MAC keys, expanded from the secret with HKDF-SHA256.
+fn derive_keys(secret: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let hkdf = Hkdf::<Sha256>::new(Some(b"wgram-message-cache"), secret);
+    let mut enc_key = [0; 32];
+    let mut mac_key = [0; 32];
+    hkdf.expand(b"enc", &mut enc_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    hkdf.expand(b"mac", &mut mac_key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    (enc_key, mac_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(secret: &[u8]) -> MessageCache {
+        MessageCache::open_with_secret(Path::new(":memory:"), secret).unwrap()
+    }
+
+    fn message(id: i32) -> WsMessage {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "text": format!("message {}", id),
+            "sender_name": "Alice",
+            "is_outgoing": false,
+            "timestamp": 1_700_000_000 + i64::from(id),
+        }))
+        .unwrap()
+    }
+
+    fn cached_ids(cache: &MessageCache, chat_id: i64) -> Vec<i32> {
+        let messages = cache.messages(chat_id, None, 100).unwrap();
+        messages.iter().map(|m| m.id).collect()
+    }
+
+    #[test]
+    fn sealed_rows_open_again() {
+        let cache = cache(b"secret");
+        let sealed = cache.seal_row("message:1:2", &message(2)).unwrap();
+        assert!(!sealed.windows(9).any(|w| w == b"message 2"));
+
+        let opened: WsMessage = cache.open_row("message:1:2", &sealed).unwrap();
+        assert_eq!(opened, message(2));
+    }
+
+    #[test]
+    fn tampered_rows_are_rejected() {
+        let cache = cache(b"secret");
+        let sealed = cache.seal_row("message:1:2", &message(2)).unwrap();
+
+        let mut flipped = sealed.clone();
+        flipped[IV_LEN] ^= 1;
+        assert!(cache.open_row::<WsMessage>("message:1:2", &flipped).is_err());
+        assert!(cache.open_row::<WsMessage>("message:1:3", &sealed).is_err());
+        assert!(cache.open_row::<WsMessage>("message:1:2", &sealed[..IV_LEN]).is_err());
+        assert!(MessageCache::open_with_secret(Path::new(":memory:"), b"other")
+            .unwrap()
+            .open_row::<WsMessage>("message:1:2", &sealed)
+            .is_err());
+    }
+
+    #[test]
+    fn derives_separate_keys_from_the_secret() {
+        let (enc_key, mac_key) = derive_keys(b"secret");
+        assert_ne!(enc_key, mac_key);
+        assert_eq!(derive_keys(b"secret"), (enc_key, mac_key));
+        assert_ne!(derive_keys(b"other").0, enc_key);
+    }
+
+    #[test]
+    fn store_page_replaces_the_range_it_covers() {
+        let cache = cache(b"secret");
+        let all: Vec<WsMessage> = (1..=6).map(message).collect();
+        cache.store_messages(10, &all).unwrap();
+        cache.store_messages(20, &all).unwrap();
+
+        // Newest page, 5 deleted meanwhile: only ids from 3 up are covered.
+        cache.store_page(10, None, &[message(3), message(4), message(6)], true).unwrap();
+        assert_eq!(cached_ids(&cache, 10), vec![1, 2, 3, 4, 6]);
+
+        // The last page reaches the start of the chat, so everything older goes.
+        cache.store_page(10, Some(3), &[message(2)], false).unwrap();
+        assert_eq!(cached_ids(&cache, 10), vec![2, 3, 4, 6]);
+
+        // An empty page with more history says nothing.
+        cache.store_page(10, Some(2), &[], true).unwrap();
+        assert_eq!(cached_ids(&cache, 10), vec![2, 3, 4, 6]);
+
+        assert_eq!(cached_ids(&cache, 20), vec![1, 2, 3, 4, 5, 6]);
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use wgram_protocol::WsTask;
+
+use crate::accounts::Account;
+use crate::telegram::TelegramManager;
+
+/// How often the task store is checked; a reminder goes out at most this late.
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Posts of one reminder that may fail before it is given up on. Between attempts
+/// the wait doubles, starting at one check, so about 15 minutes are covered.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Failed posts of a reminder, keyed by task and due date so that moving the due
+/// date starts over.
+#[derive(Default)]
+struct Failures(HashMap<(i64, i64), (u32, i64)>);
+
+impl Failures {
+    /// Whether the reminder is still backing off after a failure.
+    fn waiting(&self, key: (i64, i64), now: i64) -> bool {
+        self.0
+            .get(&key)
+            .is_some_and(|&(_, retry_at)| now < retry_at)
+    }
+
+    /// Records a failure and returns whether the reminder should be given up on.
+    fn record(&mut self, key: (i64, i64), now: i64) -> bool {
+        let (attempts, retry_at) = self.0.entry(key).or_default();
+        *attempts += 1;
+        let delay = CHECK_INTERVAL.as_secs() as i64 * (1 << (*attempts - 1));
+        *retry_at = now + delay;
+        *attempts >= MAX_ATTEMPTS
+    }
+
+    /// Forgets reminders that are no longer due, posted or not.
+    fn retain_due(&mut self, due: &[(i64, i64)]) {
+        self.0.retain(|key, _| due.contains(key));
+    }
+}
+
+/// Starts the reminder scheduler of an account. It stops once the account is closed
+/// on logout, and holds the account weakly so it never outlives it either.
+pub fn spawn(account: &Account, closed: CancellationToken) {
+    tokio::spawn(run(Arc::downgrade(account), closed));
+}
+
+async fn run(account: Weak<RwLock<TelegramManager>>, closed: CancellationToken) {
+    info!("Starting task reminder scheduler");
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    let mut failures = Failures::default();
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = closed.cancelled() => break,
+        }
+        let Some(account) = account.upgrade() else {
+            break;
+        };
+        if let Err(e) = remind_due(&account, &mut failures).await {
+            warn!("Failed to send task reminders: {}", e);
+        }
+    }
+
+    info!("Task reminder scheduler stopped");
+}
+
+/// Posts the reminders that are due. The account is locked for one step at a time,
+/// so a slow post never holds up a login or logout for the whole round.
+async fn remind_due(account: &Account, failures: &mut Failures) -> Result<(), anyhow::Error> {
+    let now = chrono::Utc::now().timestamp();
+    let due = account.read().await.tasks().due(now)?;
+    failures.retain_due(&due.iter().map(reminder_key).collect::<Vec<_>>());
+    // Checked only when something is due, since it asks Telegram. Reminders wait
+    // for the next sign-in rather than being dropped.
+    if due.is_empty() || !account.read().await.is_authorized().await? {
+        return Ok(());
+    }
+
+    for task in due {
+        let key = reminder_key(&task);
+        if failures.waiting(key, now) {
+            continue;
+        }
+        let telegram = account.read().await;
+        match telegram.post_reminder(&task).await {
+            Ok(()) => telegram.tasks().mark_reminded(&task, now)?,
+            Err(e) if failures.record(key, now) => {
+                warn!(
+                    "Giving up on the reminder for task {} after {} attempts: {}",
+                    task.id, MAX_ATTEMPTS, e
+                );
+                telegram.tasks().mark_reminded(&task, now)?;
+            }
+            Err(e) => warn!("Failed to post reminder for task {}: {}", task.id, e),
+        }
+    }
+    Ok(())
+}
+
+fn reminder_key(task: &WsTask) -> (i64, i64) {
+    (task.id, task.due_at.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_reminders_back_off_and_are_given_up() {
+        let mut failures = Failures::default();
+        let key = (1, 100);
+        assert!(!failures.waiting(key, 0));
+
+        assert!(!failures.record(key, 0));
+        assert!(failures.waiting(key, 29));
+        assert!(!failures.waiting(key, 30));
+
+        assert!(!failures.record(key, 30));
+        assert!(failures.waiting(key, 89));
+        assert!(!failures.waiting(key, 90));
+
+        for attempt in 3..MAX_ATTEMPTS {
+            assert!(
+                !failures.record(key, 90),
+                "gave up after {} attempts",
+                attempt
+            );
+        }
+        assert!(failures.record(key, 90));
+    }
+
+    #[test]
+    fn moving_the_due_date_forgets_failures() {
+        let mut failures = Failures::default();
+        failures.record((1, 100), 0);
+        failures.retain_due(&[(1, 200)]);
+        assert!(!failures.waiting((1, 100), 0));
+        assert!(failures.0.is_empty());
+    }
+}
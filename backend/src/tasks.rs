@@ -0,0 +1,533 @@
+use sqlite::State;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use tracing::info;
+use wgram_protocol::{TaskChanges, TaskPriority, WsParticipant, WsTask};
+
+const EVENTS_CHANNEL_CAPACITY: usize = 64;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tasks (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        text TEXT NOT NULL,
+        user_name TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        completed INTEGER NOT NULL DEFAULT 0,
+        chat_id INTEGER,
+        message_id INTEGER,
+        updated_at INTEGER NOT NULL DEFAULT 0,
+        completed_at INTEGER,
+        due_at INTEGER,
+        priority TEXT NOT NULL DEFAULT 'normal',
+        tags TEXT NOT NULL DEFAULT '[]',
+        notes TEXT NOT NULL DEFAULT '',
+        assignee_id INTEGER,
+        assignee_name TEXT,
+        remind_in_chat INTEGER NOT NULL DEFAULT 0,
+        reminded_at INTEGER
+    );
+";
+
+/// Columns added since the table was first created, for stores made before them.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("chat_id", "INTEGER"),
+    ("message_id", "INTEGER"),
+    ("updated_at", "INTEGER NOT NULL DEFAULT 0"),
+    ("completed_at", "INTEGER"),
+    ("due_at", "INTEGER"),
+    ("priority", "TEXT NOT NULL DEFAULT 'normal'"),
+    ("tags", "TEXT NOT NULL DEFAULT '[]'"),
+    ("notes", "TEXT NOT NULL DEFAULT ''"),
+    ("assignee_id", "INTEGER"),
+    ("assignee_name", "TEXT"),
+    ("remind_in_chat", "INTEGER NOT NULL DEFAULT 0"),
+    ("reminded_at", "INTEGER"),
+];
+
+const COLUMNS: &str = "id, text, user_name, created_at, completed, chat_id, message_id, \
+    updated_at, completed_at, due_at, priority, tags, notes, assignee_id, assignee_name, \
+    remind_in_chat";
+
+/// A write to the task list, fanned out to every WebSocket of the account.
+#[derive(Debug, Clone)]
+pub enum TaskEvent {
+    Saved(Box<WsTask>),
+    Deleted(i64),
+    Reminder(Box<WsTask>),
+}
+
+/// The account's tasks, kept in SQLite next to its session. Unlike the message cache
+/// this is the only copy, so it survives signing out.
+pub struct TaskStore {
+    connection: Mutex<sqlite::Connection>,
+    events_tx: broadcast::Sender<TaskEvent>,
+}
+
+impl TaskStore {
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        let connection = sqlite::open(path)?;
+        connection.execute(SCHEMA)?;
+        add_missing_columns(&connection)?;
+        info!("Task store at {:?}", path);
+
+        let (events_tx, _) = broadcast::channel(EVENTS_CHANNEL_CAPACITY);
+        Ok(Self {
+            connection: Mutex::new(connection),
+            events_tx,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskEvent> {
+        self.events_tx.subscribe()
+    }
+
+    /// Every task, oldest first.
+    pub fn list(&self) -> Result<Vec<WsTask>, anyhow::Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare(format!("SELECT {} FROM tasks ORDER BY id", COLUMNS))?;
+
+        let mut tasks = Vec::new();
+        while let State::Row = statement.next()? {
+            tasks.push(read_task(&statement)?);
+        }
+        Ok(tasks)
+    }
+
+    pub fn create(
+        &self,
+        text: &str,
+        user_name: &str,
+        chat_id: Option<i64>,
+        message_id: Option<i32>,
+    ) -> Result<WsTask, anyhow::Error> {
+        let task = {
+            let connection = self.connection.lock().unwrap();
+            let created_at = chrono::Utc::now().timestamp();
+
+            let mut statement = connection.prepare(
+                "INSERT INTO tasks (text, user_name, created_at, updated_at, chat_id, message_id)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )?;
+            statement.bind((1, text))?;
+            statement.bind((2, user_name))?;
+            statement.bind((3, created_at))?;
+            statement.bind((4, created_at))?;
+            statement.bind((5, chat_id))?;
+            statement.bind((6, message_id.map(i64::from)))?;
+            statement.next()?;
+
+            let mut statement = connection.prepare("SELECT last_insert_rowid()")?;
+            statement.next()?;
+            WsTask {
+                id: statement.read::<i64, _>(0)?,
+                text: text.to_string(),
+                user_name: user_name.to_string(),
+                chat_id,
+                message_id,
+                created_at,
+                updated_at: created_at,
+                completed: false,
+                completed_at: None,
+                due_at: None,
+                priority: TaskPriority::default(),
+                tags: Vec::new(),
+                notes: String::new(),
+                assignee: None,
+                remind_in_chat: false,
+            }
+        };
+
+        let _ = self
+            .events_tx
+            .send(TaskEvent::Saved(Box::new(task.clone())));
+        Ok(task)
+    }
+
+    /// Applies `changes` and stamps the task as updated. Moving the due date arms its
+    /// reminder again. Returns `None` if there is no such task.
+    pub fn update(&self, id: i64, changes: TaskChanges) -> Result<Option<WsTask>, anyhow::Error> {
+        let task = {
+            let connection = self.connection.lock().unwrap();
+            let mut statement =
+                connection.prepare(format!("SELECT {} FROM tasks WHERE id = ?", COLUMNS))?;
+            statement.bind((1, id))?;
+            if statement.next()? != State::Row {
+                return Ok(None);
+            }
+            let mut task = read_task(&statement)?;
+            apply_changes(&mut task, changes, chrono::Utc::now().timestamp());
+
+            let mut statement = connection.prepare(
+                "UPDATE tasks SET text = ?, completed = ?, updated_at = ?, completed_at = ?,
+                     due_at = ?, priority = ?, tags = ?, notes = ?,
+                     assignee_id = ?, assignee_name = ?, remind_in_chat = ?,
+                     reminded_at = CASE WHEN due_at IS ? THEN reminded_at END
+                 WHERE id = ?",
+            )?;
+            statement.bind((1, task.text.as_str()))?;
+            statement.bind((2, task.completed as i64))?;
+            statement.bind((3, task.updated_at))?;
+            statement.bind((4, task.completed_at))?;
+            statement.bind((5, task.due_at))?;
+            statement.bind((6, priority_name(task.priority)))?;
+            statement.bind((7, serde_json::to_string(&task.tags)?.as_str()))?;
+            statement.bind((8, task.notes.as_str()))?;
+            statement.bind((9, task.assignee.as_ref().map(|a| a.user_id)))?;
+            statement.bind((10, task.assignee.as_ref().map(|a| a.name.as_str())))?;
+            statement.bind((11, task.remind_in_chat as i64))?;
+            // Compared against the due date from before this update.
+            statement.bind((12, task.due_at))?;
+            statement.bind((13, id))?;
+            statement.next()?;
+            task
+        };
+
+        let _ = self
+            .events_tx
+            .send(TaskEvent::Saved(Box::new(task.clone())));
+        Ok(Some(task))
+    }
+
+    /// Returns `false` if there was no such task.
+    pub fn delete(&self, id: i64) -> Result<bool, anyhow::Error> {
+        let deleted = {
+            let connection = self.connection.lock().unwrap();
+            let mut statement = connection.prepare("DELETE FROM tasks WHERE id = ?")?;
+            statement.bind((1, id))?;
+            statement.next()?;
+            connection.change_count() > 0
+        };
+
+        if deleted {
+            let _ = self.events_tx.send(TaskEvent::Deleted(id));
+        }
+        Ok(deleted)
+    }
+
+    /// Open tasks due by `now` whose reminder has not gone out yet, soonest first.
+    pub fn due(&self, now: i64) -> Result<Vec<WsTask>, anyhow::Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(format!(
+            "SELECT {} FROM tasks
+             WHERE completed = 0 AND due_at <= ? AND reminded_at IS NULL
+             ORDER BY due_at, id",
+            COLUMNS
+        ))?;
+        statement.bind((1, now))?;
+
+        let mut tasks = Vec::new();
+        while let State::Row = statement.next()? {
+            tasks.push(read_task(&statement)?);
+        }
+        Ok(tasks)
+    }
+
+    /// Records that the reminder for `task` went out and tells every client about it.
+    pub fn mark_reminded(&self, task: &WsTask, at: i64) -> Result<(), anyhow::Error> {
+        {
+            let connection = self.connection.lock().unwrap();
+            let mut statement =
+                connection.prepare("UPDATE tasks SET reminded_at = ? WHERE id = ?")?;
+            statement.bind((1, at))?;
+            statement.bind((2, task.id))?;
+            statement.next()?;
+        }
+
+        let _ = self
+            .events_tx
+            .send(TaskEvent::Reminder(Box::new(task.clone())));
+        Ok(())
+    }
+}
+
+fn apply_changes(task: &mut WsTask, changes: TaskChanges, now: i64) {
+    let TaskChanges {
+        text,
+        completed,
+        due_at,
+        priority,
+        tags,
+        notes,
+        assignee,
+        remind_in_chat,
+    } = changes;
+
+    if let Some(text) = text {
+        task.text = text;
+    }
+    if let Some(completed) = completed {
+        if completed != task.completed {
+            task.completed_at = completed.then_some(now);
+        }
+        task.completed = completed;
+    }
+    if let Some(due_at) = due_at {
+        task.due_at = due_at;
+    }
+    if let Some(priority) = priority {
+        task.priority = priority;
+    }
+    if let Some(tags) = tags {
+        task.tags = tags
+            .into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+    }
+    if let Some(notes) = notes {
+        task.notes = notes;
+    }
+    if let Some(assignee) = assignee {
+        task.assignee = assignee;
+    }
+    if let Some(remind_in_chat) = remind_in_chat {
+        task.remind_in_chat = remind_in_chat;
+    }
+    task.updated_at = now;
+}
+
+fn read_task(statement: &sqlite::Statement) -> Result<WsTask, anyhow::Error> {
+    let created_at = statement.read::<i64, _>(3)?;
+    let assignee_id = statement.read::<Option<i64>, _>(13)?;
+    let assignee_name = statement.read::<Option<String>, _>(14)?;
+
+    Ok(WsTask {
+        id: statement.read::<i64, _>(0)?,
+        text: statement.read::<String, _>(1)?,
+        user_name: statement.read::<String, _>(2)?,
+        created_at,
+        completed: statement.read::<i64, _>(4)? != 0,
+        chat_id: statement.read::<Option<i64>, _>(5)?,
+        message_id: statement.read::<Option<i64>, _>(6)?.map(|id| id as i32),
+        // Tasks from before the column have no update time of their own.
+        updated_at: statement.read::<i64, _>(7)?.max(created_at),
+        completed_at: statement.read::<Option<i64>, _>(8)?,
+        due_at: statement.read::<Option<i64>, _>(9)?,
+        priority: parse_priority(&statement.read::<String, _>(10)?),
+        tags: serde_json::from_str(&statement.read::<String, _>(11)?).unwrap_or_default(),
+        notes: statement.read::<String, _>(12)?,
+        assignee: assignee_id.map(|user_id| WsParticipant {
+            user_id,
+            name: assignee_name.unwrap_or_default(),
+        }),
+        remind_in_chat: statement.read::<i64, _>(15)? != 0,
+    })
+}
+
+fn priority_name(priority: TaskPriority) -> &'static str {
+    match priority {
+        TaskPriority::Low => "low",
+        TaskPriority::Normal => "normal",
+        TaskPriority::High => "high",
+    }
+}
+
+fn parse_priority(name: &str) -> TaskPriority {
+    match name {
+        "low" => TaskPriority::Low,
+        "high" => TaskPriority::High,
+        _ => TaskPriority::Normal,
+    }
+}
+
+fn add_missing_columns(connection: &sqlite::Connection) -> Result<(), anyhow::Error> {
+    let mut existing = Vec::new();
+    let mut statement = connection.prepare("PRAGMA table_info(tasks)")?;
+    while let State::Row = statement.next()? {
+        existing.push(statement.read::<String, _>(1)?);
+    }
+
+    for (name, definition) in ADDED_COLUMNS {
+        if !existing.iter().any(|column| column.as_str() == *name) {
+            info!("Adding column {} to the task store", name);
+            connection.execute(format!(
+                "ALTER TABLE tasks ADD COLUMN {} {}",
+                name, definition
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> TaskStore {
+        TaskStore::open(Path::new(":memory:")).unwrap()
+    }
+
+    fn set_due(store: &TaskStore, id: i64, due_at: Option<i64>) -> WsTask {
+        let changes = TaskChanges {
+            due_at: Some(due_at),
+            ..TaskChanges::default()
+        };
+        store.update(id, changes).unwrap().unwrap()
+    }
+
+    #[test]
+    fn creates_updates_and_deletes_tasks() {
+        let store = store();
+        let mut events = store.subscribe();
+
+        let task = store
+            .create("Buy milk", "Alice", Some(42), Some(7))
+            .unwrap();
+        assert_eq!(task.text, "Buy milk");
+        assert!(!task.completed);
+        assert!(matches!(events.try_recv(), Ok(TaskEvent::Saved(saved)) if *saved == task));
+
+        let changes = TaskChanges {
+            text: Some("Buy oat milk".to_string()),
+            completed: Some(true),
+            ..TaskChanges::default()
+        };
+        let updated = store.update(task.id, changes).unwrap().unwrap();
+        assert_eq!(updated.text, "Buy oat milk");
+        assert!(updated.completed);
+        assert!(matches!(events.try_recv(), Ok(TaskEvent::Saved(saved)) if *saved == updated));
+        assert_eq!(store.list().unwrap(), vec![updated]);
+        assert!(store
+            .update(task.id + 1, TaskChanges::default())
+            .unwrap()
+            .is_none());
+
+        assert!(store.delete(task.id).unwrap());
+        assert!(matches!(events.try_recv(), Ok(TaskEvent::Deleted(id)) if id == task.id));
+        assert!(!store.delete(task.id).unwrap());
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn keeps_the_message_a_task_was_made_from() {
+        let store = store();
+
+        let linked = store
+            .create("Reply to Bob", "Alice", Some(42), Some(7))
+            .unwrap();
+        assert_eq!(linked.chat_id, Some(42));
+        assert_eq!(linked.message_id, Some(7));
+
+        let unlinked = store.create("Water plants", "Alice", None, None).unwrap();
+        assert_eq!(unlinked.chat_id, None);
+        assert_eq!(unlinked.message_id, None);
+
+        assert_eq!(store.list().unwrap(), vec![linked, unlinked]);
+    }
+
+    #[test]
+    fn updates_due_dates_priority_and_tags() {
+        let store = store();
+        let task = store.create("Pay rent", "Alice", None, None).unwrap();
+
+        let changes = TaskChanges {
+            due_at: Some(Some(500)),
+            priority: Some(TaskPriority::High),
+            tags: Some(vec![" home ".to_string(), " ".to_string()]),
+            completed: Some(true),
+            ..TaskChanges::default()
+        };
+        let updated = store.update(task.id, changes).unwrap().unwrap();
+        assert_eq!(updated.due_at, Some(500));
+        assert_eq!(updated.priority, TaskPriority::High);
+        assert_eq!(updated.tags, vec!["home".to_string()]);
+        assert!(updated.completed_at.is_some());
+
+        let changes = TaskChanges {
+            due_at: Some(None),
+            completed: Some(false),
+            ..TaskChanges::default()
+        };
+        let reopened = store.update(task.id, changes).unwrap().unwrap();
+        assert_eq!(reopened.due_at, None);
+        assert_eq!(reopened.completed_at, None);
+        assert_eq!(store.list().unwrap(), vec![reopened]);
+    }
+
+    #[test]
+    fn migrates_stores_from_before_added_columns() {
+        let path = std::env::temp_dir().join(format!("wgram-tasks-{}.db", uuid::Uuid::new_v4()));
+        {
+            let connection = sqlite::open(&path).unwrap();
+            connection
+                .execute(
+                    "CREATE TABLE tasks (
+                         id INTEGER PRIMARY KEY AUTOINCREMENT,
+                         text TEXT NOT NULL,
+                         user_name TEXT NOT NULL,
+                         created_at INTEGER NOT NULL,
+                         completed INTEGER NOT NULL DEFAULT 0
+                     );
+                     INSERT INTO tasks (text, user_name, created_at) VALUES ('Old', 'Bob', 100);",
+                )
+                .unwrap();
+        }
+
+        let tasks = TaskStore::open(&path).unwrap().list().unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(tasks.len(), 1);
+        let task = &tasks[0];
+        assert_eq!(task.text, "Old");
+        assert_eq!(task.chat_id, None);
+        assert_eq!(task.message_id, None);
+        assert_eq!(task.updated_at, 100);
+        assert_eq!(task.priority, TaskPriority::Normal);
+        assert!(task.tags.is_empty());
+        assert!(!task.remind_in_chat);
+    }
+
+    #[test]
+    fn due_lists_open_unreminded_tasks_soonest_first() {
+        let store = store();
+        let later = store.create("Later", "Alice", Some(1), Some(1)).unwrap();
+        let sooner = store.create("Sooner", "Alice", Some(1), Some(2)).unwrap();
+        let done = store.create("Done", "Alice", Some(1), Some(3)).unwrap();
+        let future = store.create("Future", "Alice", Some(1), Some(4)).unwrap();
+        store.create("Undated", "Alice", Some(1), Some(5)).unwrap();
+
+        set_due(&store, later.id, Some(200));
+        set_due(&store, sooner.id, Some(100));
+        set_due(&store, done.id, Some(100));
+        store
+            .update(
+                done.id,
+                TaskChanges {
+                    completed: Some(true),
+                    ..TaskChanges::default()
+                },
+            )
+            .unwrap();
+        set_due(&store, future.id, Some(1000));
+
+        let due: Vec<i64> = store.due(500).unwrap().iter().map(|t| t.id).collect();
+        assert_eq!(due, vec![sooner.id, later.id]);
+    }
+
+    #[test]
+    fn mark_reminded_holds_until_the_due_date_moves() {
+        let store = store();
+        let task = store.create("Call", "Alice", Some(1), Some(1)).unwrap();
+        let task = set_due(&store, task.id, Some(100));
+        let mut events = store.subscribe();
+
+        store.mark_reminded(&task, 150).unwrap();
+        assert!(matches!(events.try_recv(), Ok(TaskEvent::Reminder(t)) if t.id == task.id));
+        assert!(store.due(500).unwrap().is_empty());
+
+        // Other edits leave the reminder as sent.
+        let changes = TaskChanges {
+            notes: Some("ring twice".to_string()),
+            ..TaskChanges::default()
+        };
+        store.update(task.id, changes).unwrap();
+        set_due(&store, task.id, Some(100));
+        assert!(store.due(500).unwrap().is_empty());
+
+        // A new due date arms it again.
+        set_due(&store, task.id, Some(300));
+        assert_eq!(store.due(500).unwrap().len(), 1);
+    }
+}
@@ -1,30 +1,134 @@
 use grammers_client::{
-    types::{Dialog, IterBuffer, Message},
-    Client, SignInError,
+    grammers_tl_types as tl,
+    session::defs::{PeerAuth, PeerId, PeerKind, PeerRef},
+    types::{
+        photo_sizes::PhotoSize, Dialog, InputMessage, IterBuffer, Media, Message,
+    },
+    Client, InvocationError, SignInError, Update, UpdatesConfiguration,
 };
 use grammers_mtsender::SenderPool;
-use grammers_session::storages::SqliteSession;
+use grammers_session::{storages::SqliteSession, updates::UpdatesLike, Session};
 use std::collections::HashMap;
-use std::sync::Arc;
+use crate::message_cache::MessageCache;
+use crate::tasks::TaskStore;
+use wgram_protocol::{ParseMode, SearchFilter, TypingAction, WsParticipant, WsPresence, WsTask};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncRead;
+use tokio::sync::{broadcast, mpsc};
 use tokio::task::JoinHandle;
-use tracing::{info, warn};
-use uuid::Uuid;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+const UPDATES_CHANNEL_CAPACITY: usize = 256;
+
+/// Offset the Bot API adds to channel ids (`-100` prefix) to keep them apart from groups.
+const CHANNEL_ID_OFFSET: i64 = 1_000_000_000_000;
+
+/// What the client has to do next after submitting a login code.
+#[derive(Debug, Clone)]
+pub enum LoginStep {
+    Password { hint: Option<String> },
+    SignedIn,
+}
+
+/// Telegram events fanned out to every connected WebSocket.
+#[derive(Debug, Clone)]
+pub enum TelegramUpdate {
+    NewMessage(Message),
+    MessageEdited(Message),
+    MessagesDeleted {
+        channel_id: Option<i64>,
+        message_ids: Vec<i32>,
+    },
+    /// Incoming messages were read, possibly on another device.
+    ReadInbox {
+        chat_id: i64,
+        max_id: i32,
+        unread_count: i32,
+    },
+    /// The other side read our outgoing messages.
+    ReadOutbox {
+        chat_id: i64,
+        max_id: i32,
+    },
+    /// Someone is typing, recording or uploading in a chat.
+    UserTyping {
+        chat_id: i64,
+        user_id: i64,
+        action: TypingAction,
+    },
+    /// A user went online or offline.
+    UserStatus {
+        user_id: i64,
+        presence: WsPresence,
+    },
+}
 
 pub struct TelegramManager {
-    api_id: i32,
     api_hash: String,
     session_path: String,
     session: Arc<SqliteSession>,
     client: Client,
     _runner_handle: JoinHandle<()>,
-    sessions: HashMap<String, String>,
+    _updates_handle: JoinHandle<()>,
+    updates_tx: broadcast::Sender<TelegramUpdate>,
     pending_login_tokens: HashMap<String, grammers_client::types::LoginToken>,
     pending_password_tokens: HashMap<String, grammers_client::types::PasswordToken>,
-    chat_map: HashMap<i64, grammers_client::types::Peer>,
+    peer_cache: Mutex<HashMap<i64, PeerRef>>,
+    /// `None` if the cache could not be opened; everything then goes to Telegram.
+    message_cache: Option<MessageCache>,
+    tasks: TaskStore,
+    /// Cancelled when the account is logged out, so its sockets and tasks stop using it.
+    closed: CancellationToken,
+}
+
+/// Encodes a peer as a Bot API style dialog id: users are positive, basic groups
+/// negative and channels carry the `-100` prefix, so the id alone names the peer type.
+pub fn chat_id_for_peer(peer_id: PeerId) -> i64 {
+    match peer_id.kind() {
+        PeerKind::User | PeerKind::UserSelf => peer_id.bare_id(),
+        PeerKind::Chat => -peer_id.bare_id(),
+        PeerKind::Channel => -(CHANNEL_ID_OFFSET + peer_id.bare_id()),
+    }
+}
+
+/// Inverse of [`chat_id_for_peer`].
+pub fn peer_id_for_chat(chat_id: i64) -> PeerId {
+    if chat_id > 0 {
+        PeerId::user(chat_id)
+    } else if chat_id < -CHANNEL_ID_OFFSET {
+        PeerId::channel(-chat_id - CHANNEL_ID_OFFSET)
+    } else {
+        PeerId::chat(-chat_id)
+    }
+}
+
+/// "First Last", falling back to the username for users without a name.
+fn tl_user_name(user: &tl::types::User) -> String {
+    let name = [user.first_name.as_deref(), user.last_name.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    match (name.is_empty(), &user.username) {
+        (true, Some(username)) => format!("@{}", username),
+        (true, None) => "Deleted account".to_string(),
+        (false, _) => name,
+    }
+}
+
+/// Peer type name exposed to the frontend alongside the chat id.
+pub fn peer_type(chat_id: i64) -> &'static str {
+    match peer_id_for_chat(chat_id).kind() {
+        PeerKind::User | PeerKind::UserSelf => "user",
+        PeerKind::Chat => "group",
+        PeerKind::Channel => "channel",
+    }
 }
 
 impl TelegramManager {
-    pub async fn new() -> Result<Self, anyhow::Error> {
+    pub async fn new(session_path: String) -> Result<Self, anyhow::Error> {
         let api_id: i32 = std::env::var("TELEGRAM_API_ID")
             .unwrap_or_else(|_| {
                 warn!("TELEGRAM_API_ID not set, using placeholder");
@@ -38,37 +142,112 @@ impl TelegramManager {
             "placeholder_hash".to_string()
         });
 
-        let session_path =
-            std::env::var("TELEGRAM_SESSION_FILE").unwrap_or_else(|_| "wgram.session".to_string());
-
         info!("Initializing Telegram manager (grammers-client 0.8)");
         info!("API ID: {}", api_id);
         info!("Session file: {}", session_path);
 
         let session = Arc::new(SqliteSession::open(&session_path)?);
 
+        let cache_path = format!("{}.cache", session_path);
+        let message_cache = match MessageCache::open(Path::new(&cache_path)) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                warn!("Message cache at {} unavailable: {}", cache_path, e);
+                None
+            }
+        };
+
+        let tasks = TaskStore::open(Path::new(&format!("{}.tasks", session_path)))?;
+
         let pool = SenderPool::new(Arc::clone(&session), api_id);
         let client = Client::new(&pool);
 
-        let SenderPool { runner, .. } = pool;
+        let SenderPool {
+            runner, updates, ..
+        } = pool;
         let runner_handle = tokio::spawn(runner.run());
 
+        let (updates_tx, _) = broadcast::channel(UPDATES_CHANNEL_CAPACITY);
+        let updates_handle = tokio::spawn(Self::run_update_loop(
+            client.clone(),
+            updates,
+            updates_tx.clone(),
+        ));
+
         info!("Telegram client initialized successfully");
 
         Ok(Self {
-            api_id,
             api_hash,
             session_path,
             session,
             client,
             _runner_handle: runner_handle,
-            sessions: HashMap::new(),
+            _updates_handle: updates_handle,
+            updates_tx,
             pending_login_tokens: HashMap::new(),
             pending_password_tokens: HashMap::new(),
-            chat_map: HashMap::new(),
+            peer_cache: Mutex::new(HashMap::new()),
+            message_cache,
+            tasks,
+            closed: CancellationToken::new(),
         })
     }
 
+    async fn run_update_loop(
+        client: Client,
+        updates: mpsc::UnboundedReceiver<UpdatesLike>,
+        updates_tx: broadcast::Sender<TelegramUpdate>,
+    ) {
+        info!("Starting Telegram update loop");
+        let mut stream = client.stream_updates(updates, UpdatesConfiguration::default());
+
+        loop {
+            let update = match stream.next().await {
+                Ok(update) => update,
+                Err(InvocationError::Dropped) => {
+                    info!("Telegram update stream closed");
+                    break;
+                }
+                Err(e) => {
+                    error!("Failed to receive Telegram update: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+
+            let event = match update {
+                Update::NewMessage(message) => TelegramUpdate::NewMessage((*message).clone()),
+                Update::MessageEdited(message) => {
+                    TelegramUpdate::MessageEdited((*message).clone())
+                }
+                Update::MessageDeleted(deletion) => TelegramUpdate::MessagesDeleted {
+                    channel_id: deletion.channel_id(),
+                    message_ids: deletion.into_messages(),
+                },
+                Update::Raw(raw) => match read_update(raw.raw) {
+                    Some(event) => event,
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            // No subscribers simply means no WebSocket is connected right now.
+            let _ = updates_tx.send(event);
+        }
+    }
+
+    pub fn message_cache(&self) -> Option<&MessageCache> {
+        self.message_cache.as_ref()
+    }
+
+    pub fn tasks(&self) -> &TaskStore {
+        &self.tasks
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TelegramUpdate> {
+        self.updates_tx.subscribe()
+    }
+
     pub async fn send_code(&mut self, phone: &str) -> Result<(), anyhow::Error> {
         info!("Requesting login code for phone: {}", phone);
 
@@ -94,7 +273,11 @@ impl TelegramManager {
         Ok(())
     }
 
-    pub async fn verify_code(&mut self, phone: &str, code: &str) -> Result<String, anyhow::Error> {
+    pub async fn verify_code(
+        &mut self,
+        phone: &str,
+        code: &str,
+    ) -> Result<LoginStep, anyhow::Error> {
         info!(
             "Verifying login code for phone: {} with code: {}",
             phone, code
@@ -106,21 +289,17 @@ impl TelegramManager {
 
         match self.client.sign_in(&token, code).await {
             Ok(_user) => {
-                let session_id = Uuid::new_v4().to_string();
-                self.sessions.insert(session_id.clone(), phone.to_string());
-
-                info!("✅ Authentication successful! Session ID: {}", session_id);
-                Ok(session_id)
+                info!("✅ Authentication successful for phone: {}", phone);
+                Ok(LoginStep::SignedIn)
             }
             Err(SignInError::PasswordRequired(password_token)) => {
                 info!("2FA password required for phone: {}", phone);
 
+                let hint = password_token.hint().map(|h| h.to_string());
                 self.pending_password_tokens
                     .insert(phone.to_string(), password_token);
 
-                Err(anyhow::anyhow!(
-                    "2FA password required. Please call verify_password."
-                ))
+                Ok(LoginStep::Password { hint })
             }
             Err(SignInError::SignUpRequired { .. }) => Err(anyhow::anyhow!(
                 "This phone number is not registered. Please sign up first."
@@ -133,57 +312,60 @@ impl TelegramManager {
         &mut self,
         phone: &str,
         password: &str,
-    ) -> Result<String, anyhow::Error> {
+    ) -> Result<(), anyhow::Error> {
         info!("Verifying 2FA password for phone: {}", phone);
 
         let token = self.pending_password_tokens.remove(phone).ok_or_else(|| {
             anyhow::anyhow!("No pending 2FA auth for this phone. Verify code first!")
         })?;
 
-        match self.client.check_password(token, password).await {
+        match self.client.check_password(token.clone(), password).await {
             Ok(_user) => {
-                let session_id = Uuid::new_v4().to_string();
-                self.sessions.insert(session_id.clone(), phone.to_string());
-
-                info!(
-                    "✅ 2FA authentication successful! Session ID: {}",
-                    session_id
-                );
-                Ok(session_id)
+                info!("✅ 2FA authentication successful for phone: {}", phone);
+                Ok(())
+            }
+            Err(SignInError::InvalidPassword) => {
+                // Keep the token so the user can retry without requesting a new code.
+                self.pending_password_tokens.insert(phone.to_string(), token);
+                Err(anyhow::anyhow!("Invalid 2FA password, please try again"))
             }
             Err(e) => Err(anyhow::anyhow!("2FA password verification failed: {}", e)),
         }
     }
 
-    pub async fn get_dialogs(&mut self) -> Result<Vec<Dialog>, anyhow::Error> {
+    pub async fn get_dialogs(&self) -> Result<Vec<Dialog>, anyhow::Error> {
         info!("Fetching dialogs...");
         let mut iter: IterBuffer<_, Dialog> = self.client.iter_dialogs();
         let mut dialogs = Vec::new();
-        let mut index = 1i64;
 
         while let Some(dialog) = iter.next().await? {
-            self.chat_map.insert(index, dialog.peer().clone());
+            self.cache_peer(PeerRef::from(dialog.peer()));
             dialogs.push(dialog);
-            index += 1;
         }
 
         info!("✅ Fetched {} dialogs", dialogs.len());
         Ok(dialogs)
     }
 
+    /// Fetches up to `limit` messages older than `before_id` (or the newest ones when
+    /// `None`), returned oldest first.
     pub async fn get_messages(
         &self,
         chat_id: i64,
+        before_id: Option<i32>,
         limit: usize,
     ) -> Result<Vec<Message>, anyhow::Error> {
-        info!("Fetching messages for chat_id: {}", chat_id);
+        info!(
+            "Fetching messages for chat_id: {} before_id: {:?}",
+            chat_id, before_id
+        );
 
-        let chat = self
-            .chat_map
-            .get(&chat_id)
-            .ok_or_else(|| anyhow::anyhow!("Chat not found for id: {}", chat_id))?;
+        let chat = self.resolve_peer(chat_id).await?;
 
-        let mut iter = self.client.iter_messages(chat);
+        let mut iter = self.client.iter_messages(chat).limit(limit);
+        if let Some(before_id) = before_id {
+            iter = iter.offset_id(before_id);
+        }
         let mut messages = Vec::new();
 
         while let Some(msg) = iter.next().await? {
@@ -203,24 +385,404 @@ impl TelegramManager {
         Ok(messages)
     }
 
-    pub fn get_session(&self, session_id: &str) -> Option<&String> {
-        self.sessions.get(session_id)
+    /// Full-text search in one chat, or in all of them when `chat_id` is `None`,
+    /// newest first. Skips the first `offset` matches and returns up to `limit`.
+    pub async fn search_messages(
+        &self,
+        query: &str,
+        chat_id: Option<i64>,
+        filter: SearchFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Message>, anyhow::Error> {
+        info!(
+            "Searching {:?} ({:?}) in chat_id: {:?} from {}",
+            query, filter, chat_id, offset
+        );
+
+        // Global search pages by rate and peer, so plain offsets are re-fetched from the start.
+        let wanted = offset + limit;
+        let mut messages = Vec::new();
+        match chat_id {
+            Some(chat_id) => {
+                let chat = self.resolve_peer(chat_id).await?;
+                let mut iter = self
+                    .client
+                    .search_messages(chat)
+                    .query(query)
+                    .filter(messages_filter(filter))
+                    .limit(wanted);
+                while let Some(msg) = iter.next().await? {
+                    messages.push(msg);
+                    if messages.len() >= wanted {
+                        break;
+                    }
+                }
+            }
+            None => {
+                let mut iter = self
+                    .client
+                    .search_all_messages()
+                    .query(query)
+                    .filter(messages_filter(filter))
+                    .limit(wanted);
+                while let Some(msg) = iter.next().await? {
+                    messages.push(msg);
+                    if messages.len() >= wanted {
+                        break;
+                    }
+                }
+            }
+        }
+
+        let page = messages.split_off(offset.min(messages.len()));
+        info!("✅ Found {} messages for {:?}", page.len(), query);
+        Ok(page)
+    }
+
+    pub async fn get_message(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+    ) -> Result<Option<Message>, anyhow::Error> {
+        let mut messages = self.get_messages_by_id(chat_id, &[message_id]).await?;
+        Ok(messages.pop().flatten())
+    }
+
+    /// Looks messages up by id, in the order given; deleted ones come back as `None`.
+    pub async fn get_messages_by_id(
+        &self,
+        chat_id: i64,
+        message_ids: &[i32],
+    ) -> Result<Vec<Option<Message>>, anyhow::Error> {
+        let chat = self.resolve_peer(chat_id).await?;
+        Ok(self.client.get_messages_by_id(chat, message_ids).await?)
+    }
+
+    /// Downloads the full media file to `path`.
+    pub async fn download_media(&self, media: Media, path: &Path) -> Result<(), anyhow::Error> {
+        self.client.download_media(&media, path).await?;
+        Ok(())
+    }
+
+    /// Downloads a preview-sized thumbnail to `path`. Returns `false` when the
+    /// media has no thumbnail.
+    pub async fn download_thumbnail(
+        &self,
+        media: &Media,
+        path: &Path,
+    ) -> Result<bool, anyhow::Error> {
+        let thumbs = match media {
+            Media::Photo(photo) => photo.thumbs(),
+            Media::Document(document) => document.thumbs(),
+            Media::Sticker(sticker) => sticker.document.thumbs(),
+            _ => Vec::new(),
+        };
+        let Some(thumb) = preview_thumb(thumbs) else {
+            return Ok(false);
+        };
+
+        self.client.download_media(&thumb, path).await?;
+        Ok(true)
+    }
+
+    fn cache_peer(&self, peer_ref: PeerRef) {
+        let chat_id = chat_id_for_peer(peer_ref.id);
+        self.peer_cache.lock().unwrap().insert(chat_id, peer_ref);
+    }
+
+    /// Resolves a chat id to a packed peer, first from the in-memory cache filled by
+    /// `get_dialogs` and then from the peers grammers persisted in the session file.
+    async fn resolve_peer(&self, chat_id: i64) -> Result<PeerRef, anyhow::Error> {
+        if let Some(peer_ref) = self.peer_cache.lock().unwrap().get(&chat_id) {
+            return Ok(*peer_ref);
+        }
+
+        let peer_id = peer_id_for_chat(chat_id);
+        let auth = match peer_id.kind() {
+            // Basic groups need no access hash.
+            PeerKind::Chat => PeerAuth::default(),
+            _ => self
+                .session
+                .peer(peer_id)
+                .map(|info| info.auth())
+                .ok_or_else(|| anyhow::anyhow!("Chat not found for id: {}", chat_id))?,
+        };
+
+        let peer_ref = PeerRef { id: peer_id, auth };
+        self.cache_peer(peer_ref);
+        Ok(peer_ref)
+    }
+
+    /// Signs out of Telegram. Cached peers and messages are cleared even if Telegram
+    /// could not be reached, since the account is dropped locally either way.
+    pub async fn sign_out(&self) -> Result<(), anyhow::Error> {
+        info!("Signing out of Telegram ({})", self.session_path);
+
+        let signed_out = self.client.sign_out().await;
+        self.peer_cache.lock().unwrap().clear();
+        if let Some(cache) = &self.message_cache {
+            cache.clear()?;
+        }
+        signed_out?;
+
+        info!("✅ Signed out successfully");
+        Ok(())
     }
 
-    pub async fn send_message(&self, chat_id: i64, text: &str) -> Result<(), anyhow::Error> {
+    /// Returns the message as Telegram stored it, with its real id and date.
+    pub async fn send_message(
+        &self,
+        chat_id: i64,
+        text: &str,
+        reply_to: Option<i32>,
+        parse_mode: Option<ParseMode>,
+    ) -> Result<Message, anyhow::Error> {
         info!("Sending message to chat_id: {}", chat_id);
 
-        let chat = self
-            .chat_map
-            .get(&chat_id)
-            .ok_or_else(|| anyhow::anyhow!("Chat not found for id: {}", chat_id))?;
+        let chat = self.resolve_peer(chat_id).await?;
 
-        self.client.send_message(chat, text).await?;
+        let message = formatted(text, parse_mode).reply_to(reply_to);
+        let message = self.client.send_message(chat, message).await?;
 
         info!("✅ Message sent successfully to chat_id: {}", chat_id);
+        Ok(message)
+    }
+
+    /// Posts the reminder for a task that fell due: to Saved Messages, or as a reply to
+    /// the message the task was made from if it asks to be reminded in that chat.
+    pub async fn post_reminder(&self, task: &WsTask) -> Result<(), anyhow::Error> {
+        info!("Posting reminder for task {}", task.id);
+
+        match task.chat_id.filter(|_| task.remind_in_chat) {
+            Some(chat_id) => {
+                let text = format!("⏰ Reminder: {}", task.text);
+                self.send_message(chat_id, &text, task.message_id, None).await?;
+            }
+            None => {
+                let saved_messages = PeerRef {
+                    id: PeerId::self_user(),
+                    auth: PeerAuth::default(),
+                };
+                let text = format!("⏰ Reminder: {}\nFrom {}", task.text, task.user_name);
+                self.client
+                    .send_message(saved_messages, InputMessage::new().text(text))
+                    .await?;
+            }
+        }
+
+        info!("✅ Reminder posted for task {}", task.id);
+        Ok(())
+    }
+
+    /// Forwards `message_ids` from `from_chat` to `to_chat`, returning the new copies.
+    pub async fn forward_messages(
+        &self,
+        from_chat: i64,
+        message_ids: &[i32],
+        to_chat: i64,
+    ) -> Result<Vec<Message>, anyhow::Error> {
+        info!(
+            "Forwarding {} messages from chat_id: {} to chat_id: {}",
+            message_ids.len(),
+            from_chat,
+            to_chat
+        );
+
+        let source = self.resolve_peer(from_chat).await?;
+        let destination = self.resolve_peer(to_chat).await?;
+
+        let forwarded = self
+            .client
+            .forward_messages(destination, message_ids, source)
+            .await?;
+
+        info!("✅ Forwarded {} messages to chat_id: {}", forwarded.len(), to_chat);
+        Ok(forwarded.into_iter().flatten().collect())
+    }
+
+    /// Marks incoming messages up to `max_id` as read.
+    pub async fn mark_read(&self, chat_id: i64, max_id: i32) -> Result<(), anyhow::Error> {
+        info!("Marking chat_id: {} read up to {}", chat_id, max_id);
+
+        let chat = self.resolve_peer(chat_id).await?;
+        match tl::enums::InputPeer::from(chat) {
+            tl::enums::InputPeer::Channel(channel) => {
+                self.client
+                    .invoke(&tl::functions::channels::ReadHistory {
+                        channel: tl::types::InputChannel {
+                            channel_id: channel.channel_id,
+                            access_hash: channel.access_hash,
+                        }
+                        .into(),
+                        max_id,
+                    })
+                    .await?;
+            }
+            peer => {
+                self.client
+                    .invoke(&tl::functions::messages::ReadHistory { peer, max_id })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows "typing…" to the other members of `chat_id`, or cancels it.
+    pub async fn set_typing(&self, chat_id: i64, typing: bool) -> Result<(), anyhow::Error> {
+        let chat = self.resolve_peer(chat_id).await?;
+        let action = if typing {
+            tl::enums::SendMessageAction::SendMessageTypingAction
+        } else {
+            tl::enums::SendMessageAction::SendMessageCancelAction
+        };
+        self.client
+            .invoke(&tl::functions::messages::SetTyping {
+                peer: chat.into(),
+                top_msg_id: None,
+                action,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Members of a group or channel, up to `limit`. A private chat has no member
+    /// list, so both of its users are returned instead.
+    pub async fn get_participants(
+        &self,
+        chat_id: i64,
+        limit: usize,
+    ) -> Result<Vec<WsParticipant>, anyhow::Error> {
+        info!("Fetching participants of chat_id: {}", chat_id);
+
+        let chat = self.resolve_peer(chat_id).await?;
+        let other = match tl::enums::InputPeer::from(chat) {
+            tl::enums::InputPeer::User(user) => Some(tl::types::InputUser {
+                user_id: user.user_id,
+                access_hash: user.access_hash,
+            }),
+            tl::enums::InputPeer::PeerSelf => None,
+            _ => {
+                let mut iter = self.client.iter_participants(chat);
+                let mut participants = Vec::new();
+                while let Some(participant) = iter.next().await? {
+                    let user = &participant.user;
+                    participants.push(WsParticipant {
+                        user_id: user.bare_id(),
+                        name: user.full_name(),
+                    });
+                    if participants.len() >= limit {
+                        break;
+                    }
+                }
+                info!("✅ Fetched {} participants of chat_id: {}", participants.len(), chat_id);
+                return Ok(participants);
+            }
+        };
+
+        let mut ids = vec![tl::enums::InputUser::UserSelf];
+        ids.extend(other.map(Into::into));
+        let users = self
+            .client
+            .invoke(&tl::functions::users::GetUsers { id: ids })
+            .await?;
+        Ok(users
+            .into_iter()
+            .filter_map(|user| match user {
+                tl::enums::User::User(user) => Some(WsParticipant {
+                    user_id: user.id,
+                    name: tl_user_name(&user),
+                }),
+                tl::enums::User::Empty(_) => None,
+            })
+            .collect())
+    }
+
+    /// Replaces the text of a message, returning it as Telegram stored the edit.
+    pub async fn edit_message(
+        &self,
+        chat_id: i64,
+        message_id: i32,
+        text: &str,
+        parse_mode: Option<ParseMode>,
+    ) -> Result<Message, anyhow::Error> {
+        info!("Editing message {} in chat_id: {}", message_id, chat_id);
+
+        let chat = self.resolve_peer(chat_id).await?;
+        self.client
+            .edit_message(chat, message_id, formatted(text, parse_mode))
+            .await?;
+
+        self.get_message(chat_id, message_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Message {} no longer exists", message_id))
+    }
+
+    /// Deletes messages for everyone, or only for this account without `revoke`.
+    /// Channels and supergroups have no per-account deletion, so `revoke` is
+    /// implied there.
+    pub async fn delete_messages(
+        &self,
+        chat_id: i64,
+        message_ids: &[i32],
+        revoke: bool,
+    ) -> Result<(), anyhow::Error> {
+        info!(
+            "Deleting {} messages in chat_id: {} (revoke: {})",
+            message_ids.len(),
+            chat_id,
+            revoke
+        );
+
+        let chat = self.resolve_peer(chat_id).await?;
+        if revoke || matches!(peer_id_for_chat(chat_id).kind(), PeerKind::Channel) {
+            self.client.delete_messages(chat, message_ids).await?;
+        } else {
+            self.client
+                .invoke(&tl::functions::messages::DeleteMessages {
+                    revoke: false,
+                    id: message_ids.to_vec(),
+                })
+                .await?;
+        }
+
+        info!("✅ Deleted {} messages in chat_id: {}", message_ids.len(), chat_id);
         Ok(())
     }
 
+    pub async fn send_file<S: AsyncRead + Unpin>(
+        &self,
+        chat_id: i64,
+        stream: &mut S,
+        size: usize,
+        file_name: &str,
+        caption: &str,
+    ) -> Result<Message, anyhow::Error> {
+        info!(
+            "Uploading file {} ({} bytes) to chat_id: {}",
+            file_name, size, chat_id
+        );
+
+        let chat = self.resolve_peer(chat_id).await?;
+
+        let uploaded = self
+            .client
+            .upload_stream(stream, size, file_name.to_string())
+            .await?;
+
+        let message = InputMessage::new().text(caption);
+        let message = if is_photo(file_name) {
+            message.photo(uploaded)
+        } else {
+            message.document(uploaded)
+        };
+
+        let message = self.client.send_message(chat, message).await?;
+
+        info!("✅ File {} sent successfully to chat_id: {}", file_name, chat_id);
+        Ok(message)
+    }
+
     pub async fn is_authorized(&self) -> Result<bool, anyhow::Error> {
         Ok(self.client.is_authorized().await?)
     }
@@ -229,6 +791,17 @@ impl TelegramManager {
         info!("Disconnecting Telegram client");
         self.client.disconnect();
     }
+
+    /// Disconnects the client and stops every socket and task still holding the account.
+    pub fn close(&self) {
+        self.closed.cancel();
+        self.disconnect();
+    }
+
+    /// Cancelled once the account is closed.
+    pub fn closed(&self) -> CancellationToken {
+        self.closed.clone()
+    }
 }
 
 impl Drop for TelegramManager {
@@ -237,3 +810,142 @@ impl Drop for TelegramManager {
         self.client.disconnect();
     }
 }
+
+/// Read receipts arrive as raw updates; grammers has no typed wrapper for them.
+fn read_update(update: tl::enums::Update) -> Option<TelegramUpdate> {
+    use tl::enums::Update as U;
+
+    match update {
+        U::ReadHistoryInbox(u) => Some(TelegramUpdate::ReadInbox {
+            chat_id: chat_id_for_tl_peer(&u.peer),
+            max_id: u.max_id,
+            unread_count: u.still_unread_count,
+        }),
+        U::ReadHistoryOutbox(u) => Some(TelegramUpdate::ReadOutbox {
+            chat_id: chat_id_for_tl_peer(&u.peer),
+            max_id: u.max_id,
+        }),
+        U::ReadChannelInbox(u) => Some(TelegramUpdate::ReadInbox {
+            chat_id: chat_id_for_peer(PeerId::channel(u.channel_id)),
+            max_id: u.max_id,
+            unread_count: u.still_unread_count,
+        }),
+        U::ReadChannelOutbox(u) => Some(TelegramUpdate::ReadOutbox {
+            chat_id: chat_id_for_peer(PeerId::channel(u.channel_id)),
+            max_id: u.max_id,
+        }),
+        U::UserTyping(u) => Some(TelegramUpdate::UserTyping {
+            chat_id: chat_id_for_peer(PeerId::user(u.user_id)),
+            user_id: u.user_id,
+            action: typing_action(&u.action)?,
+        }),
+        U::ChatUserTyping(u) => Some(TelegramUpdate::UserTyping {
+            chat_id: chat_id_for_peer(PeerId::chat(u.chat_id)),
+            user_id: chat_id_for_tl_peer(&u.from_id),
+            action: typing_action(&u.action)?,
+        }),
+        U::ChannelUserTyping(u) => Some(TelegramUpdate::UserTyping {
+            chat_id: chat_id_for_peer(PeerId::channel(u.channel_id)),
+            user_id: chat_id_for_tl_peer(&u.from_id),
+            action: typing_action(&u.action)?,
+        }),
+        U::UserStatus(u) => Some(TelegramUpdate::UserStatus {
+            user_id: u.user_id,
+            presence: to_presence(&u.status),
+        }),
+        _ => None,
+    }
+}
+
+/// Actions we have no way to show (games, group calls, emoji reactions…) map to `None`.
+fn typing_action(action: &tl::enums::SendMessageAction) -> Option<TypingAction> {
+    use tl::enums::SendMessageAction as A;
+
+    Some(match action {
+        A::SendMessageTypingAction => TypingAction::Typing,
+        A::SendMessageCancelAction => TypingAction::Cancel,
+        A::SendMessageRecordAudioAction => TypingAction::RecordingVoice,
+        A::SendMessageRecordVideoAction | A::SendMessageRecordRoundAction => {
+            TypingAction::RecordingVideo
+        }
+        A::SendMessageUploadVideoAction(_)
+        | A::SendMessageUploadAudioAction(_)
+        | A::SendMessageUploadPhotoAction(_)
+        | A::SendMessageUploadDocumentAction(_)
+        | A::SendMessageUploadRoundAction(_) => TypingAction::Uploading,
+        A::SendMessageChooseStickerAction => TypingAction::ChoosingSticker,
+        _ => return None,
+    })
+}
+
+fn messages_filter(filter: SearchFilter) -> tl::enums::MessagesFilter {
+    use tl::enums::MessagesFilter as F;
+
+    match filter {
+        SearchFilter::All => F::InputMessagesFilterEmpty,
+        SearchFilter::Photos => F::InputMessagesFilterPhotos,
+        SearchFilter::Videos => F::InputMessagesFilterVideo,
+        SearchFilter::Documents => F::InputMessagesFilterDocument,
+        SearchFilter::Links => F::InputMessagesFilterUrl,
+        SearchFilter::Voice => F::InputMessagesFilterVoice,
+        SearchFilter::Music => F::InputMessagesFilterMusic,
+    }
+}
+
+/// A user's last-seen status, as coarse as their privacy settings make it.
+pub fn to_presence(status: &tl::enums::UserStatus) -> WsPresence {
+    use tl::enums::UserStatus as S;
+
+    match status {
+        S::Online(s) => WsPresence::Online { expires: s.expires as i64 },
+        S::Offline(s) => WsPresence::Offline {
+            was_online: s.was_online as i64,
+        },
+        S::Recently(_) => WsPresence::Recently,
+        S::LastWeek(_) => WsPresence::LastWeek,
+        S::LastMonth(_) => WsPresence::LastMonth,
+        S::Empty => WsPresence::LongAgo,
+    }
+}
+
+fn chat_id_for_tl_peer(peer: &tl::enums::Peer) -> i64 {
+    chat_id_for_peer(match peer {
+        tl::enums::Peer::User(p) => PeerId::user(p.user_id),
+        tl::enums::Peer::Chat(p) => PeerId::chat(p.chat_id),
+        tl::enums::Peer::Channel(p) => PeerId::channel(p.channel_id),
+    })
+}
+
+/// Outgoing text with its markup parsed into entities.
+fn formatted(text: &str, parse_mode: Option<ParseMode>) -> InputMessage {
+    match parse_mode {
+        Some(ParseMode::Markdown) => InputMessage::new().markdown(text),
+        Some(ParseMode::Html) => InputMessage::new().html(text),
+        None => InputMessage::new().text(text),
+    }
+}
+
+/// Images Telegram can show inline as a photo; anything else goes out as a document.
+fn is_photo(file_name: &str) -> bool {
+    let extension = file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    matches!(extension.as_str(), "jpg" | "jpeg" | "png" | "webp")
+}
+
+/// Telegram names sizes by letter: `s` is 100px, `m` 320px, `x` 800px. `m` is the
+/// best fit for an in-chat preview; stripped and vector sizes are skipped.
+fn preview_thumb(thumbs: Vec<PhotoSize>) -> Option<PhotoSize> {
+    let mut thumbs: Vec<PhotoSize> = thumbs
+        .into_iter()
+        .filter(|t| !matches!(t, PhotoSize::Empty(_) | PhotoSize::Stripped(_) | PhotoSize::Path(_)))
+        .collect();
+    let preferred = ["m", "s", "x"]
+        .iter()
+        .find_map(|kind| thumbs.iter().position(|t| t.photo_type() == *kind));
+    match preferred {
+        Some(index) => Some(thumbs.swap_remove(index)),
+        None => thumbs.into_iter().next(),
+    }
+}
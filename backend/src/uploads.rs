@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Telegram rejects documents above 2000 MiB for regular accounts.
+pub const MAX_FILE_SIZE: u64 = 2000 * 1024 * 1024;
+
+/// Uploads one connection may have in flight, each holding a temp file open.
+pub const MAX_CONCURRENT_UPLOADS: usize = 4;
+
+/// A file being received over the WebSocket in chunks, spooled to a temp file
+/// so large uploads never have to sit in memory.
+pub struct PendingUpload {
+    pub chat_id: i64,
+    pub file_name: String,
+    pub caption: String,
+    pub file_size: u64,
+    pub received: u64,
+    pub path: PathBuf,
+    file: tokio::fs::File,
+}
+
+impl PendingUpload {
+    pub async fn write_chunk(&mut self, data: &[u8]) -> Result<u64, anyhow::Error> {
+        let received = self.received + data.len() as u64;
+        if received > self.file_size {
+            return Err(anyhow::anyhow!(
+                "Upload exceeds announced size of {} bytes",
+                self.file_size
+            ));
+        }
+
+        self.file.write_all(data).await?;
+        self.received = received;
+        Ok(received)
+    }
+
+    pub async fn finish(&mut self) -> Result<(), anyhow::Error> {
+        if self.received != self.file_size {
+            return Err(anyhow::anyhow!(
+                "Upload incomplete: received {} of {} bytes",
+                self.received,
+                self.file_size
+            ));
+        }
+
+        self.file.flush().await?;
+        Ok(())
+    }
+}
+
+impl Drop for PendingUpload {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove upload temp file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Uploads in flight for a single WebSocket connection, keyed by client-chosen upload id.
+#[derive(Default)]
+pub struct Uploads {
+    pending: HashMap<String, PendingUpload>,
+}
+
+impl Uploads {
+    pub async fn start(
+        &mut self,
+        upload_id: &str,
+        chat_id: i64,
+        file_name: String,
+        file_size: u64,
+        caption: String,
+    ) -> Result<(), anyhow::Error> {
+        if file_size > MAX_FILE_SIZE {
+            return Err(anyhow::anyhow!(
+                "File is too large: {} bytes (max {})",
+                file_size,
+                MAX_FILE_SIZE
+            ));
+        }
+        if self.pending.contains_key(upload_id) {
+            return Err(anyhow::anyhow!("Upload {} already started", upload_id));
+        }
+        if self.pending.len() >= MAX_CONCURRENT_UPLOADS {
+            return Err(anyhow::anyhow!(
+                "Too many uploads in flight (max {})",
+                MAX_CONCURRENT_UPLOADS
+            ));
+        }
+
+        let path = std::env::temp_dir().join(format!("wgram-upload-{}", uuid::Uuid::new_v4()));
+        let file = tokio::fs::File::create(&path).await?;
+
+        info!(
+            "Started upload {} of {} ({} bytes) for chat_id: {}",
+            upload_id, file_name, file_size, chat_id
+        );
+
+        self.pending.insert(
+            upload_id.to_string(),
+            PendingUpload {
+                chat_id,
+                file_name,
+                caption,
+                file_size,
+                received: 0,
+                path,
+                file,
+            },
+        );
+        Ok(())
+    }
+
+    pub async fn write_chunk(&mut self, upload_id: &str, data: &[u8]) -> Result<u64, anyhow::Error> {
+        let upload = self
+            .pending
+            .get_mut(upload_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown upload id: {}", upload_id))?;
+
+        match upload.write_chunk(data).await {
+            Ok(received) => Ok(received),
+            Err(e) => {
+                self.pending.remove(upload_id);
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes the upload from the in-flight set once every byte has arrived.
+    /// The temp file lives until the returned value is dropped.
+    pub async fn finish(&mut self, upload_id: &str) -> Result<PendingUpload, anyhow::Error> {
+        let mut upload = self
+            .pending
+            .remove(upload_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown upload id: {}", upload_id))?;
+
+        upload.finish().await?;
+        Ok(upload)
+    }
+}
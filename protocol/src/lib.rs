@@ -0,0 +1,702 @@
+//! Wire types spoken over the `/ws` WebSocket between the backend and the UI.
+//!
+//! Both sides depend on this crate, so a change to a message shape is a change
+//! to both at once. Bump [`PROTOCOL_VERSION`] whenever a change is not
+//! backwards compatible.
+
+use serde::{Deserialize, Serialize};
+
+/// Sent by the client as the `protocol_version` query parameter of `/ws` and
+/// answered by the backend with [`WsResponse::Hello`].
+pub const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WsRequest {
+    GetDialogs,
+    GetMessages {
+        chat_id: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        before_id: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        limit: Option<usize>,
+    },
+    SendMessage {
+        chat_id: i64,
+        text: String,
+        /// Echoed back in `MessageSent` so the client can match its pending bubble.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_id: Option<String>,
+        /// Id of the message in the same chat this one replies to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reply_to: Option<i32>,
+        /// How to turn markup in `text` into formatting; plain text when absent.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parse_mode: Option<ParseMode>,
+    },
+    SendFile {
+        chat_id: i64,
+        file_name: String,
+        #[serde(with = "base64_bytes")]
+        file_data: Vec<u8>,
+        #[serde(default)]
+        caption: String,
+    },
+    StartFileUpload {
+        chat_id: i64,
+        upload_id: String,
+        file_name: String,
+        file_size: u64,
+        #[serde(default)]
+        caption: String,
+    },
+    FileChunk {
+        chat_id: i64,
+        upload_id: String,
+        /// Base64, since JSON arrays of byte numbers are about four times the size.
+        #[serde(with = "base64_bytes")]
+        data: Vec<u8>,
+    },
+    FinishFileUpload {
+        chat_id: i64,
+        upload_id: String,
+    },
+    ForwardMessages {
+        from_chat: i64,
+        ids: Vec<i32>,
+        to_chat: i64,
+    },
+    /// Answered with `MessageEdited` carrying the updated message.
+    EditMessage {
+        chat_id: i64,
+        message_id: i32,
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parse_mode: Option<ParseMode>,
+    },
+    /// Answered with `MessagesDeleted`. Without `revoke` the messages are only
+    /// removed for this account; channels and supergroups always delete for everyone.
+    DeleteMessages {
+        chat_id: i64,
+        ids: Vec<i32>,
+        #[serde(default)]
+        revoke: bool,
+    },
+    /// Marks incoming messages up to `max_id` as read on Telegram. Answered with
+    /// `ReadInbox`.
+    MarkRead {
+        chat_id: i64,
+        max_id: i32,
+    },
+    /// Shows or cancels our "typing…" in `chat_id`. Telegram drops it after about
+    /// six seconds, so clients repeat it while the user keeps typing. Only failures
+    /// are answered.
+    SetTyping {
+        chat_id: i64,
+        typing: bool,
+    },
+    /// Full-text search in one chat, or across all chats when `chat_id` is absent.
+    /// `offset` counts results already shown. Answered with `SearchResults`.
+    SearchMessages {
+        query: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chat_id: Option<i64>,
+        #[serde(default)]
+        filter: SearchFilter,
+        #[serde(default)]
+        offset: u32,
+    },
+    /// Answered with `Tasks`.
+    ListTasks,
+    /// Turns a message into a task. Answered with `TaskSaved`, which is also pushed
+    /// to every client of the account. Clients from before message links leave out
+    /// `chat_id` and `message_id`.
+    CreateTask {
+        text: String,
+        user_name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chat_id: Option<i64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_id: Option<i32>,
+    },
+    /// Changes only the fields that are present. Task ids are named `task_id` because
+    /// `id` is taken by the envelope. Answered and synced like `CreateTask`.
+    UpdateTask {
+        task_id: i64,
+        #[serde(flatten)]
+        changes: TaskChanges,
+    },
+    /// Answered with `TaskDeleted`, which is also pushed to every client of the account.
+    DeleteTask {
+        task_id: i64,
+    },
+    /// Moves the due date `minutes` past the backend's clock, so the reminder fires
+    /// again then. Answered and synced like `UpdateTask`.
+    SnoozeTask {
+        task_id: i64,
+        minutes: u32,
+    },
+    /// Members of a chat to assign tasks to. Answered with `Participants`.
+    GetParticipants {
+        chat_id: i64,
+    },
+}
+
+impl WsRequest {
+    /// The id of the outgoing message this request delivers, if any.
+    pub fn client_id(&self) -> Option<&str> {
+        match self {
+            WsRequest::SendMessage { client_id, .. } => client_id.as_deref(),
+            WsRequest::StartFileUpload { upload_id, .. }
+            | WsRequest::FileChunk { upload_id, .. }
+            | WsRequest::FinishFileUpload { upload_id, .. } => Some(upload_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WsResponse {
+    /// First frame on every connection, before any reply or update.
+    Hello {
+        protocol_version: u32,
+        /// Authorizes `/media` requests for as long as this connection stays open, so
+        /// media URLs never carry the login session.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        media_token: Option<String>,
+    },
+    Dialogs {
+        data: Vec<WsDialog>,
+    },
+    Messages {
+        chat_id: i64,
+        data: Vec<WsMessage>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        before_id: Option<i32>,
+        #[serde(default)]
+        has_more: bool,
+    },
+    MessageSent {
+        chat_id: i64,
+        success: bool,
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sent_message: Option<WsMessage>,
+    },
+    FileSent {
+        chat_id: i64,
+        success: bool,
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        upload_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sent_message: Option<WsMessage>,
+    },
+    FileUploadProgress {
+        chat_id: i64,
+        upload_id: String,
+        received: u64,
+    },
+    NewMessage {
+        chat_id: i64,
+        message: WsMessage,
+    },
+    MessageEdited {
+        chat_id: i64,
+        message: WsMessage,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+    MessagesDeleted {
+        /// Omitted when Telegram does not say which chat the messages belonged to.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chat_id: Option<i64>,
+        message_ids: Vec<i32>,
+    },
+    /// Incoming messages up to `max_id` were read, here or on another device.
+    ReadInbox {
+        chat_id: i64,
+        max_id: i32,
+        unread_count: i32,
+    },
+    /// The other side read our messages up to `max_id`.
+    ReadOutbox {
+        chat_id: i64,
+        max_id: i32,
+    },
+    /// Reply to `ForwardMessages` with the copies as they now appear in `to_chat`.
+    MessagesForwarded {
+        from_chat: i64,
+        to_chat: i64,
+        messages: Vec<WsMessage>,
+    },
+    /// Someone in `chat_id` started or stopped an action. Anything but `Cancel`
+    /// should be shown for about six seconds unless repeated.
+    UserTyping {
+        chat_id: i64,
+        user_id: i64,
+        action: TypingAction,
+    },
+    /// A user's online status changed; `user_id` is also their private chat id.
+    UserStatus {
+        user_id: i64,
+        presence: WsPresence,
+    },
+    /// Newest matches first; `next_offset` is set when there may be more.
+    SearchResults {
+        query: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chat_id: Option<i64>,
+        offset: u32,
+        results: Vec<WsSearchResult>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next_offset: Option<u32>,
+    },
+    /// Every task of the account, oldest first.
+    Tasks {
+        data: Vec<WsTask>,
+    },
+    /// A task was created or changed, by this client or another one.
+    TaskSaved {
+        task: WsTask,
+    },
+    TaskDeleted {
+        task_id: i64,
+    },
+    /// A task fell due and its reminder was posted to Telegram. Pushed once per due
+    /// date to every client of the account.
+    TaskReminder {
+        task: WsTask,
+    },
+    /// Reply to `GetParticipants`; big groups are cut off after the first few hundred.
+    Participants {
+        chat_id: i64,
+        data: Vec<WsParticipant>,
+    },
+}
+
+/// A to-do kept by the backend for the account, shared by all of its clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WsTask {
+    pub id: i64,
+    pub text: String,
+    /// Name of the chat the task was made in.
+    pub user_name: String,
+    /// The message the task was made from; absent on tasks older than the link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i32>,
+    /// Unix seconds, like every timestamp on a task.
+    pub created_at: i64,
+    /// Last change of any field; equals `created_at` until then.
+    #[serde(default)]
+    pub updated_at: i64,
+    #[serde(default)]
+    pub completed: bool,
+    /// When the task was last ticked off; cleared when it is reopened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_at: Option<i64>,
+    #[serde(default)]
+    pub priority: TaskPriority,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<WsParticipant>,
+    /// Post the reminder as a reply in the linked chat rather than to Saved Messages.
+    #[serde(default)]
+    pub remind_in_chat: bool,
+}
+
+/// Fields of a task to change; absent ones are left alone. `due_at` and `assignee`
+/// are cleared by sending them as `null`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskChanges {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
+    pub due_at: Option<Option<i64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<TaskPriority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "present"
+    )]
+    pub assignee: Option<Option<WsParticipant>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind_in_chat: Option<bool>,
+}
+
+/// Tells a field sent as `null` (`Some(None)`) apart from one left out (`None`).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Ordered from least to most pressing.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// A chat member, as offered for task assignment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsParticipant {
+    pub user_id: i64,
+    pub name: String,
+}
+
+/// Narrows a message search to one kind of content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchFilter {
+    #[default]
+    All,
+    Photos,
+    Videos,
+    Documents,
+    Links,
+    Voice,
+    Music,
+}
+
+/// A search match and the chat it was found in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WsSearchResult {
+    pub chat_id: i64,
+    pub message: WsMessage,
+}
+
+/// What a peer is busy with, as far as Telegram tells us.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypingAction {
+    Typing,
+    RecordingVoice,
+    RecordingVideo,
+    Uploading,
+    ChoosingSticker,
+    Cancel,
+}
+
+/// Last-seen status of a user, as precise as their privacy settings allow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WsPresence {
+    /// Online until `expires` (unix seconds) unless refreshed.
+    Online {
+        expires: i64,
+    },
+    Offline {
+        was_online: i64,
+    },
+    Recently,
+    LastWeek,
+    LastMonth,
+    /// Never seen, or hidden entirely.
+    LongAgo,
+}
+
+/// Markup understood in outgoing text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMode {
+    Markdown,
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    NotAuthorized,
+    Telegram,
+    UnsupportedProtocol,
+    /// The backend's own database failed.
+    Storage,
+}
+
+/// Wraps every client command; the optional `id` is echoed on its reply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub request: WsRequest,
+}
+
+/// Replies carry the request's `id`; pushed Telegram updates have none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResponseEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub response: WsResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WsDialog {
+    /// Bot API style peer id: users positive, groups negative, channels `-100…`.
+    pub id: i64,
+    /// `"user"`, `"group"` or `"channel"`.
+    pub peer_type: String,
+    pub name: String,
+    pub last_message: String,
+    pub unread_count: i32,
+    #[serde(default)]
+    pub is_archived: bool,
+    /// Newest incoming message we have read.
+    #[serde(default)]
+    pub read_inbox_max_id: i32,
+    /// Newest outgoing message the other side has read.
+    #[serde(default)]
+    pub read_outbox_max_id: i32,
+    /// Set for private chats with users.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence: Option<WsPresence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WsMessage {
+    pub id: i32,
+    pub text: String,
+    /// Formatting of `text`, possibly nested or overlapping.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<WsEntity>,
+    pub sender_name: String,
+    pub is_outgoing: bool,
+    /// Unix seconds.
+    pub timestamp: i64,
+    /// Unix seconds of the last edit; absent for messages never edited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_date: Option<i64>,
+    #[serde(default)]
+    pub is_file: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<WsMedia>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to_message_id: Option<i32>,
+    /// The replied-to message, when it could be found. Deleted or inaccessible
+    /// messages leave only `reply_to_message_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_preview: Option<WsReplyPreview>,
+}
+
+/// A formatted span of a message's text. As in Telegram, `offset` and `length`
+/// count UTF-16 code units, not bytes or chars.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WsEntity {
+    pub offset: i32,
+    pub length: i32,
+    #[serde(flatten)]
+    pub kind: WsEntityKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WsEntityKind {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
+    Code,
+    Pre {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+    Blockquote,
+    /// The text itself is the link.
+    Url,
+    /// Link text pointing at `url`.
+    TextUrl {
+        url: String,
+    },
+    Email,
+    Phone,
+    /// `@username`.
+    Mention,
+    /// A mention of a user without a username, linked by id.
+    MentionName {
+        user_id: i64,
+    },
+    Hashtag,
+    Cashtag,
+    BotCommand,
+    /// The text holds a regular emoji standing in for the custom one.
+    CustomEmoji {
+        document_id: i64,
+    },
+}
+
+/// Enough of a replied-to message to quote it above the reply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WsReplyPreview {
+    pub sender_name: String,
+    /// The message text, or a label for its media.
+    pub text: String,
+}
+
+/// What a media message carries, with whatever metadata Telegram provides for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WsMedia {
+    Photo {
+        #[serde(default)]
+        width: Option<i32>,
+        #[serde(default)]
+        height: Option<i32>,
+        #[serde(default)]
+        size: Option<u64>,
+    },
+    Document {
+        #[serde(default)]
+        file_name: Option<String>,
+        #[serde(default)]
+        mime_type: Option<String>,
+        #[serde(default)]
+        size: Option<u64>,
+    },
+    Audio {
+        #[serde(default)]
+        file_name: Option<String>,
+        #[serde(default)]
+        mime_type: Option<String>,
+        #[serde(default)]
+        size: Option<u64>,
+        /// Seconds.
+        #[serde(default)]
+        duration: Option<f64>,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        performer: Option<String>,
+        #[serde(default)]
+        voice: bool,
+    },
+    Video {
+        #[serde(default)]
+        file_name: Option<String>,
+        #[serde(default)]
+        mime_type: Option<String>,
+        #[serde(default)]
+        size: Option<u64>,
+        /// Seconds.
+        #[serde(default)]
+        duration: Option<f64>,
+        #[serde(default)]
+        width: Option<i32>,
+        #[serde(default)]
+        height: Option<i32>,
+        /// Round video message.
+        #[serde(default)]
+        round: bool,
+    },
+    Sticker {
+        #[serde(default)]
+        emoji: Option<String>,
+        #[serde(default)]
+        mime_type: Option<String>,
+        #[serde(default)]
+        width: Option<i32>,
+        #[serde(default)]
+        height: Option<i32>,
+        #[serde(default)]
+        animated: bool,
+    },
+    Geo {
+        latitude: f64,
+        longitude: f64,
+    },
+    Contact {
+        first_name: String,
+        #[serde(default)]
+        last_name: String,
+        #[serde(default)]
+        phone_number: String,
+    },
+    Poll {
+        question: String,
+        #[serde(default)]
+        answers: Vec<String>,
+    },
+}
+
+impl WsMedia {
+    /// Short label for chat list previews of messages without text.
+    pub fn describe(&self) -> String {
+        match self {
+            WsMedia::Photo { .. } => "📷 Photo".to_string(),
+            WsMedia::Document { file_name, .. } => {
+                format!("📎 {}", file_name.as_deref().unwrap_or("File"))
+            }
+            WsMedia::Audio { voice: true, .. } => "🎤 Voice message".to_string(),
+            WsMedia::Audio {
+                title, file_name, ..
+            } => format!(
+                "🎵 {}",
+                title.as_deref().or(file_name.as_deref()).unwrap_or("Audio")
+            ),
+            WsMedia::Video { round: true, .. } => "📹 Video message".to_string(),
+            WsMedia::Video { .. } => "🎬 Video".to_string(),
+            WsMedia::Sticker { emoji, .. } => {
+                format!("{} Sticker", emoji.as_deref().unwrap_or("🏷️"))
+            }
+            WsMedia::Geo { .. } => "📍 Location".to_string(),
+            WsMedia::Contact {
+                first_name,
+                last_name,
+                ..
+            } => format!("👤 {} {}", first_name, last_name)
+                .trim_end()
+                .to_string(),
+            WsMedia::Poll { question, .. } => format!("📊 {}", question),
+        }
+    }
+}
+
+/// File bytes travel as standard, padded base64 strings.
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        STANDARD.decode(text).map_err(D::Error::custom)
+    }
+}
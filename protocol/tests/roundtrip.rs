@@ -0,0 +1,637 @@
+use wgram_protocol::*;
+
+fn task() -> WsTask {
+    WsTask {
+        id: 3,
+        text: "Send the invoice".to_string(),
+        user_name: "Alice".to_string(),
+        chat_id: Some(123456789),
+        message_id: Some(4810),
+        created_at: 1_700_000_000,
+        updated_at: 1_700_000_000,
+        completed: false,
+        completed_at: None,
+        due_at: None,
+        priority: TaskPriority::Normal,
+        tags: Vec::new(),
+        notes: String::new(),
+        assignee: None,
+        remind_in_chat: false,
+    }
+}
+
+fn message() -> WsMessage {
+    WsMessage {
+        id: 4811,
+        text: "Hi".to_string(),
+        entities: Vec::new(),
+        sender_name: "Alice".to_string(),
+        is_outgoing: true,
+        timestamp: 1_718_000_000,
+        edit_date: None,
+        is_file: false,
+        file_name: None,
+        media: None,
+        reply_to_message_id: None,
+        reply_preview: None,
+    }
+}
+
+fn roundtrip_request(request: WsRequest, id: Option<u64>) {
+    let envelope = RequestEnvelope { id, request };
+    let json = serde_json::to_string(&envelope).unwrap();
+    assert_eq!(
+        serde_json::from_str::<RequestEnvelope>(&json).unwrap(),
+        envelope,
+        "{}",
+        json
+    );
+}
+
+fn roundtrip_response(response: WsResponse, id: Option<u64>) {
+    let envelope = ResponseEnvelope { id, response };
+    let json = serde_json::to_string(&envelope).unwrap();
+    assert_eq!(
+        serde_json::from_str::<ResponseEnvelope>(&json).unwrap(),
+        envelope,
+        "{}",
+        json
+    );
+}
+
+#[test]
+fn requests_roundtrip() {
+    let requests = vec![
+        WsRequest::GetDialogs,
+        WsRequest::GetMessages {
+            chat_id: -1001234567890,
+            before_id: Some(4810),
+            limit: Some(50),
+        },
+        WsRequest::SendMessage {
+            chat_id: 123456789,
+            text: "Hi".to_string(),
+            client_id: Some("1718000000000-42".to_string()),
+            reply_to: Some(4810),
+            parse_mode: Some(ParseMode::Markdown),
+        },
+        WsRequest::SendFile {
+            chat_id: 123456789,
+            file_name: "a.txt".to_string(),
+            file_data: vec![1, 2, 3],
+            caption: String::new(),
+        },
+        WsRequest::StartFileUpload {
+            chat_id: 123456789,
+            upload_id: "abc".to_string(),
+            file_name: "report.pdf".to_string(),
+            file_size: 131072,
+            caption: "Q3".to_string(),
+        },
+        WsRequest::FileChunk {
+            chat_id: 123456789,
+            upload_id: "abc".to_string(),
+            data: vec![37, 80, 68, 70],
+        },
+        WsRequest::FinishFileUpload {
+            chat_id: 123456789,
+            upload_id: "abc".to_string(),
+        },
+        WsRequest::ForwardMessages {
+            from_chat: 123456789,
+            ids: vec![4810, 4811],
+            to_chat: -1001234567890,
+        },
+        WsRequest::EditMessage {
+            chat_id: 123456789,
+            message_id: 4811,
+            text: "Hi!".to_string(),
+            parse_mode: None,
+        },
+        WsRequest::DeleteMessages {
+            chat_id: 123456789,
+            ids: vec![4810, 4811],
+            revoke: true,
+        },
+        WsRequest::MarkRead {
+            chat_id: 123456789,
+            max_id: 4811,
+        },
+        WsRequest::SetTyping {
+            chat_id: 123456789,
+            typing: true,
+        },
+        WsRequest::SearchMessages {
+            query: "invoice".to_string(),
+            chat_id: None,
+            filter: SearchFilter::Documents,
+            offset: 30,
+        },
+        WsRequest::ListTasks,
+        WsRequest::CreateTask {
+            text: "Send the invoice".to_string(),
+            user_name: "Alice".to_string(),
+            chat_id: Some(123456789),
+            message_id: Some(4810),
+        },
+        WsRequest::UpdateTask {
+            task_id: 3,
+            changes: TaskChanges {
+                completed: Some(true),
+                ..TaskChanges::default()
+            },
+        },
+        WsRequest::UpdateTask {
+            task_id: 3,
+            changes: TaskChanges {
+                due_at: Some(None),
+                priority: Some(TaskPriority::High),
+                tags: Some(vec!["billing".to_string()]),
+                assignee: Some(Some(WsParticipant {
+                    user_id: 123456789,
+                    name: "Alice".to_string(),
+                })),
+                ..TaskChanges::default()
+            },
+        },
+        WsRequest::UpdateTask {
+            task_id: 3,
+            changes: TaskChanges {
+                remind_in_chat: Some(true),
+                ..TaskChanges::default()
+            },
+        },
+        WsRequest::DeleteTask { task_id: 3 },
+        WsRequest::SnoozeTask {
+            task_id: 3,
+            minutes: 60,
+        },
+        WsRequest::GetParticipants {
+            chat_id: -1001234567890,
+        },
+    ];
+
+    for request in requests {
+        roundtrip_request(request.clone(), None);
+        roundtrip_request(request, Some(7));
+    }
+}
+
+#[test]
+fn responses_roundtrip() {
+    let responses = vec![
+        WsResponse::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            media_token: Some("token".to_string()),
+        },
+        WsResponse::Dialogs {
+            data: vec![WsDialog {
+                id: -1001234567890,
+                peer_type: "channel".to_string(),
+                name: "News".to_string(),
+                last_message: "Hi".to_string(),
+                unread_count: 3,
+                is_archived: true,
+                read_inbox_max_id: 4808,
+                read_outbox_max_id: 4811,
+                presence: None,
+            }],
+        },
+        WsResponse::Messages {
+            chat_id: 123456789,
+            data: vec![message()],
+            before_id: Some(4810),
+            has_more: true,
+        },
+        WsResponse::MessageSent {
+            chat_id: 123456789,
+            success: true,
+            message: "Message sent successfully".to_string(),
+            client_id: Some("1718000000000-42".to_string()),
+            sent_message: Some(message()),
+        },
+        WsResponse::FileSent {
+            chat_id: 123456789,
+            success: false,
+            message: "Failed to send file".to_string(),
+            upload_id: Some("abc".to_string()),
+            sent_message: None,
+        },
+        WsResponse::FileUploadProgress {
+            chat_id: 123456789,
+            upload_id: "abc".to_string(),
+            received: 65536,
+        },
+        WsResponse::NewMessage {
+            chat_id: 123456789,
+            message: message(),
+        },
+        WsResponse::MessageEdited {
+            chat_id: 123456789,
+            message: WsMessage {
+                text: "Hi!".to_string(),
+                edit_date: Some(1_718_000_060),
+                ..message()
+            },
+        },
+        WsResponse::Error {
+            code: ErrorCode::Telegram,
+            message: "Failed to get messages".to_string(),
+        },
+        WsResponse::MessagesDeleted {
+            chat_id: None,
+            message_ids: vec![42, 43],
+        },
+        WsResponse::ReadInbox {
+            chat_id: 123456789,
+            max_id: 4811,
+            unread_count: 0,
+        },
+        WsResponse::ReadOutbox {
+            chat_id: -1001234567890,
+            max_id: 4811,
+        },
+        WsResponse::MessagesForwarded {
+            from_chat: 123456789,
+            to_chat: -1001234567890,
+            messages: vec![message()],
+        },
+        WsResponse::UserTyping {
+            chat_id: -1001234567890,
+            user_id: 123456789,
+            action: TypingAction::RecordingVoice,
+        },
+        WsResponse::UserStatus {
+            user_id: 123456789,
+            presence: WsPresence::Offline {
+                was_online: 1_700_000_000,
+            },
+        },
+        WsResponse::SearchResults {
+            query: "invoice".to_string(),
+            chat_id: Some(-1001234567890),
+            offset: 0,
+            results: vec![WsSearchResult {
+                chat_id: -1001234567890,
+                message: message(),
+            }],
+            next_offset: Some(30),
+        },
+        WsResponse::Tasks { data: vec![task()] },
+        WsResponse::TaskSaved {
+            task: WsTask {
+                updated_at: 1_700_000_600,
+                completed: true,
+                completed_at: Some(1_700_000_600),
+                due_at: Some(1_700_086_400),
+                priority: TaskPriority::High,
+                tags: vec!["billing".to_string(), "q3".to_string()],
+                notes: "Ask about the VAT number".to_string(),
+                assignee: Some(WsParticipant {
+                    user_id: 123456789,
+                    name: "Alice".to_string(),
+                }),
+                ..task()
+            },
+        },
+        WsResponse::TaskSaved {
+            task: WsTask {
+                chat_id: None,
+                message_id: None,
+                ..task()
+            },
+        },
+        WsResponse::TaskDeleted { task_id: 3 },
+        WsResponse::TaskReminder {
+            task: WsTask {
+                due_at: Some(1_700_086_400),
+                remind_in_chat: true,
+                ..task()
+            },
+        },
+        WsResponse::Participants {
+            chat_id: -1001234567890,
+            data: vec![WsParticipant {
+                user_id: 123456789,
+                name: "Alice".to_string(),
+            }],
+        },
+        WsResponse::NewMessage {
+            chat_id: 123456789,
+            message: WsMessage {
+                reply_to_message_id: Some(4810),
+                reply_preview: Some(WsReplyPreview {
+                    sender_name: "Bob".to_string(),
+                    text: "📷 Photo".to_string(),
+                }),
+                ..message()
+            },
+        },
+    ];
+
+    for response in responses {
+        roundtrip_response(response.clone(), None);
+        roundtrip_response(response, Some(7));
+    }
+}
+
+#[test]
+fn media_roundtrip() {
+    let media = vec![
+        WsMedia::Photo {
+            width: Some(1280),
+            height: Some(720),
+            size: Some(102400),
+        },
+        WsMedia::Document {
+            file_name: Some("report.pdf".to_string()),
+            mime_type: Some("application/pdf".to_string()),
+            size: Some(131072),
+        },
+        WsMedia::Audio {
+            file_name: None,
+            mime_type: Some("audio/ogg".to_string()),
+            size: Some(8192),
+            duration: Some(3.0),
+            title: None,
+            performer: None,
+            voice: true,
+        },
+        WsMedia::Video {
+            file_name: Some("clip.mp4".to_string()),
+            mime_type: Some("video/mp4".to_string()),
+            size: Some(1048576),
+            duration: Some(12.5),
+            width: Some(640),
+            height: Some(360),
+            round: false,
+        },
+        WsMedia::Sticker {
+            emoji: Some("👍".to_string()),
+            mime_type: Some("image/webp".to_string()),
+            width: Some(512),
+            height: Some(512),
+            animated: false,
+        },
+        WsMedia::Geo {
+            latitude: 52.52,
+            longitude: 13.405,
+        },
+        WsMedia::Contact {
+            first_name: "Alice".to_string(),
+            last_name: String::new(),
+            phone_number: "15551234".to_string(),
+        },
+        WsMedia::Poll {
+            question: "Lunch?".to_string(),
+            answers: vec!["Yes".to_string(), "No".to_string()],
+        },
+    ];
+
+    for media in media {
+        roundtrip_response(
+            WsResponse::NewMessage {
+                chat_id: 123456789,
+                message: WsMessage {
+                    media: Some(media),
+                    ..message()
+                },
+            },
+            None,
+        );
+    }
+}
+
+#[test]
+fn entities_roundtrip() {
+    let kinds = vec![
+        WsEntityKind::Bold,
+        WsEntityKind::Italic,
+        WsEntityKind::Underline,
+        WsEntityKind::Strikethrough,
+        WsEntityKind::Spoiler,
+        WsEntityKind::Code,
+        WsEntityKind::Pre {
+            language: Some("rust".to_string()),
+        },
+        WsEntityKind::Pre { language: None },
+        WsEntityKind::Blockquote,
+        WsEntityKind::Url,
+        WsEntityKind::TextUrl {
+            url: "https://example.com".to_string(),
+        },
+        WsEntityKind::Email,
+        WsEntityKind::Phone,
+        WsEntityKind::Mention,
+        WsEntityKind::MentionName { user_id: 123456789 },
+        WsEntityKind::Hashtag,
+        WsEntityKind::Cashtag,
+        WsEntityKind::BotCommand,
+        WsEntityKind::CustomEmoji {
+            document_id: 5368324170671202286,
+        },
+    ];
+    let entities = kinds
+        .into_iter()
+        .map(|kind| WsEntity {
+            offset: 0,
+            length: 2,
+            kind,
+        })
+        .collect();
+
+    roundtrip_response(
+        WsResponse::NewMessage {
+            chat_id: 123456789,
+            message: WsMessage {
+                entities,
+                ..message()
+            },
+        },
+        None,
+    );
+
+    let json = serde_json::to_string(&WsEntity {
+        offset: 3,
+        length: 4,
+        kind: WsEntityKind::TextUrl {
+            url: "https://example.com".to_string(),
+        },
+    })
+    .unwrap();
+    assert_eq!(
+        json,
+        r#"{"offset":3,"length":4,"kind":"text_url","url":"https://example.com"}"#
+    );
+}
+
+#[test]
+fn request_id_is_optional_on_the_wire() {
+    let envelope: RequestEnvelope =
+        serde_json::from_str(r#"{ "type": "GetMessages", "chat_id": 123456789 }"#).unwrap();
+    assert_eq!(envelope.id, None);
+    assert_eq!(
+        envelope.request,
+        WsRequest::GetMessages {
+            chat_id: 123456789,
+            before_id: None,
+            limit: None,
+        }
+    );
+}
+
+#[test]
+fn error_codes_are_snake_case() {
+    let json = serde_json::to_string(&ResponseEnvelope {
+        id: Some(7),
+        response: WsResponse::Error {
+            code: ErrorCode::UnsupportedProtocol,
+            message: "Update the app".to_string(),
+        },
+    })
+    .unwrap();
+    assert!(
+        json.contains(r#""code":"unsupported_protocol""#),
+        "{}",
+        json
+    );
+    assert!(json.contains(r#""type":"Error""#), "{}", json);
+}
+
+#[test]
+fn presence_is_tagged_by_status() {
+    let json = serde_json::to_value(WsPresence::Online {
+        expires: 1_700_000_300,
+    })
+    .unwrap();
+    assert_eq!(json["status"], "online");
+    assert_eq!(json["expires"], 1_700_000_300);
+
+    let json = serde_json::to_value(WsPresence::LastWeek).unwrap();
+    assert_eq!(json, serde_json::json!({ "status": "last_week" }));
+
+    let dialog: WsDialog = serde_json::from_str(
+        r#"{"id":1,"peer_type":"user","name":"A","last_message":"","unread_count":0,"presence":{"status":"recently"}}"#,
+    )
+    .unwrap();
+    assert_eq!(dialog.presence, Some(WsPresence::Recently));
+}
+
+#[test]
+fn search_defaults_to_all_from_the_start() {
+    let request: RequestEnvelope =
+        serde_json::from_str(r#"{"type":"SearchMessages","query":"hi"}"#).unwrap();
+    assert_eq!(
+        request.request,
+        WsRequest::SearchMessages {
+            query: "hi".to_string(),
+            chat_id: None,
+            filter: SearchFilter::All,
+            offset: 0,
+        }
+    );
+}
+
+#[test]
+fn task_created_without_its_message_is_accepted() {
+    let request: RequestEnvelope = serde_json::from_str(
+        r#"{"type":"CreateTask","text":"Send the invoice","user_name":"Alice"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        request.request,
+        WsRequest::CreateTask {
+            text: "Send the invoice".to_string(),
+            user_name: "Alice".to_string(),
+            chat_id: None,
+            message_id: None,
+        }
+    );
+}
+
+#[test]
+fn hello_without_a_media_token_is_accepted() {
+    let response: ResponseEnvelope =
+        serde_json::from_str(r#"{"type":"Hello","protocol_version":2}"#).unwrap();
+    assert_eq!(
+        response.response,
+        WsResponse::Hello {
+            protocol_version: 2,
+            media_token: None,
+        }
+    );
+}
+
+#[test]
+fn task_update_carries_only_changed_fields() {
+    let request = WsRequest::UpdateTask {
+        task_id: 3,
+        changes: TaskChanges {
+            completed: Some(true),
+            ..TaskChanges::default()
+        },
+    };
+    assert_eq!(
+        serde_json::to_string(&request).unwrap(),
+        r#"{"type":"UpdateTask","task_id":3,"completed":true}"#
+    );
+}
+
+#[test]
+fn task_update_clears_fields_sent_as_null() {
+    let request: RequestEnvelope =
+        serde_json::from_str(r#"{"type":"UpdateTask","task_id":3,"due_at":null}"#).unwrap();
+    assert_eq!(
+        request.request,
+        WsRequest::UpdateTask {
+            task_id: 3,
+            changes: TaskChanges {
+                due_at: Some(None),
+                ..TaskChanges::default()
+            },
+        }
+    );
+}
+
+#[test]
+fn file_bytes_travel_as_base64() {
+    let chunk = |data: Vec<u8>| WsRequest::FileChunk {
+        chat_id: 123456789,
+        upload_id: "abc".to_string(),
+        data,
+    };
+    assert_eq!(
+        serde_json::to_string(&chunk(vec![37, 80, 68, 70])).unwrap(),
+        r#"{"type":"FileChunk","chat_id":123456789,"upload_id":"abc","data":"JVBERg=="}"#
+    );
+
+    // Every padding length, and every byte value.
+    for data in [
+        vec![],
+        vec![0],
+        vec![0, 1],
+        (0..=255).collect(),
+        vec![255; 1000],
+    ] {
+        let json = serde_json::to_string(&chunk(data.clone())).unwrap();
+        assert_eq!(
+            serde_json::from_str::<WsRequest>(&json).unwrap(),
+            chunk(data)
+        );
+    }
+}
+
+#[test]
+fn malformed_base64_is_rejected() {
+    for data in ["JVBERg=", "JV=ERg==", "JVBE*g==", "JQ===", "[37,80,68,70]"] {
+        let json = format!(
+            r#"{{"type":"FileChunk","chat_id":1,"upload_id":"abc","data":"{}"}}"#,
+            data
+        );
+        assert!(
+            serde_json::from_str::<WsRequest>(&json).is_err(),
+            "{}",
+            data
+        );
+    }
+}
@@ -2,57 +2,118 @@ use leptos::prelude::*;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
-use crate::features::{use_messaging, use_tasks, use_websocket};
-use crate::shared::{Chat, Task, ViewMode};
-use crate::widgets::{ChatList, ChatWindow, Sidebar, TaskList};
+use crate::features::{
+    delete_messages, edit_message, jump_to_message, use_history, use_messaging, use_read_receipts,
+    use_tasks, use_typing_indicator, use_websocket,
+};
+use crate::shared::utils::{load_parse_mode, scroll_to_message};
+use crate::shared::{Chat, ConnectionState, Message, PeerActivity, StoredAccount, Task, ViewMode};
+use crate::widgets::{ChatList, ChatWindow, Sidebar, TaskList, TaskReminders};
 
 #[component]
-pub fn App() -> impl IntoView {
+pub fn App(
+    session_id: String,
+    accounts: RwSignal<Vec<StoredAccount>>,
+    #[prop(into)] on_switch_account: Callback<String>,
+    #[prop(into)] on_add_account: Callback<()>,
+    #[prop(into)] on_logout: Callback<()>,
+) -> impl IntoView {
     let selected_chat = RwSignal::new(None::<i64>);
     let input_value = RwSignal::new(String::new());
     let search_query = RwSignal::new(String::new());
+    let task_query = RwSignal::new(String::new());
     let sidebar_width = RwSignal::new(384);
     let is_resizing = RwSignal::new(false);
     let view_mode = RwSignal::new(ViewMode::Chats);
     let show_archived = RwSignal::new(false);
 
     let messages_end = NodeRef::<leptos::html::Div>::new();
+    let messages_container = NodeRef::<leptos::html::Div>::new();
+    let scroll_anchor = RwSignal::new(None::<i32>);
 
     let tasks = RwSignal::new(Vec::<Task>::new());
-    let next_task_id = RwSignal::new(1);
+    // Tasks that fell due and still wait for a snooze, a tick or a dismissal.
+    let reminders = RwSignal::new(Vec::<Task>::new());
 
     let chats = RwSignal::new(Vec::<Chat>::new());
-    let ws_connected = RwSignal::new(false);
+    let connection_state = RwSignal::new(ConnectionState::Connecting);
     let is_loading_messages = RwSignal::new(false);
-    let next_message_id = RwSignal::new(100);
-    let last_message_count = RwSignal::new(std::collections::HashMap::<i64, usize>::new());
-    let last_update_time = RwSignal::new(std::collections::HashMap::<i64, f64>::new());
+    let is_loading_older = RwSignal::new(false);
+    let next_message_id = RwSignal::new(-1);
+
+    let reply_to = RwSignal::new(None::<Message>);
+    let editing = RwSignal::new(None::<Message>);
+    let parse_mode = RwSignal::new(load_parse_mode());
+    let selected_messages = RwSignal::new(Vec::<i32>::new());
+    let forwarding = RwSignal::new(None::<(i64, Vec<i32>)>);
+    let activity = RwSignal::new(PeerActivity::default());
+    // A message jumped to from search, outlined until it fades.
+    let highlighted = RwSignal::new(None::<i32>);
+    // Lets media load without the login session in its URLs; set by every `Hello`.
+    let media_token = RwSignal::new(None::<String>);
 
     use_websocket(
+        session_id.clone(),
         chats,
-        ws_connected,
+        connection_state,
         is_loading_messages,
-        last_message_count,
-        last_update_time,
+        is_loading_older,
         selected_chat,
+        activity,
+        tasks,
+        reminders,
+        media_token,
     );
 
-    let (send_message, get_messages) = use_messaging(chats, selected_chat, next_message_id);
+    let (send_message, get_messages, send_file, retry_message, discard_message) =
+        use_messaging(chats, selected_chat, next_message_id);
     let get_messages_for_effect = get_messages.clone();
+    let load_older = use_history(
+        chats,
+        selected_chat,
+        is_loading_older,
+        scroll_anchor,
+        messages_container,
+    );
 
-    let create_task = use_tasks(chats, selected_chat, tasks, next_task_id, view_mode);
+    use_read_receipts(chats, selected_chat);
+    use_typing_indicator(selected_chat, input_value, editing);
+
+    let create_task = use_tasks(chats, view_mode);
+
+    // A reply, edit or selection belongs to the chat it was started in.
+    Effect::new(move |_| {
+        selected_chat.track();
+        reply_to.set(None);
+        if editing.get_untracked().is_some() {
+            editing.set(None);
+            input_value.set(String::new());
+        }
+        selected_messages.set(Vec::new());
+    });
 
     Effect::new(move |_| {
         get_messages_for_effect();
+        if let Some(anchor) = scroll_anchor.get_untracked() {
+            if let Some(container) = messages_container.get() {
+                container.set_scroll_top(container.scroll_height() - anchor);
+                scroll_anchor.set(None);
+                return;
+            }
+        }
+        // Keep a message jumped to in view while the pages around it load.
+        if highlighted.get_untracked().is_some_and(scroll_to_message) {
+            return;
+        }
         if let Some(el) = messages_end.get() {
-            let _ = el.scroll_into_view();
+            el.scroll_into_view();
         }
     });
 
     let handle_mouse_move = move |e: web_sys::MouseEvent| {
         if is_resizing.get() {
             let new_width = e.client_x();
-            if new_width >= 280 && new_width <= 600 {
+            if (280..=600).contains(&new_width) {
                 sidebar_width.set(new_width);
             }
         }
@@ -82,7 +143,22 @@ pub fn App() -> impl IntoView {
 
     view! {
         <div class="flex h-screen antialiased select-none" style="background: #1f1d1d">
-            <Sidebar view_mode />
+            <Sidebar
+                view_mode
+                accounts
+                active_session=session_id.clone()
+                on_switch_account
+                on_add_account
+                on_logout
+            />
+
+            <TaskReminders
+                tasks
+                reminders
+                on_open=Callback::new(move |(chat_id, message_id)| {
+                    jump_to_message(chats, selected_chat, is_loading_messages, highlighted, chat_id, message_id)
+                })
+            />
 
             <Show
                 when=move || view_mode.get() == ViewMode::Chats
@@ -92,10 +168,31 @@ pub fn App() -> impl IntoView {
                         style=move || format!("width: {}px; background: #1f1d1d", sidebar_width.get())
                     >
                         <div class="p-4" style="background: #1f1d1d">
+                            <div class="mb-4">
+                                <div class="relative">
+                                    <input
+                                        type="text"
+                                        placeholder="Search tasks..."
+                                        class="w-full px-10 py-2 rounded-full text-white placeholder-white/60 outline-none text-xs"
+                                        style="background: rgba(84,54,57,0.48)"
+                                        prop:value=task_query
+                                        on:input=move |ev| task_query.set(event_target_value(&ev))
+                                    />
+                                    <div class="absolute left-3 top-1/2 -translate-y-1/2 text-white/60 text-lg">
+                                        "🔍"
+                                    </div>
+                                </div>
+                            </div>
                             <h1 class="text-white font-semibold text-xl mb-3">"Tasks"</h1>
                         </div>
                         <div class="flex-1 overflow-y-auto" style="background: #1f1d1d">
-                            <TaskList tasks />
+                            <TaskList
+                                tasks
+                                query=task_query
+                                on_open=Callback::new(move |(chat_id, message_id)| {
+                                    jump_to_message(chats, selected_chat, is_loading_messages, highlighted, chat_id, message_id)
+                                })
+                            />
                         </div>
                     </div>
                 }
@@ -107,8 +204,13 @@ pub fn App() -> impl IntoView {
                     show_archived
                     sidebar_width
                     view_mode
-                    ws_connected
+                    connection_state
                     is_loading_messages
+                    forwarding
+                    activity
+                    on_open_message=Callback::new(move |(chat_id, message_id)| {
+                        jump_to_message(chats, selected_chat, is_loading_messages, highlighted, chat_id, message_id)
+                    })
                 />
             </Show>
 
@@ -133,33 +235,74 @@ pub fn App() -> impl IntoView {
                 {
                     let get_messages_clone = get_messages.clone();
                     let send_message_clone = send_message.clone();
+                    let send_file_clone = send_file.clone();
+                    let retry_message_clone = retry_message.clone();
+                    let discard_message_clone = discard_message.clone();
+                    let load_older_clone = load_older.clone();
                     let create_task_clone = create_task.clone();
 
                     move || {
                         let chat_id = selected_chat.get().unwrap();
-                        let chat_name = chats.with(|chats_list| {
+                        let (chat_name, read_outbox_max_id) = chats.with(|chats_list| {
                             chats_list
                                 .iter()
-                                .find(|c| c.id == chat_id as i64)
-                                .map(|c| c.name.clone())
-                                .unwrap_or_else(|| "Chat".to_string())
+                                .find(|c| c.id == chat_id)
+                                .map(|c| (c.name.clone(), c.read_outbox_max_id))
+                                .unwrap_or_else(|| ("Chat".to_string(), 0))
                         });
                         let messages = get_messages_clone();
 
                         let send_msg = send_message_clone.clone();
+                        let send_file = send_file_clone.clone();
+                        let retry_message = retry_message_clone.clone();
+                        let discard_message = discard_message_clone.clone();
+                        let load_older = load_older_clone.clone();
                         let create_task = create_task_clone.clone();
 
                         view! {
                             <ChatWindow
                                 chat_id
                                 chat_name
+                                read_outbox_max_id
+                                activity
+                                highlighted
+                                media_token
                                 messages
                                 input_value
-                                ws_connected
+                                connection_state
                                 is_loading_messages
                                 messages_end
-                                on_send=Callback::new(move |text| send_msg(text))
-                                on_create_task=Callback::new(move |text| create_task(text))
+                                messages_container
+                                is_loading_older
+                                on_load_older=Callback::new(move |_| load_older())
+                                reply_to
+                                parse_mode
+                                editing
+                                selected_messages
+                                on_send=Callback::new(move |text| {
+                                    if let Some(message) = editing.get_untracked() {
+                                        edit_message(chat_id, message.id, text, parse_mode.get_untracked());
+                                        editing.set(None);
+                                    } else {
+                                        send_msg(
+                                            text,
+                                            reply_to.get_untracked().map(|m| m.id),
+                                            parse_mode.get_untracked(),
+                                        );
+                                        reply_to.set(None);
+                                    }
+                                })
+                                on_send_file=Callback::new(move |(file_name, data, caption)| send_file(file_name, data, caption))
+                                on_retry=Callback::new(retry_message)
+                                on_discard=Callback::new(discard_message)
+                                on_delete=Callback::new(move |(ids, revoke)| {
+                                    delete_messages(chat_id, ids, revoke)
+                                })
+                                on_forward=Callback::new(move |ids| {
+                                    forwarding.set(Some((chat_id, ids)));
+                                    selected_messages.set(Vec::new());
+                                })
+                                on_create_task=Callback::new(move |message| create_task(chat_id, message))
                             />
                         }
                     }
@@ -14,24 +14,93 @@ struct VerifyRequest {
     code: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct VerifyPasswordRequest {
+    phone: String,
+    password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum NextAuthStep {
+    Code,
+    Password,
+    Done,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct AuthResponse {
     success: bool,
     message: String,
     session_id: Option<String>,
+    #[serde(default)]
+    next_step: Option<NextAuthStep>,
+    #[serde(default)]
+    password_hint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SessionRequest {
+    session_id: String,
+}
+
+/// Returns `false` only when the backend explicitly rejects the session, so a
+/// stored login survives the backend being briefly unreachable.
+pub async fn check_session(session_id: &str) -> bool {
+    let body = serde_json::to_string(&SessionRequest {
+        session_id: session_id.to_string(),
+    })
+    .unwrap();
+
+    let result = Request::post("http://127.0.0.1:3000/auth/session")
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await;
+
+    match result {
+        Ok(response) => response.status() != 401,
+        Err(e) => {
+            web_sys::console::warn_1(&format!("Failed to check session: {}", e).into());
+            true
+        }
+    }
+}
+
+pub async fn logout(session_id: &str) {
+    let body = serde_json::to_string(&SessionRequest {
+        session_id: session_id.to_string(),
+    })
+    .unwrap();
+
+    let result = Request::post("http://127.0.0.1:3000/auth/logout")
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await;
+
+    if let Err(e) = result {
+        web_sys::console::error_1(&format!("Failed to log out: {}", e).into());
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum AuthStep {
     Phone,
     Code,
+    Password,
     Authenticated,
 }
 
 #[component]
-pub fn AuthForm(on_authenticated: impl Fn(String) + 'static + Copy + Send + Sync) -> impl IntoView {
+pub fn AuthForm(
+    on_authenticated: impl Fn(String, String) + 'static + Copy + Send + Sync,
+    #[prop(optional_no_strip)] on_cancel: Option<Callback<()>>,
+) -> impl IntoView {
     let phone = RwSignal::new(String::new());
     let code = RwSignal::new(String::new());
+    let password = RwSignal::new(String::new());
+    let password_hint = RwSignal::new(None::<String>);
     let step = RwSignal::new(AuthStep::Phone);
     let error = RwSignal::new(None::<String>);
     let loading = RwSignal::new(false);
@@ -101,9 +170,16 @@ pub fn AuthForm(on_authenticated: impl Fn(String) + 'static + Copy + Send + Sync
                 Ok(response) => {
                     if response.ok() {
                         let auth_response: AuthResponse = response.json().await.unwrap();
-                        if let Some(session_id) = auth_response.session_id {
-                            step.set(AuthStep::Authenticated);
-                            on_authenticated(session_id);
+                        match (auth_response.next_step, auth_response.session_id) {
+                            (Some(NextAuthStep::Password), _) => {
+                                password_hint.set(auth_response.password_hint);
+                                step.set(AuthStep::Password);
+                            }
+                            (_, Some(session_id)) => {
+                                step.set(AuthStep::Authenticated);
+                                on_authenticated(session_id, phone_val);
+                            }
+                            _ => error.set(Some(auth_response.message)),
                         }
                     } else {
                         let text = response
@@ -120,6 +196,49 @@ pub fn AuthForm(on_authenticated: impl Fn(String) + 'static + Copy + Send + Sync
         });
     };
 
+    let verify_password = move || {
+        spawn_local(async move {
+            loading.set(true);
+            error.set(None);
+
+            let body = serde_json::to_string(&VerifyPasswordRequest {
+                phone: phone.get(),
+                password: password.get(),
+            })
+            .unwrap();
+
+            let result = Request::post("http://127.0.0.1:3000/auth/verify-password")
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await;
+
+            loading.set(false);
+
+            match result {
+                Ok(response) => {
+                    if response.ok() {
+                        let auth_response: AuthResponse = response.json().await.unwrap();
+                        if let Some(session_id) = auth_response.session_id {
+                            password.set(String::new());
+                            step.set(AuthStep::Authenticated);
+                            on_authenticated(session_id, phone.get_untracked());
+                        }
+                    } else {
+                        let text = match response.json::<AuthResponse>().await {
+                            Ok(auth_response) => auth_response.message,
+                            Err(_) => "Invalid password".to_string(),
+                        };
+                        error.set(Some(text));
+                    }
+                }
+                Err(e) => {
+                    error.set(Some(format!("Network error: {}", e)));
+                }
+            }
+        });
+    };
+
     view! {
         <div class="min-h-screen flex items-center justify-center bg-gray-100 dark:bg-gray-900">
             <div class="bg-white dark:bg-gray-800 p-8 rounded-2xl shadow-xl w-full max-w-md">
@@ -137,43 +256,89 @@ pub fn AuthForm(on_authenticated: impl Fn(String) + 'static + Copy + Send + Sync
                 <Show
                     when=move || step.get() == AuthStep::Phone
                     fallback=move || view! {
-                        <div class="space-y-4">
-                            <div>
-                                <label class="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-2">
-                                    Verification Code
-                                </label>
-                                <input
-                                    type="text"
-                                    placeholder="12345"
-                                    class="w-full px-4 py-3 rounded-lg border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-white outline-none focus:border-blue-500 transition"
-                                    prop:value=code
-                                    on:input=move |ev| code.set(event_target_value(&ev))
-                                    on:keydown=move |ev| {
-                                        if ev.key() == "Enter" && !loading.get() {
-                                            verify_code();
+                        <Show
+                            when=move || step.get() == AuthStep::Password
+                            fallback=move || view! {
+                                <div class="space-y-4">
+                                    <div>
+                                        <label class="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-2">
+                                            Verification Code
+                                        </label>
+                                        <input
+                                            type="text"
+                                            placeholder="12345"
+                                            class="w-full px-4 py-3 rounded-lg border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-white outline-none focus:border-blue-500 transition"
+                                            prop:value=code
+                                            on:input=move |ev| code.set(event_target_value(&ev))
+                                            on:keydown=move |ev| {
+                                                if ev.key() == "Enter" && !loading.get() {
+                                                    verify_code();
+                                                }
+                                            }
+                                        />
+                                        <p class="text-xs text-gray-500 dark:text-gray-400 mt-2">
+                                            Check your SMS or email for the code
+                                        </p>
+                                    </div>
+
+                                    <button
+                                        class="w-full px-6 py-3 bg-blue-600 hover:bg-blue-700 disabled:bg-gray-400 text-white font-semibold rounded-lg transition shadow-lg"
+                                        on:click=move |_| verify_code()
+                                        disabled=move || loading.get()
+                                    >
+                                        {move || if loading.get() { "Verifying..." } else { "Sign In" }}
+                                    </button>
+
+                                    <button
+                                        class="w-full px-6 py-3 text-blue-600 dark:text-blue-400 font-semibold"
+                                        on:click=move |_| step.set(AuthStep::Phone)
+                                    >
+                                        "Change phone number"
+                                    </button>
+                                </div>
+                            }
+                        >
+                            <div class="space-y-4">
+                                <div>
+                                    <label class="block text-sm font-medium text-gray-700 dark:text-gray-300 mb-2">
+                                        Cloud Password
+                                    </label>
+                                    <input
+                                        type="password"
+                                        placeholder="Password"
+                                        class="w-full px-4 py-3 rounded-lg border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-white outline-none focus:border-blue-500 transition"
+                                        prop:value=password
+                                        on:input=move |ev| password.set(event_target_value(&ev))
+                                        on:keydown=move |ev| {
+                                            if ev.key() == "Enter" && !loading.get() {
+                                                verify_password();
+                                            }
                                         }
-                                    }
-                                />
-                                <p class="text-xs text-gray-500 dark:text-gray-400 mt-2">
-                                    Check your SMS or email for the code
-                                </p>
-                            </div>
+                                    />
+                                    <p class="text-xs text-gray-500 dark:text-gray-400 mt-2">
+                                        {move || match password_hint.get() {
+                                            Some(hint) if !hint.is_empty() => format!("Hint: {}", hint),
+                                            _ => "Your account is protected with two-step verification".to_string(),
+                                        }}
+                                    </p>
+                                </div>
 
-                            <button
-                                class="w-full px-6 py-3 bg-blue-600 hover:bg-blue-700 disabled:bg-gray-400 text-white font-semibold rounded-lg transition shadow-lg"
-                                on:click=move |_| verify_code()
-                                disabled=move || loading.get()
-                            >
-                                {move || if loading.get() { "Verifying..." } else { "Sign In" }}
-                            </button>
+                                <button
+                                    class="w-full px-6 py-3 bg-blue-600 hover:bg-blue-700 disabled:bg-gray-400 text-white font-semibold rounded-lg transition shadow-lg"
+                                    on:click=move |_| verify_password()
+                                    disabled=move || loading.get()
+                                >
+                                    {move || if loading.get() { "Verifying..." } else { "Sign In" }}
+                                </button>
 
-                            <button
-                                class="w-full px-6 py-3 text-blue-600 dark:text-blue-400 font-semibold"
-                                on:click=move |_| step.set(AuthStep::Phone)
-                            >
-                                "Change phone number"
-                            </button>
-                        </div>
+                                <button
+                                    class="w-full px-6 py-3 text-blue-600 dark:text-blue-400 font-semibold"
+                                    on:click=move |_| step.set(AuthStep::Phone)
+                                >
+                                    "Change phone number"
+                                </button>
+                            </div>
+                        </Show>
                     }
                 >
                     <div class="space-y-4">
@@ -202,6 +367,15 @@ pub fn AuthForm(on_authenticated: impl Fn(String) + 'static + Copy + Send + Sync
                         >
                             {move || if loading.get() { "Sending..." } else { "Send Code" }}
                         </button>
+
+                        {on_cancel.map(|on_cancel| view! {
+                            <button
+                                class="w-full px-6 py-3 text-blue-600 dark:text-blue-400 font-semibold"
+                                on:click=move |_| on_cancel.run(())
+                            >
+                                "Back to chats"
+                            </button>
+                        })}
                     </div>
                 </Show>
             </div>
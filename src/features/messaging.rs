@@ -1,64 +1,238 @@
 use leptos::prelude::*;
-use crate::shared::{Chat, Message};
-use crate::shared::api::WsRequest;
+use std::collections::HashMap;
+use crate::shared::{Chat, DeliveryState, Message};
+use crate::shared::api::{ParseMode, WsRequest};
 use crate::shared::utils::get_current_time;
-use crate::features::websocket::WS_REF;
+use crate::features::websocket::{request, send_if_connected, send_or_queue};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
 
+/// Files are streamed to the backend in pieces so large uploads never have to fit in one frame.
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Telegram drops a typing action after about six seconds, so it is repeated a bit sooner.
+const TYPING_REPEAT_MS: f64 = 5000.0;
+
+/// Correlates an outgoing message with the backend's reply about it.
+fn new_client_id() -> String {
+    format!(
+        "{}-{}",
+        js_sys::Date::now() as u64,
+        (js_sys::Math::random() * 1e9) as u64
+    )
+}
+
+/// Temporary ids count down from -1 so they never collide with Telegram's.
+fn next_temp_id(next_message_id: RwSignal<i32>) -> i32 {
+    let msg_id = next_message_id.get_untracked();
+    next_message_id.set(msg_id - 1);
+    msg_id
+}
+
+fn send_text(
+    chat_id: i64,
+    text: String,
+    client_id: String,
+    reply_to: Option<i32>,
+    parse_mode: Option<ParseMode>,
+) {
+    web_sys::console::log_1(&format!("📤 Sending message to chat {}: {}", chat_id, text).into());
+    send_or_queue(&WsRequest::SendMessage {
+        chat_id,
+        text,
+        client_id: Some(client_id),
+        reply_to,
+        parse_mode,
+    });
+}
+
+/// Forwards `ids` from `from_chat` to `to_chat`. The copies are added to the target
+/// chat when the backend replies with `MessagesForwarded`.
+pub fn forward_messages(from_chat: i64, ids: Vec<i32>, to_chat: i64) {
+    web_sys::console::log_1(
+        &format!("↪️ Forwarding {} messages from chat {} to chat {}", ids.len(), from_chat, to_chat)
+            .into(),
+    );
+    spawn_local(async move {
+        let result = request(WsRequest::ForwardMessages {
+            from_chat,
+            ids,
+            to_chat,
+        })
+        .await;
+        if let Err(e) = result {
+            web_sys::console::error_1(&format!("Failed to forward messages: {}", e).into());
+        }
+    });
+}
+
+/// Streams the file while the socket is open. Nothing is queued offline, since the
+/// chunks would sit in memory twice; returns false so the message can be retried.
+fn upload_file(chat_id: i64, upload_id: String, file: &OutgoingFile) -> bool {
+    web_sys::console::log_1(
+        &format!(
+            "📤 Uploading {} ({} bytes) to chat {}",
+            file.file_name,
+            file.data.len(),
+            chat_id
+        )
+        .into(),
+    );
+
+    let started = send_if_connected(&WsRequest::StartFileUpload {
+        chat_id,
+        upload_id: upload_id.clone(),
+        file_name: file.file_name.clone(),
+        file_size: file.data.len() as u64,
+        caption: file.caption.clone(),
+    });
+    if !started {
+        web_sys::console::warn_1(&"📮 WebSocket offline, not uploading".into());
+        return false;
+    }
+
+    for chunk in file.data.chunks(FILE_CHUNK_SIZE) {
+        let sent = send_if_connected(&WsRequest::FileChunk {
+            chat_id,
+            upload_id: upload_id.clone(),
+            data: chunk.to_vec(),
+        });
+        if !sent {
+            return false;
+        }
+    }
+
+    send_if_connected(&WsRequest::FinishFileUpload { chat_id, upload_id })
+}
+
+/// Replaces a message's text; the bubble updates when the backend answers with
+/// `MessageEdited`.
+pub fn edit_message(chat_id: i64, message_id: i32, text: String, parse_mode: Option<ParseMode>) {
+    web_sys::console::log_1(&format!("✏️ Editing message {} in chat {}", message_id, chat_id).into());
+    spawn_local(async move {
+        let result = request(WsRequest::EditMessage {
+            chat_id,
+            message_id,
+            text,
+            parse_mode,
+        })
+        .await;
+        if let Err(e) = result {
+            web_sys::console::error_1(&format!("Failed to edit message: {}", e).into());
+        }
+    });
+}
+
+/// Deletes messages for everyone with `revoke`, otherwise only for this account.
+/// The bubbles go away when the backend answers with `MessagesDeleted`.
+pub fn delete_messages(chat_id: i64, ids: Vec<i32>, revoke: bool) {
+    web_sys::console::log_1(
+        &format!("🗑️ Deleting {} messages in chat {} (revoke: {})", ids.len(), chat_id, revoke).into(),
+    );
+    spawn_local(async move {
+        let result = request(WsRequest::DeleteMessages {
+            chat_id,
+            ids,
+            revoke,
+        })
+        .await;
+        if let Err(e) = result {
+            web_sys::console::error_1(&format!("Failed to delete messages: {}", e).into());
+        }
+    });
+}
+
+/// File contents kept until the upload is confirmed, so a failed one can be retried.
+struct OutgoingFile {
+    file_name: String,
+    data: Vec<u8>,
+    caption: String,
+}
+
+fn set_delivery(
+    chats: RwSignal<Vec<Chat>>,
+    client_id: &str,
+    delivery: DeliveryState,
+) -> Option<(i64, Message)> {
+    let mut found = None;
+    chats.update(|chats_list| {
+        for chat in chats_list.iter_mut() {
+            if let Some(message) = chat
+                .messages
+                .iter_mut()
+                .find(|m| m.client_id.as_deref() == Some(client_id))
+            {
+                message.delivery = delivery;
+                found = Some((chat.id, message.clone()));
+                return;
+            }
+        }
+    });
+    found
+}
+
+#[allow(clippy::type_complexity)]
 pub fn use_messaging(
     chats: RwSignal<Vec<Chat>>,
     selected_chat: RwSignal<Option<i64>>,
     next_message_id: RwSignal<i32>,
-) -> (impl Fn(String) + Clone, impl Fn() -> Vec<Message> + Clone) {
-    let send_message = move |text: String| {
+) -> (
+    impl Fn(String, Option<i32>, Option<ParseMode>) + Clone,
+    impl Fn() -> Vec<Message> + Clone,
+    impl Fn(String, Vec<u8>, String) + Clone,
+    impl Fn(String) + Clone,
+    impl Fn(String) + Clone,
+) {
+    let outgoing_files = StoredValue::new(HashMap::<String, OutgoingFile>::new());
+
+    // Drop file contents once their message is confirmed or discarded.
+    Effect::new(move |_| {
+        let unconfirmed: Vec<String> = chats.with(|chats_list| {
+            chats_list
+                .iter()
+                .flat_map(|c| c.messages.iter())
+                .filter(|m| m.delivery != DeliveryState::Sent)
+                .filter_map(|m| m.client_id.clone())
+                .collect()
+        });
+        outgoing_files.update_value(|files| files.retain(|id, _| unconfirmed.contains(id)));
+    });
+
+    let send_message = move |text: String, reply_to: Option<i32>, parse_mode: Option<ParseMode>| {
         if let Some(chat_id) = selected_chat.get() {
             let current_time = get_current_time();
-            let msg_id = next_message_id.get();
-            next_message_id.set(msg_id + 1);
+            let msg_id = next_temp_id(next_message_id);
+            let client_id = new_client_id();
 
             chats.update(|chats_list| {
-                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id as i64) {
+                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id) {
+                    let reply_preview = reply_to.and_then(|id| {
+                        chat.messages.iter().find(|m| m.id == id).map(Message::reply_preview)
+                    });
                     chat.messages.push(Message {
                         id: msg_id,
                         sender_name: "You".to_string(),
                         text: text.clone(),
+                        entities: Vec::new(),
                         is_outgoing: true,
-                        timestamp: js_sys::Date::now() as i64,
+                        timestamp: (js_sys::Date::now() / 1000.0) as i64,
+                        edit_date: None,
                         is_file: false,
                         file_name: None,
+                        media: None,
+                        reply_to_message_id: reply_to,
+                        reply_preview,
+                        delivery: DeliveryState::Pending,
+                        client_id: Some(client_id.clone()),
+                        parse_mode,
                     });
                     chat.last_message = text.clone();
                     chat.time = current_time;
                 }
             });
 
-            WS_REF.with(|ws_ref| {
-                if let Some(ref ws) = *ws_ref.borrow() {
-                    let request = WsRequest::SendMessage {
-                        chat_id,
-                        text: text.clone(),
-                    };
-                    match serde_json::to_string(&request) {
-                        Ok(json) => {
-                            web_sys::console::log_1(
-                                &format!("📤 Sending message to chat {}: {}", chat_id, text).into(),
-                            );
-                            if let Err(e) = ws.send_with_str(&json) {
-                                web_sys::console::error_1(
-                                    &format!("Failed to send message via WebSocket: {:?}", e)
-                                        .into(),
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            web_sys::console::error_1(
-                                &format!("Failed to serialize SendMessage request: {:?}", e).into(),
-                            );
-                        }
-                    }
-                } else {
-                    web_sys::console::error_1(&"WebSocket not connected".into());
-                }
-            });
+            send_text(chat_id, text, client_id, reply_to, parse_mode);
         }
     };
 
@@ -67,10 +241,10 @@ pub fn use_messaging(
             chats.with(|chats_list| {
                 chats_list
                     .iter()
-                    .find(|c| c.id == chat_id as i64)
+                    .find(|c| c.id == chat_id)
                     .map(|c| {
                         let mut messages = c.messages.clone();
-                        messages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+                        messages.sort_by_key(|a| a.timestamp);
                         messages
                     })
                     .unwrap_or_default()
@@ -80,6 +254,252 @@ pub fn use_messaging(
         }
     };
 
-    (send_message, get_messages)
+    let send_file = move |file_name: String, data: Vec<u8>, caption: String| {
+        let Some(chat_id) = selected_chat.get_untracked() else {
+            return;
+        };
+
+        let msg_id = next_temp_id(next_message_id);
+        let upload_id = new_client_id();
+
+        chats.update(|chats_list| {
+            if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id) {
+                chat.messages.push(Message {
+                    id: msg_id,
+                    sender_name: "You".to_string(),
+                    text: caption.clone(),
+                    entities: Vec::new(),
+                    is_outgoing: true,
+                    timestamp: (js_sys::Date::now() / 1000.0) as i64,
+                    edit_date: None,
+                    is_file: true,
+                    file_name: Some(file_name.clone()),
+                    media: None,
+                    reply_to_message_id: None,
+                    reply_preview: None,
+                    delivery: DeliveryState::Pending,
+                    client_id: Some(upload_id.clone()),
+                    parse_mode: None,
+                });
+                chat.last_message = format!("📎 {}", file_name);
+                chat.time = get_current_time();
+            }
+        });
+
+        let file = OutgoingFile {
+            file_name,
+            data,
+            caption,
+        };
+        if !upload_file(chat_id, upload_id.clone(), &file) {
+            set_delivery(chats, &upload_id, DeliveryState::Failed);
+        }
+        outgoing_files.update_value(|files| {
+            files.insert(upload_id, file);
+        });
+    };
+
+    let retry_message = move |client_id: String| {
+        let Some((chat_id, message)) = set_delivery(chats, &client_id, DeliveryState::Pending)
+        else {
+            return;
+        };
+
+        if !message.is_file {
+            send_text(
+                chat_id,
+                message.text,
+                client_id,
+                message.reply_to_message_id,
+                message.parse_mode,
+            );
+            return;
+        }
+
+        // A new upload id, since the backend may still hold part of the failed one.
+        let upload_id = new_client_id();
+        let file = outgoing_files.try_update_value(|files| files.remove(&client_id)).flatten();
+        match file {
+            Some(file) => {
+                chats.update(|chats_list| {
+                    if let Some(m) = chats_list
+                        .iter_mut()
+                        .flat_map(|c| c.messages.iter_mut())
+                        .find(|m| m.client_id.as_deref() == Some(client_id.as_str()))
+                    {
+                        m.client_id = Some(upload_id.clone());
+                    }
+                });
+                if !upload_file(chat_id, upload_id.clone(), &file) {
+                    set_delivery(chats, &upload_id, DeliveryState::Failed);
+                }
+                outgoing_files.update_value(|files| {
+                    files.insert(upload_id, file);
+                });
+            }
+            None => {
+                web_sys::console::error_1(&"File contents are no longer available".into());
+                set_delivery(chats, &client_id, DeliveryState::Failed);
+            }
+        }
+    };
+
+    let discard_message = move |client_id: String| {
+        chats.update(|chats_list| {
+            for chat in chats_list.iter_mut() {
+                chat.messages.retain(|m| m.client_id.as_deref() != Some(client_id.as_str()));
+            }
+        });
+    };
+
+    (send_message, get_messages, send_file, retry_message, discard_message)
+}
+
+/// Marks the open chat read on Telegram whenever it shows incoming messages newer
+/// than the last read one, as long as the page is actually visible.
+pub fn use_read_receipts(chats: RwSignal<Vec<Chat>>, selected_chat: RwSignal<Option<i64>>) {
+    let document = web_sys::window().and_then(|w| w.document());
+    let is_visible = RwSignal::new(document.as_ref().is_none_or(|d| !d.hidden()));
+
+    if let Some(document) = document {
+        let listener_document = document.clone();
+        let on_visibility_change = Closure::wrap(Box::new(move || {
+            is_visible.set(!listener_document.hidden());
+        }) as Box<dyn FnMut()>);
+        let _ = document.add_event_listener_with_callback(
+            "visibilitychange",
+            on_visibility_change.as_ref().unchecked_ref(),
+        );
+        on_visibility_change.forget();
+    }
+
+    Effect::new(move |_| {
+        if !is_visible.get() {
+            return;
+        }
+        let Some(chat_id) = selected_chat.get() else {
+            return;
+        };
+
+        let unread_max_id = chats.with(|chats_list| {
+            let chat = chats_list.iter().find(|c| c.id == chat_id)?;
+            let newest = chat
+                .messages
+                .iter()
+                .filter(|m| !m.is_outgoing && m.id > 0)
+                .map(|m| m.id)
+                .max()?;
+            (newest > chat.read_inbox_max_id).then_some(newest)
+        });
+        let Some(max_id) = unread_max_id else {
+            return;
+        };
+
+        chats.update(|chats_list| {
+            if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id) {
+                chat.read_inbox_max_id = max_id;
+                chat.unread_count = 0;
+            }
+        });
+        send_or_queue(&WsRequest::MarkRead { chat_id, max_id });
+    });
+}
+
+/// Shows the open chat that we are typing while the composer text changes, repeating
+/// it every few seconds, and cancels it once the composer is emptied.
+pub fn use_typing_indicator(
+    selected_chat: RwSignal<Option<i64>>,
+    input_value: RwSignal<String>,
+    editing: RwSignal<Option<Message>>,
+) {
+    // Chat and time of the last "typing" we sent, while it may still be showing.
+    let last_sent = StoredValue::new(None::<(i64, f64)>);
+
+    Effect::new(move |previous: Option<String>| {
+        let text = input_value.get();
+        let Some(chat_id) = selected_chat.get_untracked() else {
+            return text;
+        };
+        if previous.is_none_or(|previous| previous == text) {
+            return text;
+        }
+
+        let now = js_sys::Date::now();
+        let is_empty = text.trim().is_empty();
+        match last_sent.get_value() {
+            Some((sent_chat, _)) if is_empty => {
+                last_sent.set_value(None);
+                send_if_connected(&WsRequest::SetTyping {
+                    chat_id: sent_chat,
+                    typing: false,
+                });
+            }
+            Some((sent_chat, at)) if sent_chat == chat_id && now - at < TYPING_REPEAT_MS => {}
+            // Fixing an old message is not something the other side should see.
+            _ if is_empty || editing.get_untracked().is_some() => {}
+            _ => {
+                last_sent.set_value(Some((chat_id, now)));
+                send_if_connected(&WsRequest::SetTyping {
+                    chat_id,
+                    typing: true,
+                });
+            }
+        }
+        text
+    });
 }
 
+/// Returns a loader that requests the page of history just before the oldest loaded
+/// message, remembering the scroll distance from the bottom so the view stays put
+/// once the older messages are prepended.
+pub fn use_history(
+    chats: RwSignal<Vec<Chat>>,
+    selected_chat: RwSignal<Option<i64>>,
+    is_loading_older: RwSignal<bool>,
+    scroll_anchor: RwSignal<Option<i32>>,
+    messages_container: NodeRef<leptos::html::Div>,
+) -> impl Fn() + Clone {
+    move || {
+        if is_loading_older.get_untracked() {
+            return;
+        }
+        let Some(chat_id) = selected_chat.get_untracked() else {
+            return;
+        };
+
+        let oldest_id = chats.with_untracked(|chats_list| {
+            chats_list
+                .iter()
+                .find(|c| c.id == chat_id)
+                .filter(|c| c.has_more_messages)
+                .and_then(|c| c.messages.iter().min_by_key(|m| m.timestamp))
+                .map(|m| m.id)
+        });
+        let Some(before_id) = oldest_id else {
+            return;
+        };
+
+        if let Some(container) = messages_container.get_untracked() {
+            scroll_anchor.set(Some(container.scroll_height() - container.scroll_top()));
+        }
+
+        web_sys::console::log_1(
+            &format!("📜 Loading messages before {} in chat {}", before_id, chat_id).into(),
+        );
+
+        is_loading_older.set(true);
+        spawn_local(async move {
+            let result = request(WsRequest::GetMessages {
+                chat_id,
+                before_id: Some(before_id),
+                limit: None,
+            })
+            .await;
+            if let Err(e) = result {
+                web_sys::console::error_1(&format!("Failed to load older messages: {}", e).into());
+                scroll_anchor.set(None);
+            }
+            is_loading_older.set(false);
+        });
+    }
+}
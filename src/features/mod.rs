@@ -1,8 +1,9 @@
 pub mod messaging;
+pub mod search;
 pub mod tasks;
 pub mod websocket;
 
 pub use messaging::*;
+pub use search::*;
 pub use tasks::*;
 pub use websocket::*;
-
@@ -0,0 +1,195 @@
+use leptos::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use crate::shared::Chat;
+use crate::shared::api::{SearchFilter, WsRequest, WsResponse, WsSearchResult};
+use crate::shared::utils::scroll_to_message;
+use crate::features::websocket::request;
+
+/// Pause after the last keystroke before the query goes to Telegram.
+const SEARCH_DEBOUNCE_MS: u32 = 400;
+/// Older history pages loaded at most while looking for a message to jump to.
+const MAX_JUMP_PAGES: usize = 20;
+/// How long a message jumped to stays highlighted.
+const HIGHLIGHT_MS: u32 = 3000;
+
+/// The running message search: matches so far and whether more can be loaded.
+#[derive(Clone, Copy)]
+pub struct MessageSearch {
+    pub results: RwSignal<Vec<WsSearchResult>>,
+    pub is_searching: RwSignal<bool>,
+    next_offset: RwSignal<Option<u32>>,
+    /// Bumped whenever the search changes, so replies to an older one are dropped.
+    generation: StoredValue<u32>,
+    query: RwSignal<String>,
+    chat_id: Signal<Option<i64>>,
+    filter: RwSignal<SearchFilter>,
+}
+
+impl MessageSearch {
+    pub fn has_more(&self) -> bool {
+        self.next_offset.get().is_some()
+    }
+
+    pub fn load_more(&self) {
+        if self.is_searching.get_untracked() {
+            return;
+        }
+        let Some(offset) = self.next_offset.get_untracked() else {
+            return;
+        };
+        let search = *self;
+        let generation = self.generation.get_value();
+        self.is_searching.set(true);
+        spawn_local(async move { search.fetch(generation, offset).await });
+    }
+
+    async fn fetch(self, generation: u32, offset: u32) {
+        let result = request(WsRequest::SearchMessages {
+            query: self.query.get_untracked(),
+            chat_id: self.chat_id.get_untracked(),
+            filter: self.filter.get_untracked(),
+            offset,
+        })
+        .await;
+        if self.generation.get_value() != generation {
+            return;
+        }
+
+        match result {
+            Ok(WsResponse::SearchResults {
+                results,
+                next_offset,
+                ..
+            }) => {
+                self.results.update(|all| all.extend(results));
+                self.next_offset.set(next_offset);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                web_sys::console::error_1(&format!("Failed to search messages: {}", e).into());
+                self.next_offset.set(None);
+            }
+        }
+        self.is_searching.set(false);
+    }
+}
+
+/// Searches message text on Telegram whenever the query, the chat it is limited to
+/// or the filter change, once typing pauses.
+pub fn use_message_search(
+    query: RwSignal<String>,
+    chat_id: Signal<Option<i64>>,
+    filter: RwSignal<SearchFilter>,
+) -> MessageSearch {
+    let search = MessageSearch {
+        results: RwSignal::new(Vec::new()),
+        is_searching: RwSignal::new(false),
+        next_offset: RwSignal::new(None),
+        generation: StoredValue::new(0),
+        query,
+        chat_id,
+        filter,
+    };
+
+    Effect::new(move |_| {
+        let text = query.get();
+        chat_id.track();
+        filter.track();
+
+        search.generation.update_value(|g| *g += 1);
+        let generation = search.generation.get_value();
+        search.results.set(Vec::new());
+        search.next_offset.set(None);
+        search.is_searching.set(!text.trim().is_empty());
+        if text.trim().is_empty() {
+            return;
+        }
+
+        spawn_local(async move {
+            gloo_timers::future::TimeoutFuture::new(SEARCH_DEBOUNCE_MS).await;
+            if search.generation.get_value() == generation {
+                search.fetch(generation, 0).await;
+            }
+        });
+    });
+
+    search
+}
+
+/// Opens the chat and loads older history until the message is there, then centers
+/// and briefly highlights it.
+pub fn jump_to_message(
+    chats: RwSignal<Vec<Chat>>,
+    selected_chat: RwSignal<Option<i64>>,
+    is_loading_messages: RwSignal<bool>,
+    highlighted: RwSignal<Option<i32>>,
+    chat_id: i64,
+    message_id: i32,
+) {
+    selected_chat.set(Some(chat_id));
+    highlighted.set(Some(message_id));
+
+    // Whether the message is loaded, and the oldest loaded id if there is more history.
+    let lookup = move || {
+        chats.with_untracked(|chats_list| {
+            let chat = chats_list.iter().find(|c| c.id == chat_id)?;
+            let found = chat.messages.iter().any(|m| m.id == message_id);
+            let oldest = chat
+                .has_more_messages
+                .then(|| chat.messages.iter().min_by_key(|m| m.timestamp).map(|m| m.id))
+                .flatten();
+            Some((found, oldest, chat.messages.is_empty()))
+        })
+    };
+
+    spawn_local(async move {
+        if lookup().is_some_and(|(_, _, is_empty)| is_empty) {
+            is_loading_messages.set(true);
+            let result = request(WsRequest::GetMessages {
+                chat_id,
+                before_id: None,
+                limit: None,
+            })
+            .await;
+            if let Err(e) = result {
+                web_sys::console::error_1(&format!("Failed to load messages for chat {}: {}", chat_id, e).into());
+            }
+            if selected_chat.get_untracked() == Some(chat_id) {
+                is_loading_messages.set(false);
+            }
+        }
+
+        for _ in 0..MAX_JUMP_PAGES {
+            if selected_chat.get_untracked() != Some(chat_id) {
+                return;
+            }
+            let Some((false, Some(before_id), _)) = lookup() else {
+                break;
+            };
+            web_sys::console::log_1(
+                &format!("🔎 Loading messages before {} to reach {}", before_id, message_id).into(),
+            );
+            let result = request(WsRequest::GetMessages {
+                chat_id,
+                before_id: Some(before_id),
+                limit: None,
+            })
+            .await;
+            if let Err(e) = result {
+                web_sys::console::error_1(&format!("Failed to load older messages: {}", e).into());
+                break;
+            }
+        }
+
+        // Let the new page render before looking for the bubble.
+        gloo_timers::future::TimeoutFuture::new(0).await;
+        if !scroll_to_message(message_id) {
+            web_sys::console::warn_1(&format!("⚠️ Message {} is too far back to show", message_id).into());
+        }
+
+        gloo_timers::future::TimeoutFuture::new(HIGHLIGHT_MS).await;
+        if highlighted.get_untracked() == Some(message_id) {
+            highlighted.set(None);
+        }
+    });
+}
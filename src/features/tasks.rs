@@ -1,39 +1,471 @@
 use leptos::prelude::*;
-use crate::shared::{Chat, Task, ViewMode};
-use crate::shared::utils::get_current_time;
+use crate::shared::{Chat, Message, Task, ViewMode};
+use crate::shared::api::{TaskChanges, TaskPriority, WsParticipant, WsRequest, WsResponse};
+use crate::shared::utils::start_of_day;
+use crate::features::websocket::{request, send_or_queue};
 
+/// Creates a task from a message in `chat_id`. It shows up once the backend stores
+/// it and echoes it back as `TaskSaved`, on this client and every other one.
 pub fn use_tasks(
     chats: RwSignal<Vec<Chat>>,
-    selected_chat: RwSignal<Option<i64>>,
-    tasks: RwSignal<Vec<Task>>,
-    next_task_id: RwSignal<usize>,
     view_mode: RwSignal<ViewMode>,
-) -> impl Fn(String) + Clone {
-    move |text: String| {
-        if let Some(chat_id) = selected_chat.get() {
-            let user_name = chats.with(|chats_list| {
-                chats_list
-                    .iter()
-                    .find(|c| c.id == chat_id as i64)
-                    .map(|c| c.name.clone())
-                    .unwrap_or_else(|| "Unknown".to_string())
-            });
+) -> impl Fn(i64, Message) + Clone {
+    move |chat_id: i64, message: Message| {
+        let user_name = chats.with(|chats_list| {
+            chats_list
+                .iter()
+                .find(|c| c.id == chat_id)
+                .map(|c| c.name.clone())
+                .unwrap_or_else(|| "Unknown".to_string())
+        });
 
-            let task_id = next_task_id.get();
-            next_task_id.set(task_id + 1);
+        send_or_queue(&WsRequest::CreateTask {
+            text: message.preview(),
+            user_name,
+            chat_id: Some(chat_id),
+            message_id: Some(message.id),
+        });
+        view_mode.set(ViewMode::Tasks);
+    }
+}
+
+/// Sends only the fields that changed; the list follows the backend's `TaskSaved`.
+pub fn update_task(task_id: i64, changes: TaskChanges) {
+    if changes != TaskChanges::default() {
+        send_or_queue(&WsRequest::UpdateTask { task_id, changes });
+    }
+}
+
+/// Ticks the box right away; the backend's `TaskSaved` then confirms it everywhere.
+pub fn set_task_completed(tasks: RwSignal<Vec<Task>>, task_id: i64, completed: bool) {
+    tasks.update(|tasks_list| {
+        if let Some(task) = tasks_list.iter_mut().find(|t| t.id == task_id) {
+            task.completed = completed;
+        }
+    });
+    update_task(
+        task_id,
+        TaskChanges {
+            completed: Some(completed),
+            ..TaskChanges::default()
+        },
+    );
+}
+
+/// The task goes away when the backend answers with `TaskDeleted`.
+pub fn delete_task(task_id: i64) {
+    send_or_queue(&WsRequest::DeleteTask { task_id });
+}
+
+/// Pushes the due date `minutes` out from now, so the reminder fires again then.
+pub fn snooze_task(task_id: i64, minutes: u32) {
+    send_or_queue(&WsRequest::SnoozeTask { task_id, minutes });
+}
+
+/// Ticks off those of `task_ids` that are still open.
+pub fn complete_tasks(tasks: RwSignal<Vec<Task>>, task_ids: &[i64]) {
+    let open: Vec<i64> = tasks.with_untracked(|tasks_list| {
+        tasks_list
+            .iter()
+            .filter(|t| !t.completed && task_ids.contains(&t.id))
+            .map(|t| t.id)
+            .collect()
+    });
+    for task_id in open {
+        set_task_completed(tasks, task_id, true);
+    }
+}
 
-            tasks.update(|tasks_list| {
-                tasks_list.push(Task {
-                    id: task_id,
-                    user_name,
-                    text,
-                    created_at: get_current_time(),
-                    completed: false,
-                });
-            });
+pub fn delete_tasks(task_ids: &[i64]) {
+    for &task_id in task_ids {
+        delete_task(task_id);
+    }
+}
 
-            view_mode.set(ViewMode::Tasks);
+/// Members of `chat_id` a task can be assigned to; empty if they cannot be fetched.
+pub async fn fetch_participants(chat_id: i64) -> Vec<WsParticipant> {
+    match request(WsRequest::GetParticipants { chat_id }).await {
+        Ok(WsResponse::Participants { data, .. }) => data,
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            web_sys::console::error_1(&format!("Failed to get participants: {}", e).into());
+            Vec::new()
         }
     }
 }
 
+/// Which tasks the list shows. Under `All` completed ones sit in the archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskStatus {
+    All,
+    Open,
+    Overdue,
+    Completed,
+}
+
+impl TaskStatus {
+    pub const ALL: [TaskStatus; 4] = [
+        TaskStatus::All,
+        TaskStatus::Open,
+        TaskStatus::Overdue,
+        TaskStatus::Completed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TaskStatus::All => "All",
+            TaskStatus::Open => "Open",
+            TaskStatus::Overdue => "Overdue",
+            TaskStatus::Completed => "Completed",
+        }
+    }
+
+    pub fn matches(self, task: &Task, now: i64) -> bool {
+        match self {
+            TaskStatus::All => true,
+            TaskStatus::Open => !task.completed,
+            TaskStatus::Overdue => task.is_overdue(now),
+            TaskStatus::Completed => task.completed,
+        }
+    }
+}
+
+/// Whether every word of `query` appears, ignoring case, in the task's text, notes,
+/// tags, chat or assignee.
+pub fn task_matches(task: &Task, query: &str) -> bool {
+    let haystack = [
+        task.text.as_str(),
+        task.notes.as_str(),
+        task.user_name.as_str(),
+        task.assignee.as_ref().map_or("", |a| a.name.as_str()),
+    ]
+    .into_iter()
+    .chain(task.tags.iter().map(String::as_str))
+    .collect::<Vec<_>>()
+    .join("\n")
+    .to_lowercase();
+
+    query
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| haystack.contains(word.trim_start_matches('#')))
+}
+
+/// The chats tasks were made from, by name, to filter the list by.
+pub fn task_chats(tasks: &[Task]) -> Vec<(i64, String)> {
+    let mut chats: Vec<(i64, String)> = Vec::new();
+    for task in tasks {
+        if let Some(chat_id) = task.chat_id {
+            if !chats.iter().any(|(id, _)| *id == chat_id) {
+                chats.push((chat_id, task.user_name.clone()));
+            }
+        }
+    }
+    chats.sort_by_key(|(_, name)| name.to_lowercase());
+    chats
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskGrouping {
+    None,
+    DueDate,
+    Priority,
+    Chat,
+}
+
+impl TaskGrouping {
+    pub const ALL: [TaskGrouping; 4] = [
+        TaskGrouping::None,
+        TaskGrouping::DueDate,
+        TaskGrouping::Priority,
+        TaskGrouping::Chat,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TaskGrouping::None => "Created",
+            TaskGrouping::DueDate => "Due date",
+            TaskGrouping::Priority => "Priority",
+            TaskGrouping::Chat => "Chat",
+        }
+    }
+}
+
+/// Sort rank, title and, for chats, the chat id of a section. Unlike the title it is
+/// unique, since two chats can share a name.
+pub type SectionKey = (u8, String, Option<i64>);
+
+/// A section's key, its title and its tasks.
+pub type Section = (SectionKey, String, Vec<Task>);
+
+/// Splits tasks into titled sections in display order. Within a section tasks are
+/// sorted by due date, soonest first, then by priority; without grouping they keep
+/// the order they were created in.
+pub fn group_tasks(tasks: &[Task], grouping: TaskGrouping, now: i64) -> Vec<Section> {
+    let days = [start_of_day(1), start_of_day(2), start_of_day(7)];
+    group_tasks_by_days(tasks, grouping, now, days)
+}
+
+/// `group_tasks` with the starts of tomorrow, the day after and a week from today
+/// passed in.
+fn group_tasks_by_days(
+    tasks: &[Task],
+    grouping: TaskGrouping,
+    now: i64,
+    [tomorrow, day_after, next_week]: [i64; 3],
+) -> Vec<Section> {
+    let mut tasks = tasks.to_vec();
+    if grouping != TaskGrouping::None {
+        tasks.sort_by(|a, b| {
+            let due = |task: &Task| task.due_at.unwrap_or(i64::MAX);
+            due(a)
+                .cmp(&due(b))
+                .then(b.priority.cmp(&a.priority))
+                .then(a.id.cmp(&b.id))
+        });
+    }
+
+    // Chats are told apart by id, since two of them can share a name.
+    let section = |task: &Task| -> SectionKey {
+        let titled = |rank: u8, title: &str| (rank, title.to_string(), None);
+        match grouping {
+            TaskGrouping::None => titled(0, ""),
+            TaskGrouping::DueDate => match task.due_at {
+                _ if task.is_overdue(now) => titled(0, "Overdue"),
+                Some(due_at) if due_at < tomorrow => titled(1, "Today"),
+                Some(due_at) if due_at < day_after => titled(2, "Tomorrow"),
+                Some(due_at) if due_at < next_week => titled(3, "This week"),
+                Some(_) => titled(4, "Later"),
+                None => titled(5, "No due date"),
+            },
+            TaskGrouping::Priority => match task.priority {
+                TaskPriority::High => titled(0, "High priority"),
+                TaskPriority::Normal => titled(1, "Normal priority"),
+                TaskPriority::Low => titled(2, "Low priority"),
+            },
+            TaskGrouping::Chat => (0, task.user_name.clone(), task.chat_id),
+        }
+    };
+
+    let mut sections: Vec<(SectionKey, Vec<Task>)> = Vec::new();
+    for task in tasks {
+        let key = section(&task);
+        match sections.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, section_tasks)) => section_tasks.push(task),
+            None => sections.push((key, vec![task])),
+        }
+    }
+    sections.sort_by(|(a, _), (b, _)| a.cmp(b));
+    sections
+        .into_iter()
+        .map(|(key, tasks)| {
+            let title = key.1.clone();
+            (key, title, tasks)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+    const DAY: i64 = 24 * 60 * 60;
+    const DAYS: [i64; 3] = [NOW + DAY / 2, NOW + DAY / 2 + DAY, NOW + DAY / 2 + 6 * DAY];
+
+    fn task(id: i64, text: &str) -> Task {
+        Task {
+            id,
+            user_name: "Alice".to_string(),
+            text: text.to_string(),
+            created_at: NOW - DAY,
+            updated_at: NOW - DAY,
+            completed: false,
+            completed_at: None,
+            chat_id: Some(1),
+            message_id: Some(id as i32),
+            due_at: None,
+            priority: TaskPriority::Normal,
+            tags: Vec::new(),
+            notes: String::new(),
+            assignee: None,
+            remind_in_chat: false,
+        }
+    }
+
+    fn due(id: i64, due_at: i64) -> Task {
+        Task {
+            due_at: Some(due_at),
+            ..task(id, "Due")
+        }
+    }
+
+    fn in_chat(id: i64, chat_id: i64, name: &str) -> Task {
+        Task {
+            chat_id: Some(chat_id),
+            user_name: name.to_string(),
+            ..task(id, "Chat")
+        }
+    }
+
+    fn outline(sections: &[Section]) -> Vec<(&str, Vec<i64>)> {
+        sections
+            .iter()
+            .map(|(_, title, tasks)| (title.as_str(), tasks.iter().map(|t| t.id).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn ungrouped_tasks_keep_their_order() {
+        let tasks = [due(2, NOW + DAY), task(1, "First"), due(3, NOW - DAY)];
+        let sections = group_tasks_by_days(&tasks, TaskGrouping::None, NOW, DAYS);
+        assert_eq!(outline(&sections), vec![("", vec![2, 1, 3])]);
+    }
+
+    #[test]
+    fn groups_by_due_date() {
+        let tasks = [
+            task(1, "Undated"),
+            due(2, NOW + 30 * DAY),
+            due(3, NOW + 3 * DAY),
+            due(4, NOW + DAY),
+            due(5, NOW + 60),
+            due(6, NOW - 60),
+            due(7, NOW + 10),
+        ];
+        let sections = group_tasks_by_days(&tasks, TaskGrouping::DueDate, NOW, DAYS);
+        assert_eq!(
+            outline(&sections),
+            vec![
+                ("Overdue", vec![6]),
+                ("Today", vec![7, 5]),
+                ("Tomorrow", vec![4]),
+                ("This week", vec![3]),
+                ("Later", vec![2]),
+                ("No due date", vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_by_priority_then_due_date() {
+        let high = |id, due_at| Task {
+            priority: TaskPriority::High,
+            ..due(id, due_at)
+        };
+        let tasks = [
+            Task {
+                priority: TaskPriority::Low,
+                ..task(1, "Low")
+            },
+            high(2, NOW + 2 * DAY),
+            task(3, "Normal"),
+            high(4, NOW + DAY),
+        ];
+        let sections = group_tasks_by_days(&tasks, TaskGrouping::Priority, NOW, DAYS);
+        assert_eq!(
+            outline(&sections),
+            vec![
+                ("High priority", vec![4, 2]),
+                ("Normal priority", vec![3]),
+                ("Low priority", vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_by_chat_id_not_name() {
+        let tasks = [
+            in_chat(1, 10, "Team"),
+            in_chat(2, 20, "Bob"),
+            in_chat(3, 30, "Team"),
+            in_chat(4, 10, "Team"),
+        ];
+        let sections = group_tasks_by_days(&tasks, TaskGrouping::Chat, NOW, DAYS);
+        assert_eq!(
+            outline(&sections),
+            vec![("Bob", vec![2]), ("Team", vec![1, 4]), ("Team", vec![3])]
+        );
+    }
+
+    #[test]
+    fn same_named_chats_get_their_own_keys() {
+        let tasks = [in_chat(1, 10, "Team"), in_chat(2, 30, "Team")];
+        let sections = group_tasks_by_days(&tasks, TaskGrouping::Chat, NOW, DAYS);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].1, sections[1].1);
+        assert_ne!(sections[0].0, sections[1].0);
+    }
+
+    #[test]
+    fn search_needs_every_word() {
+        let task = Task {
+            notes: "Ask about the Q3 numbers".to_string(),
+            tags: vec!["finance".to_string()],
+            assignee: Some(WsParticipant {
+                user_id: 7,
+                name: "Carol".to_string(),
+            }),
+            ..task(1, "Send the invoice")
+        };
+        assert!(task_matches(&task, ""));
+        assert!(task_matches(&task, "INVOICE"));
+        assert!(task_matches(&task, "invoice q3 carol alice"));
+        assert!(!task_matches(&task, "invoice receipt"));
+    }
+
+    #[test]
+    fn search_strips_tag_hashes() {
+        let task = Task {
+            tags: vec!["finance".to_string()],
+            ..task(1, "Send the invoice")
+        };
+        assert!(task_matches(&task, "#finance"));
+        assert!(task_matches(&task, "#FIN invoice"));
+        assert!(!task_matches(&task, "#legal"));
+    }
+
+    #[test]
+    fn status_filters() {
+        let open = due(1, NOW + DAY);
+        let overdue = due(2, NOW - DAY);
+        let done = Task {
+            completed: true,
+            ..due(3, NOW - DAY)
+        };
+
+        let shown = |status: TaskStatus| -> Vec<i64> {
+            [&open, &overdue, &done]
+                .into_iter()
+                .filter(|task| status.matches(task, NOW))
+                .map(|task| task.id)
+                .collect()
+        };
+        assert_eq!(shown(TaskStatus::All), vec![1, 2, 3]);
+        assert_eq!(shown(TaskStatus::Open), vec![1, 2]);
+        assert_eq!(shown(TaskStatus::Overdue), vec![2]);
+        assert_eq!(shown(TaskStatus::Completed), vec![3]);
+    }
+
+    #[test]
+    fn chats_are_listed_once_by_name() {
+        let tasks = [
+            in_chat(1, 10, "team"),
+            in_chat(2, 20, "Bob"),
+            in_chat(3, 10, "team"),
+            in_chat(4, 30, "Team"),
+            Task {
+                chat_id: None,
+                ..task(5, "Old")
+            },
+        ];
+        assert_eq!(
+            task_chats(&tasks),
+            vec![
+                (20, "Bob".to_string()),
+                (10, "team".to_string()),
+                (30, "Team".to_string()),
+            ]
+        );
+    }
+}
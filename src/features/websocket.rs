@@ -1,274 +1,818 @@
+use futures_channel::oneshot;
 use leptos::prelude::*;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::WebSocket;
-use crate::shared::Chat;
-use crate::shared::api::{WsRequest, WsResponse};
-use crate::shared::utils::format_timestamp;
+use crate::shared::{Chat, ConnectionState, DeliveryState, Message, PeerActivity, Task, Typing};
+use crate::shared::api::{
+    ErrorCode, RequestEnvelope, ResponseEnvelope, TypingAction, WsRequest, WsResponse,
+    PROTOCOL_VERSION,
+};
+use crate::shared::utils::{format_timestamp, show_notification};
 
 thread_local! {
-    pub static WS_REF: RefCell<Option<WebSocket>> = RefCell::new(None);
+    pub static WS_REF: RefCell<Option<WebSocket>> = const { RefCell::new(None) };
+    /// Requests made while the socket was down, flushed once it reconnects,
+    /// along with the outgoing message each one delivers.
+    static OUTBOX: RefCell<Vec<(Option<String>, String)>> = const { RefCell::new(Vec::new()) };
+    /// Bumped when the app closes the socket on purpose so pending reconnects give up.
+    static GENERATION: Cell<u32> = const { Cell::new(0) };
+    static NEXT_REQUEST_ID: Cell<u64> = const { Cell::new(1) };
+    /// Callers awaiting the reply to a tagged request, by request id.
+    static PENDING_REPLIES: RefCell<HashMap<u64, oneshot::Sender<Result<WsResponse, RpcError>>>> =
+        RefCell::new(HashMap::new());
 }
 
-pub fn use_websocket(
+const RECONNECT_BASE_DELAY_MS: f64 = 1000.0;
+const RECONNECT_MAX_DELAY_MS: f64 = 30000.0;
+/// Telegram repeats typing actions about every five seconds while they last.
+const TYPING_TIMEOUT_MS: u32 = 6000;
+
+#[derive(Clone, Copy)]
+struct WsSignals {
     chats: RwSignal<Vec<Chat>>,
-    ws_connected: RwSignal<bool>,
+    connection_state: RwSignal<ConnectionState>,
     is_loading_messages: RwSignal<bool>,
-    last_message_count: RwSignal<std::collections::HashMap<i64, usize>>,
-    last_update_time: RwSignal<std::collections::HashMap<i64, f64>>,
+    is_loading_older: RwSignal<bool>,
     selected_chat: RwSignal<Option<i64>>,
-) {
-    Effect::new(move |_| {
-        let ws = match WebSocket::new("ws://127.0.0.1:3000/ws") {
-            Ok(socket) => socket,
-            Err(e) => {
-                web_sys::console::error_1(&format!("Failed to create WebSocket: {:?}", e).into());
-                return;
+    activity: RwSignal<PeerActivity>,
+    tasks: RwSignal<Vec<Task>>,
+    reminders: RwSignal<Vec<Task>>,
+    media_token: RwSignal<Option<String>>,
+}
+
+/// Sends the request right away when the socket is open, otherwise keeps it for the
+/// next reconnect. Returns `false` only if the request could not be serialized.
+pub fn send_or_queue(request: &WsRequest) -> bool {
+    let json = match serde_json::to_string(request) {
+        Ok(json) => json,
+        Err(e) => {
+            web_sys::console::error_1(&format!("Failed to serialize request: {:?}", e).into());
+            return false;
+        }
+    };
+
+    let sent = WS_REF.with(|ws_ref| match ws_ref.borrow().as_ref() {
+        Some(ws) if ws.ready_state() == WebSocket::OPEN => ws.send_with_str(&json).is_ok(),
+        _ => false,
+    });
+    if !sent {
+        web_sys::console::warn_1(&"📮 WebSocket offline, queueing request".into());
+        let client_id = request.client_id().map(str::to_string);
+        OUTBOX.with(|outbox| outbox.borrow_mut().push((client_id, json)));
+    }
+    true
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcError {
+    /// The socket was not open, so the request was never sent.
+    Offline,
+    /// The socket closed before the reply arrived.
+    Disconnected,
+    Server { code: ErrorCode, message: String },
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Offline => write!(f, "WebSocket not connected"),
+            RpcError::Disconnected => write!(f, "WebSocket closed before the reply arrived"),
+            RpcError::Server { code, message } => write!(f, "{:?}: {}", code, message),
+        }
+    }
+}
+
+/// Sends a request tagged with a fresh id and resolves with the reply to that id,
+/// so callers are not confused by replies to other, racing requests. The reply is
+/// still applied to the shared state as usual before the future resolves.
+pub async fn request(request: WsRequest) -> Result<WsResponse, RpcError> {
+    let id = NEXT_REQUEST_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    let json = serde_json::to_string(&RequestEnvelope {
+        id: Some(id),
+        request,
+    })
+    .map_err(|e| RpcError::Server {
+        code: ErrorCode::BadRequest,
+        message: e.to_string(),
+    })?;
+
+    let (sender, receiver) = oneshot::channel();
+    PENDING_REPLIES.with(|pending| pending.borrow_mut().insert(id, sender));
+
+    let sent = WS_REF.with(|ws_ref| match ws_ref.borrow().as_ref() {
+        Some(ws) if ws.ready_state() == WebSocket::OPEN => ws.send_with_str(&json).is_ok(),
+        _ => false,
+    });
+    if !sent {
+        PENDING_REPLIES.with(|pending| pending.borrow_mut().remove(&id));
+        return Err(RpcError::Offline);
+    }
+
+    receiver.await.unwrap_or(Err(RpcError::Disconnected))
+}
+
+fn is_awaited(id: Option<u64>) -> bool {
+    id.is_some_and(|id| PENDING_REPLIES.with(|pending| pending.borrow().contains_key(&id)))
+}
+
+fn resolve_reply(id: u64, response: WsResponse) {
+    let Some(sender) = PENDING_REPLIES.with(|pending| pending.borrow_mut().remove(&id)) else {
+        return;
+    };
+    let reply = match response {
+        WsResponse::Error { code, message } => Err(RpcError::Server { code, message }),
+        response => Ok(response),
+    };
+    let _ = sender.send(reply);
+}
+
+/// Sends the request only while the socket is open, returning whether it did. For
+/// state like typing that would be stale by the time a reconnect flushed the outbox,
+/// and for file chunks that are too big to hold on to.
+pub fn send_if_connected(request: &WsRequest) -> bool {
+    WS_REF.with(|ws_ref| match ws_ref.borrow().as_ref() {
+        Some(ws) if ws.ready_state() == WebSocket::OPEN => {
+            send_now(ws, request);
+            true
+        }
+        _ => false,
+    })
+}
+
+fn send_now(ws: &WebSocket, request: &WsRequest) {
+    match serde_json::to_string(request) {
+        Ok(json) => {
+            // File chunks would flood the console with base64.
+            if !matches!(request, WsRequest::FileChunk { .. }) {
+                web_sys::console::log_1(&format!("📤 Sending request: {}", json).into());
             }
-        };
+            if let Err(e) = ws.send_with_str(&json) {
+                web_sys::console::error_1(&format!("Failed to send message: {:?}", e).into());
+            }
+        }
+        Err(e) => {
+            web_sys::console::error_1(&format!("JSON error: {:?}", e).into());
+        }
+    }
+}
 
-        WS_REF.with(|ws_ref| {
-            *ws_ref.borrow_mut() = Some(ws.clone());
-        });
+/// Exponential backoff capped at 30s, with up to 50% random jitter so many clients
+/// do not hammer a restarted backend in lockstep.
+fn reconnect_delay(attempt: u32) -> u32 {
+    let delay = (RECONNECT_BASE_DELAY_MS * 2f64.powi(attempt.min(16) as i32)).min(RECONNECT_MAX_DELAY_MS);
+    let jitter = delay * 0.5 * js_sys::Math::random();
+    (delay * 0.75 + jitter) as u32
+}
 
-        let ws_clone = ws.clone();
-        let onopen_callback = Closure::wrap(Box::new(move |_: web_sys::Event| {
-            web_sys::console::log_1(&"✅ WebSocket connection opened".into());
-            ws_connected.set(true);
-            let request = WsRequest::GetDialogs;
-            match serde_json::to_string(&request) {
-                Ok(json) => {
-                    web_sys::console::log_1(
-                        &format!("📤 Sending GetDialogs request: {}", json).into(),
-                    );
-                    if let Err(e) = ws_clone.send_with_str(&json) {
-                        web_sys::console::error_1(
-                            &format!("Failed to send message: {:?}", e).into(),
-                        );
+fn connect(url: String, signals: WsSignals, attempt: u32, generation: u32) {
+    let WsSignals {
+        chats,
+        connection_state,
+        is_loading_messages,
+        is_loading_older,
+        selected_chat,
+        activity: _,
+        tasks: _,
+        reminders: _,
+        media_token: _,
+    } = signals;
+
+    connection_state.set(ConnectionState::Connecting);
+
+    let ws = match WebSocket::new(&url) {
+        Ok(socket) => socket,
+        Err(e) => {
+            web_sys::console::error_1(&format!("Failed to create WebSocket: {:?}", e).into());
+            schedule_reconnect(url, signals, attempt + 1, generation);
+            return;
+        }
+    };
+
+    WS_REF.with(|ws_ref| {
+        *ws_ref.borrow_mut() = Some(ws.clone());
+    });
+
+    let opened = std::rc::Rc::new(Cell::new(false));
+
+    let ws_clone = ws.clone();
+    let opened_clone = opened.clone();
+    let onopen_callback = Closure::wrap(Box::new(move |_: web_sys::Event| {
+        web_sys::console::log_1(&"✅ WebSocket connection opened".into());
+        opened_clone.set(true);
+        connection_state.set(ConnectionState::Connected);
+
+        let queued = OUTBOX.with(|outbox| std::mem::take(&mut *outbox.borrow_mut()));
+        if !queued.is_empty() {
+            web_sys::console::log_1(&format!("📮 Flushing {} queued requests", queued.len()).into());
+        }
+        for (_, json) in queued {
+            if let Err(e) = ws_clone.send_with_str(&json) {
+                web_sys::console::error_1(&format!("Failed to flush request: {:?}", e).into());
+            }
+        }
+
+        send_now(&ws_clone, &WsRequest::GetDialogs);
+        // Tasks may have changed on other clients while we were away.
+        send_now(&ws_clone, &WsRequest::ListTasks);
+        if let Some(chat_id) = selected_chat.get_untracked() {
+            is_loading_messages.set(true);
+            send_now(
+                &ws_clone,
+                &WsRequest::GetMessages {
+                    chat_id,
+                    before_id: None,
+                    limit: None,
+                },
+            );
+        }
+    }) as Box<dyn FnMut(_)>);
+
+    ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
+    onopen_callback.forget();
+
+    let onmessage_callback = Closure::wrap(Box::new(move |e: web_sys::MessageEvent| {
+        if let Ok(text) = e.data().dyn_into::<js_sys::JsString>() {
+            let text: String = text.into();
+            web_sys::console::log_1(&format!("📥 Received message: {}", text).into());
+
+            match serde_json::from_str::<ResponseEnvelope>(&text) {
+                Ok(ResponseEnvelope { id, response }) => {
+                    let awaited = is_awaited(id);
+                    let reply = id.filter(|_| awaited).map(|id| (id, response.clone()));
+                    apply_response(response, signals, awaited);
+                    if let Some((id, response)) = reply {
+                        resolve_reply(id, response);
                     }
                 }
                 Err(e) => {
-                    web_sys::console::error_1(&format!("JSON error: {:?}", e).into());
+                    web_sys::console::error_1(
+                        &format!("❌ Failed to parse response: {}", e).into(),
+                    );
                 }
             }
-        }) as Box<dyn FnMut(_)>);
-
-        ws.set_onopen(Some(onopen_callback.as_ref().unchecked_ref()));
-        onopen_callback.forget();
-
-        let onmessage_callback = Closure::wrap(Box::new(move |e: web_sys::MessageEvent| {
-            if let Ok(text) = e.data().dyn_into::<js_sys::JsString>() {
-                let text: String = text.into();
-                web_sys::console::log_1(&format!("📥 Received message: {}", text).into());
-
-                match serde_json::from_str::<WsResponse>(&text) {
-                    Ok(response) => match response {
-                        WsResponse::Dialogs { data } => {
-                            web_sys::console::log_1(
-                                &format!("✅ Received {} dialogs", data.len()).into(),
-                            );
-                            if data.is_empty() {
-                                web_sys::console::warn_1(&"⚠️ No dialogs received - check if Telegram client is authorized".into());
-                            }
-                            chats.set(data);
-                        }
-                        WsResponse::Messages { chat_id, data } => {
-                            web_sys::console::log_1(
-                                &format!(
-                                    "✅ Received {} messages for chat {}",
-                                    data.len(),
-                                    chat_id
-                                )
-                                .into(),
-                            );
-                            is_loading_messages.set(false);
-
-                            let has_new_messages = last_message_count.with_untracked(|counts| {
-                                counts
-                                    .get(&chat_id)
-                                    .map_or(true, |&old_count| data.len() > old_count)
-                            });
-
-                            if has_new_messages {
-                                web_sys::console::log_1(&"📨 New messages detected!".into());
-                            }
+        }
+    }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+    ws.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
+    onmessage_callback.forget();
 
-                            chats.update(|chats_list| {
-                                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id)
-                                {
-                                    chat.messages = data.clone();
-                                }
-                            });
-
-                            last_message_count.update(|counts| {
-                                counts.insert(chat_id, data.len());
-                            });
-                            last_update_time.update(|times| {
-                                times.insert(chat_id, js_sys::Date::now());
-                            });
-                        }
-                        WsResponse::MessageSent {
-                            chat_id,
-                            success,
-                            message,
-                        } => {
-                            web_sys::console::log_1(
-                                &format!(
-                                    "📤 Message send result for chat {}: success={}, message={}",
-                                    chat_id, success, message
-                                )
-                                .into(),
-                            );
-                            if success {
-                                web_sys::console::log_1(&"✅ Message sent successfully".into());
-                                let request = WsRequest::GetMessages { chat_id };
-                                if let Ok(json) = serde_json::to_string(&request) {
-                                    WS_REF.with(|ws_ref| {
-                                        if let Some(ws) = ws_ref.borrow().as_ref() {
-                                            let _ = ws.send_with_str(&json);
-                                        }
-                                    });
-                                }
-                            } else {
-                                web_sys::console::error_1(
-                                    &format!("❌ Failed to send message: {}", message).into(),
-                                );
-                            }
-                        }
-                        WsResponse::FileSent {
-                            chat_id,
-                            success,
-                            message,
-                        } => {
-                            web_sys::console::log_1(
-                                &format!(
-                                    "📎 File send result for chat {}: success={}, message={}",
-                                    chat_id, success, message
-                                )
-                                .into(),
-                            );
-                            if success {
-                                web_sys::console::log_1(&"✅ File sent successfully".into());
-                                let request = WsRequest::GetMessages { chat_id };
-                                if let Ok(json) = serde_json::to_string(&request) {
-                                    WS_REF.with(|ws_ref| {
-                                        if let Some(ws) = ws_ref.borrow().as_ref() {
-                                            let _ = ws.send_with_str(&json);
-                                        }
-                                    });
-                                }
-                            } else {
-                                web_sys::console::error_1(
-                                    &format!("❌ Failed to send file: {}", message).into(),
-                                );
+    let onerror_callback = Closure::wrap(Box::new(move |e: web_sys::ErrorEvent| {
+        web_sys::console::error_1(&format!("❌ WebSocket error: {:?}", e).into());
+    }) as Box<dyn FnMut(web_sys::ErrorEvent)>);
+    ws.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
+    onerror_callback.forget();
+
+    let onclose_callback = Closure::once(Box::new(move |e: web_sys::CloseEvent| {
+        web_sys::console::warn_1(
+            &format!(
+                "⚠️ WebSocket closed: code={}, reason={}",
+                e.code(),
+                e.reason()
+            )
+            .into(),
+        );
+
+        if GENERATION.with(|g| g.get()) != generation {
+            return;
+        }
+        connection_state.set(ConnectionState::Offline);
+        is_loading_messages.set(false);
+        is_loading_older.set(false);
+        fail_unqueued_messages(chats);
+        // Dropping the senders resolves every awaiting `request` as disconnected.
+        PENDING_REPLIES.with(|pending| pending.borrow_mut().clear());
+
+        let next_attempt = if opened.get() { 0 } else { attempt + 1 };
+        schedule_reconnect(url, signals, next_attempt, generation);
+    }) as Box<dyn FnOnce(web_sys::CloseEvent)>);
+    ws.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
+    onclose_callback.forget();
+}
+
+/// Applies a reply or pushed update to the shared state. `awaited` replies belong to
+/// a `request` caller, which owns the loading flags for them.
+fn apply_response(response: WsResponse, signals: WsSignals, awaited: bool) {
+    let WsSignals {
+        chats,
+        connection_state,
+        is_loading_messages,
+        is_loading_older,
+        selected_chat,
+        activity,
+        tasks,
+        reminders,
+        media_token,
+    } = signals;
+
+    match response {
+        WsResponse::Hello {
+            protocol_version,
+            media_token: token,
+        } => {
+            if protocol_version == PROTOCOL_VERSION {
+                web_sys::console::log_1(
+                    &format!("🤝 Backend speaks protocol {}", protocol_version).into(),
+                );
+                // Each connection gets its own token; the previous one died with its socket.
+                media_token.set(token);
+            } else {
+                stop_incompatible(
+                    connection_state,
+                    &format!(
+                        "Backend speaks protocol {}, this app speaks {}",
+                        protocol_version, PROTOCOL_VERSION
+                    ),
+                );
+            }
+        }
+        WsResponse::Error {
+            code: ErrorCode::UnsupportedProtocol,
+            message,
+        } => {
+            stop_incompatible(connection_state, &message);
+        }
+        WsResponse::Dialogs { data } => {
+            web_sys::console::log_1(
+                &format!("✅ Received {} dialogs", data.len()).into(),
+            );
+            if data.is_empty() {
+                web_sys::console::warn_1(&"⚠️ No dialogs received - check if Telegram client is authorized".into());
+            }
+            let presence: Vec<_> = data
+                .iter()
+                .filter_map(|dialog| Some((dialog.id, dialog.presence?)))
+                .collect();
+            // Keep messages already loaded so a resync does not blank the open chat.
+            chats.update(|chats_list| {
+                let mut data: Vec<Chat> = data.into_iter().map(Chat::from).collect();
+                for chat in data.iter_mut() {
+                    if let Some(old) = chats_list.iter_mut().find(|c| c.id == chat.id) {
+                        chat.messages = std::mem::take(&mut old.messages);
+                        chat.has_more_messages = old.has_more_messages;
+                    }
+                }
+                *chats_list = data;
+            });
+            activity.update(|activity| {
+                for &(user_id, status) in &presence {
+                    activity.presence.insert(user_id, status);
+                }
+            });
+        }
+        WsResponse::Messages {
+            chat_id,
+            data,
+            before_id,
+            has_more,
+        } => {
+            web_sys::console::log_1(
+                &format!(
+                    "✅ Received {} messages for chat {} (before {:?})",
+                    data.len(),
+                    chat_id,
+                    before_id
+                )
+                .into(),
+            );
+
+            chats.update(|chats_list| {
+                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id)
+                {
+                    if before_id.is_some() {
+                        let mut page: Vec<_> = data
+                            .into_iter()
+                            .filter(|m| !chat.messages.iter().any(|e| e.id == m.id))
+                            .map(Message::from)
+                            .collect();
+                        page.append(&mut chat.messages);
+                        chat.messages = page;
+                        chat.has_more_messages = has_more;
+                    } else {
+                        // Keep older pages loaded by scrolling up, and outgoing
+                        // messages the backend has not confirmed yet.
+                        let (oldest_id, oldest_timestamp) = data
+                            .first()
+                            .map_or((i32::MIN, i64::MIN), |m| (m.id, m.timestamp));
+                        let mut unconfirmed = Vec::new();
+                        chat.messages.retain(|m| {
+                            if m.delivery != DeliveryState::Sent {
+                                unconfirmed.push(m.clone());
+                                return false;
                             }
+                            m.id < oldest_id && m.timestamp <= oldest_timestamp
+                        });
+                        if chat.messages.is_empty() {
+                            chat.has_more_messages = has_more;
                         }
-                        WsResponse::NewMessage { chat_id, message } => {
-                            web_sys::console::log_1(
-                                &format!("📨 Received new message for chat {}", chat_id).into(),
-                            );
-                            chats.update(|chats_list| {
-                                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id)
-                                {
-                                    chat.messages.push(message.clone());
-                                    chat.last_message = message.text.clone();
-                                    chat.time = format_timestamp(message.timestamp);
-                                }
-                            });
+                        chat.messages.extend(data.into_iter().map(Message::from));
+                        chat.messages.extend(unconfirmed);
+                    }
+                }
+            });
+
+            if !awaited {
+                if before_id.is_some() {
+                    is_loading_older.set(false);
+                } else {
+                    is_loading_messages.set(false);
+                }
+            }
+        }
+        WsResponse::MessageSent {
+            chat_id,
+            success,
+            message,
+            client_id,
+            sent_message,
+        } => {
+            web_sys::console::log_1(
+                &format!(
+                    "📤 Message send result for chat {}: success={}, message={}",
+                    chat_id, success, message
+                )
+                .into(),
+            );
+            if let Some(client_id) = client_id {
+                confirm_delivery(chats, chat_id, &client_id, success, sent_message.map(Message::from));
+            }
+            if !success {
+                web_sys::console::error_1(
+                    &format!("❌ Failed to send message: {}", message).into(),
+                );
+            }
+        }
+        WsResponse::FileSent {
+            chat_id,
+            success,
+            message,
+            upload_id,
+            sent_message,
+        } => {
+            web_sys::console::log_1(
+                &format!(
+                    "📎 File send result for chat {}: success={}, message={}",
+                    chat_id, success, message
+                )
+                .into(),
+            );
+            if let Some(upload_id) = upload_id {
+                confirm_delivery(chats, chat_id, &upload_id, success, sent_message.map(Message::from));
+            }
+            if !success {
+                web_sys::console::error_1(
+                    &format!("❌ Failed to send file: {}", message).into(),
+                );
+            }
+        }
+        WsResponse::FileUploadProgress {
+            chat_id,
+            upload_id,
+            received,
+        } => {
+            web_sys::console::log_1(
+                &format!(
+                    "📎 Upload {} for chat {}: {} bytes received",
+                    upload_id, chat_id, received
+                )
+                .into(),
+            );
+        }
+        WsResponse::NewMessage { chat_id, message } => {
+            web_sys::console::log_1(
+                &format!("📨 Received new message for chat {}", chat_id).into(),
+            );
+            let is_open = selected_chat.get_untracked() == Some(chat_id);
+            let message = Message::from(message);
+            chats.update(|chats_list| {
+                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id)
+                {
+                    if !chat.messages.iter().any(|m| m.id == message.id) {
+                        chat.messages.push(message.clone());
+                    }
+                    chat.last_message = message.preview();
+                    chat.time = format_timestamp(message.timestamp);
+                    if !is_open && !message.is_outgoing {
+                        chat.unread_count += 1;
+                    }
+                }
+            });
+            // The message is what the other side of a private chat was typing.
+            if !message.is_outgoing && chat_id > 0 {
+                activity.update(|activity| {
+                    activity.typing.remove(&chat_id);
+                });
+            }
+        }
+        WsResponse::MessageEdited { chat_id, message } => {
+            web_sys::console::log_1(
+                &format!("✏️ Message {} edited in chat {}", message.id, chat_id)
+                    .into(),
+            );
+            let message = Message::from(message);
+            chats.update(|chats_list| {
+                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id)
+                {
+                    if let Some(existing) =
+                        chat.messages.iter_mut().find(|m| m.id == message.id)
+                    {
+                        *existing = message.clone();
+                    }
+                    if chat.messages.last().map(|m| m.id) == Some(message.id) {
+                        chat.last_message = message.preview();
+                    }
+                }
+            });
+        }
+        WsResponse::MessagesDeleted {
+            chat_id,
+            message_ids,
+        } => {
+            web_sys::console::log_1(
+                &format!(
+                    "🗑️ {} messages deleted in chat {:?}",
+                    message_ids.len(),
+                    chat_id
+                )
+                .into(),
+            );
+            chats.update(|chats_list| {
+                for chat in chats_list
+                    .iter_mut()
+                    .filter(|c| chat_id.is_none_or(|id| c.id == id))
+                {
+                    chat.messages.retain(|m| !message_ids.contains(&m.id));
+                }
+            });
+        }
+        WsResponse::ReadInbox {
+            chat_id,
+            max_id,
+            unread_count,
+        } => {
+            web_sys::console::log_1(
+                &format!("👁️ Chat {} read up to {}", chat_id, max_id).into(),
+            );
+            chats.update(|chats_list| {
+                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id) {
+                    chat.read_inbox_max_id = chat.read_inbox_max_id.max(max_id);
+                    chat.unread_count = unread_count;
+                }
+            });
+        }
+        WsResponse::ReadOutbox { chat_id, max_id } => {
+            chats.update(|chats_list| {
+                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id) {
+                    chat.read_outbox_max_id = chat.read_outbox_max_id.max(max_id);
+                }
+            });
+        }
+        WsResponse::MessagesForwarded {
+            from_chat,
+            to_chat,
+            messages,
+        } => {
+            web_sys::console::log_1(
+                &format!(
+                    "↪️ {} messages forwarded from chat {} to chat {}",
+                    messages.len(),
+                    from_chat,
+                    to_chat
+                )
+                .into(),
+            );
+            chats.update(|chats_list| {
+                if let Some(chat) = chats_list.iter_mut().find(|c| c.id == to_chat) {
+                    for message in messages.into_iter().map(Message::from) {
+                        if !chat.messages.iter().any(|m| m.id == message.id) {
+                            chat.last_message = message.preview();
+                            chat.time = format_timestamp(message.timestamp);
+                            chat.messages.push(message);
                         }
-                    },
-                    Err(e) => {
-                        web_sys::console::error_1(
-                            &format!("❌ Failed to parse response: {}", e).into(),
-                        );
                     }
                 }
+            });
+        }
+        WsResponse::UserTyping {
+            chat_id,
+            user_id,
+            action,
+        } => {
+            activity.update(|activity| {
+                let typing = activity.typing.entry(chat_id).or_default();
+                typing.retain(|t| t.user_id != user_id);
+                if action != TypingAction::Cancel {
+                    typing.push(Typing {
+                        user_id,
+                        action,
+                        until: js_sys::Date::now() + TYPING_TIMEOUT_MS as f64,
+                    });
+                }
+            });
+            if action != TypingAction::Cancel {
+                wasm_bindgen_futures::spawn_local(async move {
+                    gloo_timers::future::TimeoutFuture::new(TYPING_TIMEOUT_MS).await;
+                    let now = js_sys::Date::now();
+                    activity.update(|activity| {
+                        if let Some(typing) = activity.typing.get_mut(&chat_id) {
+                            typing.retain(|t| t.until > now);
+                        }
+                    });
+                });
             }
-        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
-        ws.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
-        onmessage_callback.forget();
-
-        let ws_connected_clone = ws_connected.clone();
-        let selected_chat_clone = selected_chat.clone();
-        let last_activity = RwSignal::new(js_sys::Date::now());
-        let is_loading_clone = is_loading_messages.clone();
-        let update_time_clone = last_update_time.clone();
-
-        wasm_bindgen_futures::spawn_local(async move {
-            loop {
-                let time_since_activity = js_sys::Date::now() - last_activity.get();
-                let interval = if time_since_activity > 60000.0 {
-                    30000
-                } else if time_since_activity > 30000.0 {
-                    10000
-                } else if time_since_activity > 10000.0 {
-                    5000
-                } else {
-                    2000
-                };
+        }
+        WsResponse::UserStatus { user_id, presence } => {
+            activity.update(|activity| {
+                activity.presence.insert(user_id, presence);
+            });
+        }
+        // Search results and member lists only matter to the caller that awaits them.
+        WsResponse::SearchResults { .. } | WsResponse::Participants { .. } => {}
+        WsResponse::Tasks { data } => {
+            web_sys::console::log_1(&format!("✅ Received {} tasks", data.len()).into());
+            tasks.set(data.into_iter().map(Task::from).collect());
+        }
+        WsResponse::TaskSaved { task } => {
+            let task = Task::from(task);
+            // A reminder is over once its task is done or moved to another time.
+            reminders.update(|reminders| {
+                reminders.retain(|r| r.id != task.id || (!task.completed && r.due_at == task.due_at))
+            });
+            tasks.update(|tasks_list| {
+                match tasks_list.iter_mut().find(|t| t.id == task.id) {
+                    Some(existing) => *existing = task,
+                    None => tasks_list.push(task),
+                }
+            });
+        }
+        WsResponse::TaskDeleted { task_id } => {
+            reminders.update(|reminders| reminders.retain(|r| r.id != task_id));
+            tasks.update(|tasks_list| tasks_list.retain(|t| t.id != task_id));
+        }
+        WsResponse::TaskReminder { task } => {
+            let task = Task::from(task);
+            web_sys::console::log_1(&format!("⏰ Task {} is due", task.id).into());
+            show_notification("⏰ Task due", &task.text, &format!("task-{}", task.id));
+            reminders.update(|reminders| {
+                reminders.retain(|r| r.id != task.id);
+                reminders.push(task);
+            });
+        }
+        WsResponse::Error { code, message } => {
+            web_sys::console::error_1(&format!("❌ Backend error {:?}: {}", code, message).into());
+            if !awaited {
+                is_loading_messages.set(false);
+                is_loading_older.set(false);
+            }
+        }
+    }
+}
 
-                gloo_timers::future::TimeoutFuture::new(interval).await;
+/// Reconnecting cannot help when the two sides speak different protocol versions.
+fn stop_incompatible(connection_state: RwSignal<ConnectionState>, reason: &str) {
+    web_sys::console::error_1(&format!("❌ {}", reason).into());
+    GENERATION.with(|g| g.set(g.get() + 1));
+    connection_state.set(ConnectionState::Incompatible);
+    WS_REF.with(|ws_ref| {
+        if let Some(ws) = ws_ref.borrow_mut().take() {
+            let _ = ws.close();
+        }
+    });
+}
 
-                if ws_connected_clone.get() && !is_loading_clone.get() {
-                    if let Some(chat_id) = selected_chat_clone.get() {
-                        let should_update = update_time_clone.with_untracked(|times| {
-                            times
-                                .get(&chat_id)
-                                .map_or(true, |&last_time| js_sys::Date::now() - last_time > 1500.0)
-                        });
+/// Swaps the temporary bubble for the message Telegram stored, or marks it failed.
+fn confirm_delivery(
+    chats: RwSignal<Vec<Chat>>,
+    chat_id: i64,
+    client_id: &str,
+    success: bool,
+    sent_message: Option<Message>,
+) {
+    chats.update(|chats_list| {
+        let Some(chat) = chats_list.iter_mut().find(|c| c.id == chat_id) else {
+            return;
+        };
+        let Some(index) = chat
+            .messages
+            .iter()
+            .position(|m| m.client_id.as_deref() == Some(client_id))
+        else {
+            return;
+        };
 
-                        if should_update {
-                            WS_REF.with(|ws_ref| {
-                                if let Some(ref ws) = *ws_ref.borrow() {
-                                    let request = WsRequest::GetMessages { chat_id };
-                                    if let Ok(json) = serde_json::to_string(&request) {
-                                        is_loading_clone.set(true);
-                                        let _ = ws.send_with_str(&json);
-                                    }
-                                }
-                            });
-                        }
-                    }
+        if !success {
+            chat.messages[index].delivery = DeliveryState::Failed;
+            return;
+        }
+
+        match sent_message {
+            // The live update for this message may have arrived first.
+            Some(sent) if chat.messages.iter().any(|m| m.id == sent.id) => {
+                chat.messages.remove(index);
+            }
+            Some(sent) => {
+                let pending = &mut chat.messages[index];
+                pending.id = sent.id;
+                pending.timestamp = sent.timestamp;
+                pending.delivery = DeliveryState::Sent;
+                // Markup in the text has been parsed into entities by now.
+                pending.text = sent.text;
+                pending.entities = sent.entities;
+                pending.parse_mode = None;
+                if sent.media.is_some() {
+                    pending.is_file = sent.is_file;
+                    pending.file_name = sent.file_name.or(pending.file_name.take());
+                    pending.media = sent.media;
                 }
             }
-        });
-
-        let activity_tracker = last_activity.clone();
-        let window = web_sys::window().unwrap();
-        let document = window.document().unwrap();
+            None => chat.messages[index].delivery = DeliveryState::Sent,
+        }
+    });
+}
 
-        let activity_callback =
-            wasm_bindgen::closure::Closure::wrap(Box::new(move |_: web_sys::Event| {
-                activity_tracker.set(js_sys::Date::now());
-            }) as Box<dyn FnMut(_)>);
+/// A dropped socket loses every request already handed to it, so only messages
+/// still waiting in the outbox can stay pending.
+fn fail_unqueued_messages(chats: RwSignal<Vec<Chat>>) {
+    let queued: Vec<String> = OUTBOX.with(|outbox| {
+        outbox
+            .borrow()
+            .iter()
+            .filter_map(|(client_id, _)| client_id.clone())
+            .collect()
+    });
 
-        let _ = document
-            .add_event_listener_with_callback("click", activity_callback.as_ref().unchecked_ref());
-        let _ = document.add_event_listener_with_callback(
-            "keypress",
-            activity_callback.as_ref().unchecked_ref(),
-        );
-        activity_callback.forget();
+    chats.update(|chats_list| {
+        for message in chats_list.iter_mut().flat_map(|c| c.messages.iter_mut()) {
+            if message.delivery == DeliveryState::Pending
+                && !message
+                    .client_id
+                    .as_ref()
+                    .is_some_and(|id| queued.contains(id))
+            {
+                message.delivery = DeliveryState::Failed;
+            }
+        }
+    });
+}
 
-        let onerror_callback = Closure::wrap(Box::new(move |e: web_sys::ErrorEvent| {
-            web_sys::console::error_1(&format!("❌ WebSocket error: {:?}", e).into());
-        }) as Box<dyn FnMut(web_sys::ErrorEvent)>);
-        ws.set_onerror(Some(onerror_callback.as_ref().unchecked_ref()));
-        onerror_callback.forget();
+fn schedule_reconnect(url: String, signals: WsSignals, attempt: u32, generation: u32) {
+    let delay = reconnect_delay(attempt);
+    web_sys::console::log_1(&format!("🔄 Reconnecting in {} ms (attempt {})", delay, attempt + 1).into());
 
-        let onclose_callback = Closure::wrap(Box::new(move |e: web_sys::CloseEvent| {
-            web_sys::console::warn_1(
-                &format!(
-                    "⚠️ WebSocket closed: code={}, reason={}",
-                    e.code(),
-                    e.reason()
-                )
-                .into(),
-            );
-        }) as Box<dyn FnMut(web_sys::CloseEvent)>);
-        ws.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
-        onclose_callback.forget();
+    wasm_bindgen_futures::spawn_local(async move {
+        gloo_timers::future::TimeoutFuture::new(delay).await;
+        if GENERATION.with(|g| g.get()) == generation {
+            connect(url, signals, attempt, generation);
+        }
     });
 }
 
+#[allow(clippy::too_many_arguments)]
+pub fn use_websocket(
+    session_id: String,
+    chats: RwSignal<Vec<Chat>>,
+    connection_state: RwSignal<ConnectionState>,
+    is_loading_messages: RwSignal<bool>,
+    is_loading_older: RwSignal<bool>,
+    selected_chat: RwSignal<Option<i64>>,
+    activity: RwSignal<PeerActivity>,
+    tasks: RwSignal<Vec<Task>>,
+    reminders: RwSignal<Vec<Task>>,
+    media_token: RwSignal<Option<String>>,
+) {
+    let signals = WsSignals {
+        chats,
+        connection_state,
+        is_loading_messages,
+        is_loading_older,
+        selected_chat,
+        activity,
+        tasks,
+        reminders,
+        media_token,
+    };
+
+    Effect::new(move |_| {
+        let url = format!(
+            "ws://127.0.0.1:3000/ws?session_id={}&protocol_version={}",
+            session_id, PROTOCOL_VERSION
+        );
+        let generation = GENERATION.with(|g| g.get());
+        connect(url, signals, 0, generation);
+    });
+
+    on_cleanup(|| {
+        GENERATION.with(|g| g.set(g.get() + 1));
+        OUTBOX.with(|outbox| outbox.borrow_mut().clear());
+        WS_REF.with(|ws_ref| {
+            if let Some(ws) = ws_ref.borrow_mut().take() {
+                let _ = ws.close();
+            }
+        });
+    });
+}
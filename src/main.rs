@@ -2,21 +2,91 @@ mod auth;
 
 use auth::*;
 use leptos::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use wgram_ui::shared::utils::{
+    clear_session_id, load_accounts, load_session_id, save_accounts, save_session_id,
+};
+use wgram_ui::shared::StoredAccount;
 use wgram_ui::App;
 
 fn main() {
     console_error_panic_hook::set_once();
     mount_to_body(|| {
-        let session_id = RwSignal::new(None::<String>);
+        let accounts = RwSignal::new(load_accounts());
+        let session_id = RwSignal::new(
+            load_session_id()
+                .filter(|sid| accounts.with_untracked(|list| list.iter().any(|a| &a.session_id == sid)))
+                .or_else(|| accounts.with_untracked(|list| list.first().map(|a| a.session_id.clone()))),
+        );
+        let adding_account = RwSignal::new(false);
+
+        let activate = move |next: Option<String>| {
+            match &next {
+                Some(sid) => save_session_id(sid),
+                None => clear_session_id(),
+            }
+            session_id.set(next);
+        };
+
+        let forget_account = move |sid: &str| {
+            accounts.update(|list| list.retain(|a| a.session_id != sid));
+            accounts.with_untracked(|list| save_accounts(list));
+            if session_id.get_untracked().as_deref() == Some(sid) {
+                activate(accounts.with_untracked(|list| list.first().map(|a| a.session_id.clone())));
+            }
+        };
+
+        for stored in accounts.get_untracked() {
+            spawn_local(async move {
+                if !check_session(&stored.session_id).await {
+                    forget_account(&stored.session_id);
+                }
+            });
+        }
+
+        let on_logout = move |_| {
+            if let Some(current) = session_id.get_untracked() {
+                forget_account(&current);
+                spawn_local(async move {
+                    logout(&current).await;
+                });
+            }
+        };
+
+        let on_authenticated = move |sid: String, phone: String| {
+            accounts.update(|list| {
+                list.retain(|a| a.phone != phone);
+                list.push(StoredAccount {
+                    session_id: sid.clone(),
+                    phone,
+                });
+            });
+            accounts.with_untracked(|list| save_accounts(list));
+            adding_account.set(false);
+            activate(Some(sid));
+        };
 
         view! {
             <Show
-                when=move || session_id.get().is_some()
-                fallback=move || view! {
-                    <AuthForm on_authenticated=move |sid| session_id.set(Some(sid)) />
+                when=move || session_id.get().is_some() && !adding_account.get()
+                fallback=move || {
+                    let on_cancel = (!accounts.with_untracked(|list| list.is_empty()))
+                        .then(|| Callback::new(move |_| adding_account.set(false)));
+                    view! { <AuthForm on_authenticated on_cancel /> }
                 }
             >
-                <App />
+                {move || {
+                    // Switching accounts remounts the app so it reconnects with the new session.
+                    session_id.get().map(|sid| view! {
+                        <App
+                            session_id=sid
+                            accounts
+                            on_switch_account=Callback::new(move |sid| activate(Some(sid)))
+                            on_add_account=Callback::new(move |_| adding_account.set(true))
+                            on_logout=Callback::new(on_logout)
+                        />
+                    })
+                }}
             </Show>
         }
     })
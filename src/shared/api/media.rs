@@ -0,0 +1,11 @@
+/// URL of a message's media on the backend. The media token from `Hello` goes in the
+/// query since `<img>`, `<video>` and `<audio>` cannot send headers.
+pub fn media_url(media_token: &str, chat_id: i64, message_id: i32, thumb: bool) -> String {
+    format!(
+        "http://127.0.0.1:3000/media/{}/{}?token={}{}",
+        chat_id,
+        message_id,
+        media_token,
+        if thumb { "&thumb=true" } else { "" }
+    )
+}
@@ -1,4 +1,5 @@
+pub mod media;
 pub mod websocket;
 
+pub use media::*;
 pub use websocket::*;
-
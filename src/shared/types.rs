@@ -1,43 +1,232 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wgram_protocol::{
+    ParseMode, TaskPriority, TypingAction, WsDialog, WsEntity, WsMedia, WsMessage, WsParticipant,
+    WsPresence, WsReplyPreview, WsTask,
+};
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A message as the UI holds it: the wire message plus local delivery state.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Message {
     pub id: i32,
-    #[serde(default)]
     pub sender_name: String,
     pub text: String,
-    #[serde(default)]
-    #[serde(alias = "is_own")]
+    pub entities: Vec<WsEntity>,
     pub is_outgoing: bool,
-    #[serde(default)]
     pub timestamp: i64,
-    #[serde(default)]
+    pub edit_date: Option<i64>,
     pub is_file: bool,
-    #[serde(default)]
     pub file_name: Option<String>,
+    pub media: Option<WsMedia>,
+    pub reply_to_message_id: Option<i32>,
+    pub reply_preview: Option<WsReplyPreview>,
+    pub delivery: DeliveryState,
+    /// Set on messages sent from this client until the backend confirms them.
+    pub client_id: Option<String>,
+    /// Markup of an outgoing text, kept so a retry is parsed the same way.
+    pub parse_mode: Option<ParseMode>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+impl Message {
+    /// What the chat list shows for this message: its text, or a label for its media.
+    pub fn preview(&self) -> String {
+        match &self.media {
+            Some(media) if self.text.is_empty() => media.describe(),
+            _ => self.text.clone(),
+        }
+    }
+
+    /// How a reply to this message quotes it.
+    pub fn reply_preview(&self) -> WsReplyPreview {
+        WsReplyPreview {
+            sender_name: self.sender_name.clone(),
+            text: self.preview(),
+        }
+    }
+}
+
+impl From<WsMessage> for Message {
+    fn from(message: WsMessage) -> Self {
+        Self {
+            id: message.id,
+            sender_name: message.sender_name,
+            text: message.text,
+            entities: message.entities,
+            is_outgoing: message.is_outgoing,
+            timestamp: message.timestamp,
+            edit_date: message.edit_date,
+            is_file: message.is_file,
+            file_name: message.file_name,
+            media: message.media,
+            reply_to_message_id: message.reply_to_message_id,
+            reply_preview: message.reply_preview,
+            delivery: DeliveryState::Sent,
+            client_id: None,
+            parse_mode: None,
+        }
+    }
+}
+
+/// Where an outgoing message is on its way to Telegram.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DeliveryState {
+    Pending,
+    #[default]
+    Sent,
+    Failed,
+}
+
+/// A dialog as the UI holds it, with the messages loaded for it so far.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Chat {
     pub id: i64,
+    pub peer_type: String,
     pub name: String,
     pub last_message: String,
-    #[serde(default)]
     pub time: String,
     pub unread_count: i32,
-    #[serde(default)]
     pub is_archived: bool,
-    #[serde(default)]
     pub messages: Vec<Message>,
+    pub has_more_messages: bool,
+    pub read_inbox_max_id: i32,
+    /// Outgoing messages up to this id have been read by the other side.
+    pub read_outbox_max_id: i32,
+}
+
+impl From<WsDialog> for Chat {
+    fn from(dialog: WsDialog) -> Self {
+        Self {
+            id: dialog.id,
+            peer_type: dialog.peer_type,
+            name: dialog.name,
+            last_message: dialog.last_message,
+            time: String::new(),
+            unread_count: dialog.unread_count,
+            is_archived: dialog.is_archived,
+            messages: Vec::new(),
+            has_more_messages: false,
+            read_inbox_max_id: dialog.read_inbox_max_id,
+            read_outbox_max_id: dialog.read_outbox_max_id,
+        }
+    }
+}
+
+/// Presence and typing, kept out of `Chat` because they change far more often than
+/// anything that should re-render the open conversation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerActivity {
+    /// Last-seen status by user id, which is also the user's private chat id.
+    pub presence: HashMap<i64, WsPresence>,
+    /// Who is typing in each chat.
+    pub typing: HashMap<i64, Vec<Typing>>,
+}
+
+/// One member's ongoing action, shown until `until` (ms since the epoch).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Typing {
+    pub user_id: i64,
+    pub action: TypingAction,
+    pub until: f64,
+}
+
+impl PeerActivity {
+    /// Whether the user is online right now; an `Online` status lapses at `expires`.
+    pub fn is_online(&self, user_id: i64, now: f64) -> bool {
+        matches!(
+            self.presence.get(&user_id),
+            Some(WsPresence::Online { expires }) if (*expires as f64) * 1000.0 > now
+        )
+    }
+
+    /// "typing…" and friends for the chat, if anyone in it is busy.
+    pub fn typing_label(&self, chat_id: i64, now: f64) -> Option<String> {
+        let active: Vec<&Typing> = self
+            .typing
+            .get(&chat_id)?
+            .iter()
+            .filter(|typing| typing.until > now)
+            .collect();
+        let verb = match active.first()?.action {
+            TypingAction::Typing | TypingAction::Cancel => "typing",
+            TypingAction::RecordingVoice => "recording voice",
+            TypingAction::RecordingVideo => "recording video",
+            TypingAction::Uploading => "sending a file",
+            TypingAction::ChoosingSticker => "choosing a sticker",
+        };
+        Some(match active.len() {
+            // In a private chat the only one who can be typing is the other side.
+            1 if chat_id > 0 => format!("{}…", verb),
+            1 => format!("someone is {}…", verb),
+            n => format!("{} people are typing…", n),
+        })
+    }
 }
 
+/// A task as the UI holds it; the backend owns the list and numbers the tasks.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Task {
-    pub id: usize,
+    pub id: i64,
     pub user_name: String,
     pub text: String,
-    pub created_at: String,
+    /// Unix seconds, like every timestamp here.
+    pub created_at: i64,
+    pub updated_at: i64,
     pub completed: bool,
+    pub completed_at: Option<i64>,
+    /// Where the task came from, to jump back to the message.
+    pub chat_id: Option<i64>,
+    pub message_id: Option<i32>,
+    pub due_at: Option<i64>,
+    pub priority: TaskPriority,
+    pub tags: Vec<String>,
+    pub notes: String,
+    pub assignee: Option<WsParticipant>,
+    /// The reminder replies to the source message instead of going to Saved Messages.
+    pub remind_in_chat: bool,
+}
+
+impl Task {
+    pub fn is_overdue(&self, now: i64) -> bool {
+        !self.completed && self.due_at.is_some_and(|due_at| due_at < now)
+    }
+}
+
+impl From<WsTask> for Task {
+    fn from(task: WsTask) -> Self {
+        Self {
+            id: task.id,
+            user_name: task.user_name,
+            text: task.text,
+            created_at: task.created_at,
+            updated_at: task.updated_at,
+            completed: task.completed,
+            completed_at: task.completed_at,
+            chat_id: task.chat_id,
+            message_id: task.message_id,
+            due_at: task.due_at,
+            priority: task.priority,
+            tags: task.tags,
+            notes: task.notes,
+            assignee: task.assignee,
+            remind_in_chat: task.remind_in_chat,
+        }
+    }
+}
+
+/// A signed-in Telegram account remembered by the browser.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredAccount {
+    pub session_id: String,
+    pub phone: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Offline,
+    /// The backend speaks another protocol version; no reconnect is attempted.
+    Incompatible,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
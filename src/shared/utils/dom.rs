@@ -0,0 +1,20 @@
+/// DOM id of a message bubble, so it can be scrolled to.
+pub fn message_element_id(message_id: i32) -> String {
+    format!("message-{}", message_id)
+}
+
+/// Scrolls the message list so the given message is centered. Returns `false` if it
+/// is not rendered.
+pub fn scroll_to_message(message_id: i32) -> bool {
+    let Some(element) = web_sys::window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.get_element_by_id(&message_element_id(message_id)))
+    else {
+        return false;
+    };
+    let options = web_sys::ScrollIntoViewOptions::new();
+    options.set_behavior(web_sys::ScrollBehavior::Smooth);
+    options.set_block(web_sys::ScrollLogicalPosition::Center);
+    element.scroll_into_view_with_scroll_into_view_options(&options);
+    true
+}
@@ -1,4 +1,11 @@
+pub mod dom;
+pub mod format;
+pub mod notifications;
+pub mod storage;
 pub mod time;
 
+pub use dom::*;
+pub use format::*;
+pub use notifications::*;
+pub use storage::*;
 pub use time::*;
-
@@ -0,0 +1,42 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{Notification, NotificationOptions, NotificationPermission};
+
+/// Some webviews have no `Notification` at all. In the desktop app Tauri's
+/// notification plugin provides one that raises native notifications.
+fn notifications_supported() -> bool {
+    web_sys::window()
+        .is_some_and(|window| js_sys::Reflect::has(&window, &"Notification".into()).unwrap_or(false))
+}
+
+/// Asks to show notifications unless the user already answered. Browsers only
+/// prompt from a click or key press, so call it from one.
+pub fn request_notification_permission() {
+    if notifications_supported() && Notification::permission() == NotificationPermission::Default {
+        let _ = Notification::request_permission();
+    }
+}
+
+/// Shows a system notification if they are allowed; clicking it brings the app to
+/// the front. A newer notification with the same `tag` replaces the older one.
+pub fn show_notification(title: &str, body: &str, tag: &str) {
+    if !notifications_supported() || Notification::permission() != NotificationPermission::Granted {
+        return;
+    }
+
+    let options = NotificationOptions::new();
+    options.set_body(body);
+    options.set_tag(tag);
+    match Notification::new_with_options(title, &options) {
+        Ok(notification) => {
+            let on_click = Closure::once_into_js(|| {
+                if let Some(window) = web_sys::window() {
+                    let _ = window.focus();
+                }
+            });
+            notification.set_onclick(Some(on_click.unchecked_ref()));
+        }
+        Err(e) => {
+            web_sys::console::warn_1(&format!("Failed to show notification: {:?}", e).into());
+        }
+    }
+}
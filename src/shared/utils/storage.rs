@@ -0,0 +1,53 @@
+use crate::shared::api::ParseMode;
+use crate::shared::types::StoredAccount;
+
+const ACCOUNTS_KEY: &str = "wgram_accounts";
+const ACTIVE_SESSION_KEY: &str = "wgram_session_id";
+const PARSE_MODE_KEY: &str = "wgram_parse_mode";
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+pub fn load_accounts() -> Vec<StoredAccount> {
+    local_storage()
+        .and_then(|storage| storage.get_item(ACCOUNTS_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_accounts(accounts: &[StoredAccount]) {
+    if let (Some(storage), Ok(json)) = (local_storage(), serde_json::to_string(accounts)) {
+        let _ = storage.set_item(ACCOUNTS_KEY, &json);
+    }
+}
+
+pub fn load_session_id() -> Option<String> {
+    local_storage()?.get_item(ACTIVE_SESSION_KEY).ok().flatten()
+}
+
+pub fn save_session_id(session_id: &str) {
+    if let Some(storage) = local_storage() {
+        let _ = storage.set_item(ACTIVE_SESSION_KEY, session_id);
+    }
+}
+
+pub fn clear_session_id() {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(ACTIVE_SESSION_KEY);
+    }
+}
+
+/// The composer's formatting mode; plain text when never chosen.
+pub fn load_parse_mode() -> Option<ParseMode> {
+    local_storage()
+        .and_then(|storage| storage.get_item(PARSE_MODE_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten()
+}
+
+pub fn save_parse_mode(parse_mode: Option<ParseMode>) {
+    if let (Some(storage), Ok(json)) = (local_storage(), serde_json::to_string(&parse_mode)) {
+        let _ = storage.set_item(PARSE_MODE_KEY, &json);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::shared::api::WsPresence;
+
 pub fn get_current_time() -> String {
     let now = js_sys::Date::new_0();
     format!("{:02}:{:02}", now.get_hours(), now.get_minutes())
@@ -11,3 +13,89 @@ pub fn format_timestamp(timestamp: i64) -> String {
     format!("{:02}:{:02}", date.get_hours(), date.get_minutes())
 }
 
+/// Full local date and time, for tooltips.
+pub fn format_date_time(timestamp: i64) -> String {
+    let date = js_sys::Date::new(&((timestamp as f64) * 1000.0).into());
+    format!(
+        "{:02}.{:02}.{} {:02}:{:02}",
+        date.get_date(),
+        date.get_month() + 1,
+        date.get_full_year(),
+        date.get_hours(),
+        date.get_minutes()
+    )
+}
+
+/// `YYYY-MM-DDTHH:MM` in local time, the value format of `<input type="datetime-local">`.
+pub fn to_datetime_local(timestamp: i64) -> String {
+    let date = js_sys::Date::new(&((timestamp as f64) * 1000.0).into());
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}",
+        date.get_full_year(),
+        date.get_month() + 1,
+        date.get_date(),
+        date.get_hours(),
+        date.get_minutes()
+    )
+}
+
+/// Inverse of [`to_datetime_local`]; `None` for an empty or unparsable value.
+pub fn parse_datetime_local(value: &str) -> Option<i64> {
+    if value.is_empty() {
+        return None;
+    }
+    // Date-time strings without an offset are read as local time.
+    let time = js_sys::Date::new(&value.into()).get_time();
+    (!time.is_nan()).then(|| (time / 1000.0) as i64)
+}
+
+/// Local midnight `days` days from today, in unix seconds.
+pub fn start_of_day(days: u32) -> i64 {
+    let date = js_sys::Date::new_0();
+    date.set_hours(0);
+    date.set_minutes(0);
+    date.set_seconds(0);
+    date.set_milliseconds(0);
+    // Overflowing the day of the month rolls over into the next month.
+    date.set_date(date.get_date() + days);
+    (date.get_time() / 1000.0) as i64
+}
+
+/// "online" or the most precise "last seen …" the user's privacy settings allow.
+pub fn format_presence(presence: &WsPresence) -> String {
+    let now = js_sys::Date::now();
+    match *presence {
+        WsPresence::Online { expires } if (expires as f64) * 1000.0 > now => "online".to_string(),
+        WsPresence::Online { expires: seen } | WsPresence::Offline { was_online: seen } => {
+            format_last_seen(seen, now)
+        }
+        WsPresence::Recently => "last seen recently".to_string(),
+        WsPresence::LastWeek => "last seen within a week".to_string(),
+        WsPresence::LastMonth => "last seen within a month".to_string(),
+        WsPresence::LongAgo => "last seen a long time ago".to_string(),
+    }
+}
+
+fn format_last_seen(timestamp: i64, now: f64) -> String {
+    let seen = js_sys::Date::new(&((timestamp as f64) * 1000.0).into());
+    let minutes = ((now - seen.get_time()) / 60_000.0).max(0.0) as i64;
+    let today = js_sys::Date::new(&now.into());
+    let same_day = |a: &js_sys::Date, b: &js_sys::Date| {
+        a.get_date() == b.get_date()
+            && a.get_month() == b.get_month()
+            && a.get_full_year() == b.get_full_year()
+    };
+    let yesterday = js_sys::Date::new(&(now - 86_400_000.0).into());
+
+    if minutes < 1 {
+        "last seen just now".to_string()
+    } else if minutes < 60 {
+        format!("last seen {} min ago", minutes)
+    } else if same_day(&seen, &today) {
+        format!("last seen at {}", format_timestamp(timestamp))
+    } else if same_day(&seen, &yesterday) {
+        format!("last seen yesterday at {}", format_timestamp(timestamp))
+    } else {
+        format!("last seen {}", format_date_time(timestamp))
+    }
+}
@@ -1,7 +1,10 @@
 use leptos::prelude::*;
-use crate::shared::{Chat, ViewMode};
+use crate::shared::{Chat, ConnectionState, PeerActivity, ViewMode};
 use crate::shared::api::WsRequest;
-use crate::features::websocket::WS_REF;
+use crate::features::forward_messages;
+use crate::widgets::SearchResults;
+use crate::features::websocket::request;
+use wasm_bindgen_futures::spawn_local;
 
 #[component]
 pub fn ChatList(
@@ -11,8 +14,13 @@ pub fn ChatList(
     show_archived: RwSignal<bool>,
     sidebar_width: RwSignal<i32>,
     view_mode: RwSignal<ViewMode>,
-    ws_connected: RwSignal<bool>,
+    connection_state: RwSignal<ConnectionState>,
     is_loading_messages: RwSignal<bool>,
+    /// Messages waiting for a target chat; the next chat clicked receives them.
+    forwarding: RwSignal<Option<(i64, Vec<i32>)>>,
+    activity: RwSignal<PeerActivity>,
+    /// Opens a message search result in its chat.
+    #[prop(into)] on_open_message: Callback<(i64, i32)>,
 ) -> impl IntoView {
     view! {
         <div
@@ -41,6 +49,18 @@ pub fn ChatList(
                 <h1 class="text-white font-semibold text-xl mb-3">
                     {move || if view_mode.get() == ViewMode::Chats { "Message" } else { "Tasks" }}
                 </h1>
+
+                {move || forwarding.get().map(|(_, ids)| view! {
+                    <div class="flex items-center justify-between gap-2 px-3 py-2 rounded-lg text-xs text-white" style="background: rgba(33,255,95,0.15)">
+                        <span>{format!("↪️ Choose a chat to forward {} message{}", ids.len(), if ids.len() == 1 { "" } else { "s" })}</span>
+                        <button
+                            class="text-white/70 hover:text-white transition"
+                            on:click=move |_| forwarding.set(None)
+                        >
+                            "Cancel"
+                        </button>
+                    </div>
+                })}
             </div>
 
             <div class="flex-1 overflow-y-auto" style="background: #1f1d1d">
@@ -71,7 +91,7 @@ pub fn ChatList(
                             </div>
                         }.into_any()
                     } else {
-                        view! {}.into_any()
+                        ().into_any()
                     }
                 }}
 
@@ -94,13 +114,17 @@ pub fn ChatList(
                 >
                     <div
                         class="mx-2 mb-2 p-3 rounded-lg cursor-pointer flex gap-3 transition-colors"
-                        style=move || if selected_chat.get() == Some(chat.id as i64) {
+                        style=move || if selected_chat.get() == Some(chat.id) {
                             "background: #312f2f"
                         } else {
                             "background: #1f1d1d"
                         }
                         on:click=move |_| {
                             let chat_id = chat.id;
+                            if let Some((from_chat, ids)) = forwarding.get_untracked() {
+                                forwarding.set(None);
+                                forward_messages(from_chat, ids, chat_id);
+                            }
                             selected_chat.set(Some(chat_id));
 
                             chats.update(|chats_list| {
@@ -109,17 +133,24 @@ pub fn ChatList(
                                 }
                             });
 
-                            if ws_connected.get() {
-                                let request = WsRequest::GetMessages { chat_id };
-                                if let Ok(json) = serde_json::to_string(&request) {
-                                    web_sys::console::log_1(&format!("📤 Requesting messages for chat {}", chat_id).into());
-                                    is_loading_messages.set(true);
-                                    WS_REF.with(|ws_ref| {
-                                        if let Some(ws) = ws_ref.borrow().as_ref() {
-                                            let _ = ws.send_with_str(&json);
-                                        }
-                                    });
-                                }
+                            if connection_state.get_untracked() == ConnectionState::Connected {
+                                web_sys::console::log_1(&format!("📤 Requesting messages for chat {}", chat_id).into());
+                                is_loading_messages.set(true);
+                                spawn_local(async move {
+                                    let result = request(WsRequest::GetMessages {
+                                        chat_id,
+                                        before_id: None,
+                                        limit: None,
+                                    })
+                                    .await;
+                                    if let Err(e) = result {
+                                        web_sys::console::error_1(&format!("Failed to load messages for chat {}: {}", chat_id, e).into());
+                                    }
+                                    // A reply for a chat the user already left must not end the newer load.
+                                    if selected_chat.get_untracked() == Some(chat_id) {
+                                        is_loading_messages.set(false);
+                                    }
+                                });
                             }
                         }
                     >
@@ -127,7 +158,9 @@ pub fn ChatList(
                             <div class="w-12 h-12 rounded-full flex items-center justify-center text-white text-sm font-semibold" style="background: linear-gradient(135deg, #667eea 0%, #764ba2 100%)">
                                 {chat.name.chars().next().unwrap_or('?')}
                             </div>
-                            <div class="absolute bottom-0 right-0 w-3.5 h-3.5 rounded-full border-2" style="background: #21ff5f; border-color: #1f1d1d"></div>
+                            <Show when=move || activity.with(|a| a.is_online(chat.id, js_sys::Date::now()))>
+                                <div class="absolute bottom-0 right-0 w-3.5 h-3.5 rounded-full border-2" style="background: #21ff5f; border-color: #1f1d1d"></div>
+                            </Show>
                         </div>
 
                         <div class="flex-1 min-w-0">
@@ -136,7 +169,17 @@ pub fn ChatList(
                                 <div class="text-xs ml-2 flex-shrink-0" style="color: #767876">{chat.time.clone()}</div>
                             </div>
                             <div class="flex justify-between items-center gap-2">
-                                <div class="text-xs truncate flex-1" style="color: rgba(33,255,95,0.93)">{chat.last_message.clone()}</div>
+                                {
+                                    let last_message = chat.last_message.clone();
+                                    move || match activity.with(|a| a.typing_label(chat.id, js_sys::Date::now())) {
+                                        Some(label) => view! {
+                                            <div class="text-xs truncate flex-1 italic" style="color: rgba(33,255,95,0.93)">{label}</div>
+                                        }.into_any(),
+                                        None => view! {
+                                            <div class="text-xs truncate flex-1" style="color: rgba(33,255,95,0.93)">{last_message.clone()}</div>
+                                        }.into_any(),
+                                    }
+                                }
                                 {if chat.unread_count > 0 {
                                     view! {
                                         <span class="text-black text-[10px] rounded-full min-w-[20px] h-5 flex items-center justify-center px-1.5 font-semibold" style="background: #21ff5f">
@@ -144,12 +187,16 @@ pub fn ChatList(
                                         </span>
                                     }.into_any()
                                 } else {
-                                    view! {}.into_any()
+                                    ().into_any()
                                 }}
                             </div>
                         </div>
                     </div>
                 </For>
+
+                <Show when=move || view_mode.get() == ViewMode::Chats && !search_query.get().trim().is_empty()>
+                    <SearchResults query=search_query chats selected_chat on_open=on_open_message/>
+                </Show>
             </div>
         </div>
     }
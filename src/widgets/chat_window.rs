@@ -1,22 +1,64 @@
 use leptos::prelude::*;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use crate::shared::Message;
-use crate::shared::utils::format_timestamp;
+use crate::shared::api::{media_url, ParseMode, WsReplyPreview};
+use crate::shared::{ConnectionState, DeliveryState, Message, PeerActivity};
+use crate::shared::utils::{
+    format_date_time, format_presence, format_timestamp, message_element_id, save_parse_mode,
+    scroll_to_message,
+};
+use crate::widgets::{MediaPreview, RichText};
 
 #[component]
 pub fn ChatWindow(
-    #[allow(unused_variables)]
     chat_id: i64,
     chat_name: String,
+    /// Outgoing messages up to this id show as read.
+    read_outbox_max_id: i32,
+    /// Presence of the other side and who is typing; only read, never re-creates the window.
+    activity: RwSignal<PeerActivity>,
+    /// Message to outline, e.g. one opened from search.
+    highlighted: RwSignal<Option<i32>>,
+    /// Authorizes media URLs; `None` until the backend's `Hello` arrives.
+    media_token: RwSignal<Option<String>>,
     messages: Vec<Message>,
     input_value: RwSignal<String>,
-    ws_connected: RwSignal<bool>,
+    connection_state: RwSignal<ConnectionState>,
     is_loading_messages: RwSignal<bool>,
     messages_end: NodeRef<leptos::html::Div>,
+    messages_container: NodeRef<leptos::html::Div>,
+    is_loading_older: RwSignal<bool>,
+    #[prop(into)] on_load_older: Callback<()>,
     #[prop(into)] on_send: Callback<String>,
-    #[prop(into)] on_create_task: Callback<String>,
+    #[prop(into)] on_send_file: Callback<(String, Vec<u8>, String)>,
+    #[prop(into)] on_retry: Callback<String>,
+    #[prop(into)] on_discard: Callback<String>,
+    /// Turns a bubble into a task linked back to it.
+    #[prop(into)] on_create_task: Callback<Message>,
+    reply_to: RwSignal<Option<Message>>,
+    /// Markup the composer's text is sent with.
+    parse_mode: RwSignal<Option<ParseMode>>,
+    /// The outgoing message whose text the input currently replaces.
+    editing: RwSignal<Option<Message>>,
+    /// Messages picked for forwarding; while non-empty, clicking a bubble toggles it.
+    selected_messages: RwSignal<Vec<i32>>,
+    #[prop(into)] on_forward: Callback<Vec<i32>>,
+    /// Message ids and whether to delete them for everyone.
+    #[prop(into)] on_delete: Callback<(Vec<i32>, bool)>,
 ) -> impl IntoView {
+    // The message a context menu is open for, and where it was opened.
+    let menu = RwSignal::new(None::<(Message, i32, i32)>);
+
+    let toggle_selected = move |id: i32| {
+        selected_messages.update(|ids| {
+            if let Some(index) = ids.iter().position(|&i| i == id) {
+                ids.remove(index);
+            } else {
+                ids.push(id);
+            }
+        });
+    };
+
     view! {
         <div class="flex-1 flex flex-col" style="background: rgba(5,5,5,0.67)">
             <div class="px-5 py-4 flex items-center justify-between" style="background: #1f1d1d">
@@ -27,26 +69,49 @@ pub fn ChatWindow(
                     <div>
                         <div class="font-semibold text-white text-xl">{chat_name.clone()}</div>
                         <div class="text-xs flex items-center gap-1">
-                            <div class=format!(
-                                "w-2 h-2 rounded-full {}",
-                                if ws_connected.get() { "bg-[#21ff5f]" } else { "bg-rose-500" }
-                            )></div>
-                            <span style="color: rgba(33,255,95,0.99)">
-                                {if is_loading_messages.get() {
-                                    "updating..."
-                                } else if ws_connected.get() {
-                                    "Online"
-                                } else {
-                                    "offline"
-                                }}
-                            </span>
+                            {move || {
+                                let now = js_sys::Date::now();
+                                let (dot, label) = match connection_state.get() {
+                                    ConnectionState::Connected if is_loading_messages.get() => ("bg-[#21ff5f]", "updating...".to_string()),
+                                    ConnectionState::Connected => match activity.with(|a| {
+                                        a.typing_label(chat_id, now).map(|label| (label, true)).or_else(|| {
+                                            a.presence.get(&chat_id).map(|p| (format_presence(p), a.is_online(chat_id, now)))
+                                        })
+                                    }) {
+                                        Some((label, true)) => ("bg-[#21ff5f]", label),
+                                        Some((label, false)) => ("bg-white/30", label),
+                                        None => return ().into_any(),
+                                    },
+                                    ConnectionState::Connecting => ("bg-amber-400", "connecting...".to_string()),
+                                    ConnectionState::Offline => ("bg-rose-500", "offline, reconnecting...".to_string()),
+                                    ConnectionState::Incompatible => ("bg-rose-500", "app update required".to_string()),
+                                };
+                                view! {
+                                    <div class=format!("w-2 h-2 rounded-full {}", dot)></div>
+                                    <span style="color: rgba(33,255,95,0.99)">{label}</span>
+                                }.into_any()
+                            }}
                         </div>
                     </div>
                 </div>
             </div>
 
-            <div class="flex-1 overflow-y-auto p-4" style="background: rgba(5,5,5,0.67)">
+            <div
+                class="flex-1 overflow-y-auto p-4"
+                style="background: rgba(5,5,5,0.67)"
+                node_ref=messages_container
+                on:scroll=move |_| {
+                    if let Some(container) = messages_container.get_untracked() {
+                        if container.scroll_top() < 120 {
+                            on_load_older.run(());
+                        }
+                    }
+                }
+            >
                 <div class="max-w-4xl mx-auto space-y-3">
+                    <Show when=move || is_loading_older.get()>
+                        <div class="text-center text-white/50 text-xs py-2">"Loading older messages..."</div>
+                    </Show>
                     <div class="text-center mb-4">
                         <span class="text-white/60 text-sm px-4 py-1.5 rounded-full inline-block" style="background: rgba(255,255,255,0.1)">
                             "Today, 9:30 am"
@@ -57,7 +122,27 @@ pub fn ChatWindow(
                         key=|msg| msg.id
                         let:msg
                     >
-                        <div class=if msg.is_outgoing { "flex justify-end items-start gap-2" } else { "flex justify-start items-start gap-2" }>
+                        <div
+                            id=message_element_id(msg.id)
+                            class=if msg.is_outgoing { "group flex justify-end items-start gap-2" } else { "group flex justify-start items-start gap-2" }
+                            on:click={
+                                let id = msg.id;
+                                move |_| {
+                                    if !selected_messages.get_untracked().is_empty() && id > 0 {
+                                        toggle_selected(id);
+                                    }
+                                }
+                            }
+                            on:contextmenu={
+                                let message = msg.clone();
+                                move |ev: web_sys::MouseEvent| {
+                                    if message.delivery == DeliveryState::Sent {
+                                        ev.prevent_default();
+                                        menu.set(Some((message.clone(), ev.client_x(), ev.client_y())));
+                                    }
+                                }
+                            }
+                        >
                             {if !msg.is_outgoing {
                                 view! {
                                     <div class="w-10 h-10 rounded-full flex items-center justify-center text-white text-sm font-semibold flex-shrink-0" style="background: linear-gradient(135deg, #667eea 0%, #764ba2 100%)">
@@ -65,17 +150,48 @@ pub fn ChatWindow(
                                     </div>
                                 }.into_any()
                             } else {
-                                view! {}.into_any()
+                                ().into_any()
                             }}
-                            <div class=format!(
-                                "max-w-md px-4 py-2.5 rounded-3xl {}",
-                                if msg.is_outgoing {
-                                    "bg-[#312f2f] text-white"
-                                } else {
-                                    "bg-[#312f2f] text-white"
+                            {
+                                let message = msg.clone();
+                                // Only messages Telegram has confirmed can be acted on.
+                                (msg.delivery == DeliveryState::Sent && msg.is_outgoing).then(|| view! {
+                                    <MessageActions message reply_to menu on_create_task/>
+                                })
+                            }
+                            <div
+                                class="max-w-md px-4 py-2.5 rounded-3xl bg-[#312f2f] text-white"
+                                style={
+                                    let id = msg.id;
+                                    move || if selected_messages.get().contains(&id) {
+                                        "box-shadow: 0 0 0 2px #21ff5f"
+                                    } else if highlighted.get() == Some(id) {
+                                        "box-shadow: 0 0 0 2px #fbbf24; transition: box-shadow 0.3s"
+                                    } else {
+                                        "transition: box-shadow 0.3s"
+                                    }
                                 }
-                            )>
-                                {if msg.is_file {
+                            >
+                                {msg.reply_to_message_id.map(|reply_id| view! {
+                                    <ReplyQuote reply_id preview=msg.reply_preview.clone()/>
+                                })}
+                                {if let Some(media) = msg.media.clone() {
+                                    let id = msg.id;
+                                    view! {
+                                        {move || media_token.get().map(|token| view! {
+                                            <MediaPreview
+                                                media=media.clone()
+                                                src=media_url(&token, chat_id, id, false)
+                                                thumb_src=media_url(&token, chat_id, id, true)
+                                            />
+                                        })}
+                                        {(!msg.text.is_empty()).then(|| view! {
+                                            <div class="break-words whitespace-pre-wrap mt-2">
+                                                <RichText text=msg.text.clone() entities=msg.entities.clone()/>
+                                            </div>
+                                        })}
+                                    }.into_any()
+                                } else if msg.is_file {
                                     view! {
                                         <div class="flex items-center gap-2">
                                             <div class="p-2 rounded-lg bg-slate-100 dark:bg-slate-700">
@@ -90,7 +206,7 @@ pub fn ChatWindow(
                                                 {if !msg.text.is_empty() {
                                                     view! {
                                                         <div class="text-sm opacity-75 mt-1">
-                                                            {msg.text.clone()}
+                                                            <RichText text=msg.text.clone() entities=msg.entities.clone()/>
                                                         </div>
                                                     }.into_any()
                                                 } else {
@@ -101,16 +217,67 @@ pub fn ChatWindow(
                                     }.into_any()
                                 } else {
                                     view! {
-                                        <div class="break-words whitespace-pre-wrap">{msg.text.clone()}</div>
+                                        <div class="break-words whitespace-pre-wrap">
+                                            <RichText text=msg.text.clone() entities=msg.entities.clone()/>
+                                        </div>
                                     }.into_any()
                                 }}
 
-                                <div class="flex items-center justify-end mt-1">
+                                <div class="flex items-center justify-end gap-1 mt-1">
+                                    {msg.edit_date.map(|edit_date| view! {
+                                        <div class="text-[10px] italic" style="color: rgba(255,255,255,0.5)" title=format!("Edited {}", format_date_time(edit_date))>
+                                            "edited"
+                                        </div>
+                                    })}
                                     <div class="text-[10px]" style="color: rgba(255,255,255,0.5)">
                                         {format_timestamp(msg.timestamp)}
                                     </div>
+                                    {match msg.delivery {
+                                        DeliveryState::Pending => view! {
+                                            <span class="text-[10px]" style="color: rgba(255,255,255,0.5)" title="Sending">"🕓"</span>
+                                        }.into_any(),
+                                        DeliveryState::Failed => view! {
+                                            <span class="text-[10px] text-rose-400" title="Not sent">"⚠️"</span>
+                                        }.into_any(),
+                                        DeliveryState::Sent if msg.is_outgoing && msg.id <= read_outbox_max_id => view! {
+                                            <span class="text-[10px] font-semibold tracking-[-0.2em]" style="color: #21ff5f" title="Read">"✓✓"</span>
+                                        }.into_any(),
+                                        DeliveryState::Sent if msg.is_outgoing => view! {
+                                            <span class="text-[10px]" style="color: rgba(255,255,255,0.5)" title="Sent">"✓"</span>
+                                        }.into_any(),
+                                        DeliveryState::Sent => ().into_any(),
+                                    }}
                                 </div>
+                                {match (msg.delivery, msg.client_id.clone()) {
+                                    (DeliveryState::Failed, Some(client_id)) => {
+                                        let retry_id = client_id.clone();
+                                        view! {
+                                            <div class="flex items-center justify-end gap-3 mt-1 text-xs">
+                                                <span class="text-rose-400">"Not sent"</span>
+                                                <button
+                                                    class="text-white/70 hover:text-white transition"
+                                                    on:click=move |_| on_retry.run(retry_id.clone())
+                                                >
+                                                    "Retry"
+                                                </button>
+                                                <button
+                                                    class="text-white/70 hover:text-white transition"
+                                                    on:click=move |_| on_discard.run(client_id.clone())
+                                                >
+                                                    "Discard"
+                                                </button>
+                                            </div>
+                                        }.into_any()
+                                    }
+                                    _ => ().into_any(),
+                                }}
                             </div>
+                            {
+                                let message = msg.clone();
+                                (!msg.is_outgoing).then(|| view! {
+                                    <MessageActions message reply_to menu on_create_task/>
+                                })
+                            }
                         </div>
                     </For>
                     <div node_ref=messages_end></div>
@@ -118,12 +285,93 @@ pub fn ChatWindow(
             </div>
 
             <div class="p-4" style="background: rgba(5,5,5,0.67)">
+                {move || {
+                    let count = selected_messages.with(Vec::len);
+                    (count > 0).then(|| view! {
+                        <div class="max-w-4xl mx-auto mb-3 flex items-center gap-3 px-4 py-2 rounded-2xl text-sm text-white" style="background: #312f2f">
+                            <span class="flex-1">{format!("{} selected", count)}</span>
+                            <button
+                                class="px-3 py-1 rounded-full hover:opacity-80 transition"
+                                style="background: rgba(33,255,95,0.2)"
+                                on:click=move |_| on_forward.run(selected_messages.get_untracked())
+                            >
+                                "Forward"
+                            </button>
+                            <button
+                                class="text-white/70 hover:text-white transition"
+                                on:click=move |_| selected_messages.set(Vec::new())
+                            >
+                                "Cancel"
+                            </button>
+                        </div>
+                    })
+                }}
+                {move || editing.get().map(|message| view! {
+                    <div class="max-w-4xl mx-auto mb-3 flex items-center gap-3 px-4 py-2 rounded-2xl" style="background: #312f2f">
+                        <span class="text-lg" style="color: #21ff5f">"✏️"</span>
+                        <div class="flex-1 min-w-0 pl-2 border-l-2 text-xs" style="border-color: #21ff5f">
+                            <div class="font-semibold" style="color: #21ff5f">"Edit message"</div>
+                            <div class="truncate text-white/60">{message.preview()}</div>
+                        </div>
+                        <button
+                            class="text-white/70 hover:text-white transition"
+                            on:click=move |_| {
+                                editing.set(None);
+                                input_value.set(String::new());
+                            }
+                            title="Cancel editing"
+                        >
+                            "✕"
+                        </button>
+                    </div>
+                })}
+                {move || reply_to.get().map(|message| view! {
+                    <div class="max-w-4xl mx-auto mb-3 flex items-center gap-3 px-4 py-2 rounded-2xl" style="background: #312f2f">
+                        <span class="text-lg" style="color: #21ff5f">"↩"</span>
+                        <div class="flex-1 min-w-0 pl-2 border-l-2 text-xs" style="border-color: #21ff5f">
+                            <div class="font-semibold" style="color: #21ff5f">{format!("Reply to {}", message.sender_name)}</div>
+                            <div class="truncate text-white/60">{message.preview()}</div>
+                        </div>
+                        <button
+                            class="text-white/70 hover:text-white transition"
+                            on:click=move |_| reply_to.set(None)
+                            title="Cancel reply"
+                        >
+                            "✕"
+                        </button>
+                    </div>
+                })}
                 <div class="max-w-4xl mx-auto flex gap-3 items-center">
                     <div class="flex-1 flex items-center gap-3 px-4 py-3 rounded-3xl" style="background: #312f2f">
-                        <input
-                            type="text"
+                        <button
+                            class="text-xs font-mono px-2 py-1 rounded-lg text-white/70 hover:text-white transition flex-shrink-0"
+                            style="background: rgba(255,255,255,0.1)"
+                            on:click=move |_| {
+                                let next = match parse_mode.get_untracked() {
+                                    None => Some(ParseMode::Markdown),
+                                    Some(ParseMode::Markdown) => Some(ParseMode::Html),
+                                    Some(ParseMode::Html) => None,
+                                };
+                                parse_mode.set(next);
+                                save_parse_mode(next);
+                            }
+                            title=move || match parse_mode.get() {
+                                None => "Plain text (click for Markdown)",
+                                Some(ParseMode::Markdown) => "Markdown: **bold**, _italic_, `code`, ```blocks``` (click for HTML)",
+                                Some(ParseMode::Html) => "HTML: <b>, <i>, <code>, <pre>, <a href> (click for plain text)",
+                            }
+                        >
+                            {move || match parse_mode.get() {
+                                None => "Aa",
+                                Some(ParseMode::Markdown) => "MD",
+                                Some(ParseMode::Html) => "</>",
+                            }}
+                        </button>
+                        // Shift+Enter adds a line, so code blocks can be typed.
+                        <textarea
                             placeholder="Message..."
-                            class="flex-1 bg-transparent text-white placeholder-white/40 outline-none text-xl"
+                            class="flex-1 bg-transparent text-white placeholder-white/40 outline-none text-xl resize-none"
+                            rows=move || input_value.with(|text| text.split('\n').count().clamp(1, 8)).to_string()
                             prop:value=input_value
                             on:input=move |ev| input_value.set(event_target_value(&ev))
                             on:keydown=move |ev| {
@@ -134,7 +382,7 @@ pub fn ChatWindow(
                                     input_value.set(String::new());
                                 }
                             }
-                        />
+                        ></textarea>
                         <button
                             class="text-white/70 hover:text-white transition text-xl flex-shrink-0"
                             on:click=move |_| {
@@ -143,8 +391,42 @@ pub fn ChatWindow(
                                 input.set_attribute("type", "file").unwrap();
                                 input.set_attribute("accept", "*/*").unwrap();
 
-                                let closure = Closure::wrap(Box::new(move |_: web_sys::Event| {
-                                    web_sys::console::log_1(&"File selected".into());
+                                let closure = Closure::wrap(Box::new(move |ev: web_sys::Event| {
+                                    let Some(file) = ev
+                                        .target()
+                                        .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+                                        .and_then(|input| input.files())
+                                        .and_then(|files| files.get(0))
+                                    else {
+                                        return;
+                                    };
+
+                                    let file_name = file.name();
+                                    web_sys::console::log_1(&format!("📎 File selected: {}", file_name).into());
+
+                                    let reader = match web_sys::FileReader::new() {
+                                        Ok(reader) => reader,
+                                        Err(e) => {
+                                            web_sys::console::error_1(&format!("Failed to create FileReader: {:?}", e).into());
+                                            return;
+                                        }
+                                    };
+
+                                    let reader_clone = reader.clone();
+                                    let onload = Closure::once(Box::new(move |_: web_sys::ProgressEvent| {
+                                        if let Ok(buffer) = reader_clone.result() {
+                                            let data = js_sys::Uint8Array::new(&buffer).to_vec();
+                                            let caption = input_value.get_untracked();
+                                            input_value.set(String::new());
+                                            on_send_file.run((file_name, data, caption));
+                                        }
+                                    }) as Box<dyn FnOnce(_)>);
+                                    reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+                                    onload.forget();
+
+                                    if let Err(e) = reader.read_as_array_buffer(&file) {
+                                        web_sys::console::error_1(&format!("Failed to read file: {:?}", e).into());
+                                    }
                                 }) as Box<dyn FnMut(_)>);
 
                                 input.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref()).unwrap();
@@ -173,22 +455,211 @@ pub fn ChatWindow(
                     >
                         "↑"
                     </button>
-                    <button
-                        class="px-4 py-2 rounded-full bg-emerald-600/80 hover:bg-emerald-600 text-white font-semibold transition text-sm"
-                        on:click=move |_| {
-                            let text = input_value.get_untracked();
-                            if !text.is_empty() {
-                                on_create_task.run(text);
-                                input_value.set(String::new());
-                            }
-                        }
-                        title="Create task"
-                    >
-                        "Task"
-                    </button>
                 </div>
             </div>
+
+            {move || menu.get().map(|(message, x, y)| view! {
+                <MessageMenu
+                    message
+                    x
+                    y
+                    on_close=move || menu.set(None)
+                    on_reply=move |message| reply_to.set(Some(message))
+                    on_select=move |id| toggle_selected(id)
+                    on_create_task=move |message| on_create_task.run(message)
+                    on_edit=move |message: Message| {
+                        reply_to.set(None);
+                        input_value.set(message.text.clone());
+                        editing.set(Some(message));
+                    }
+                    on_delete=move |(id, revoke)| on_delete.run((vec![id], revoke))
+                />
+            })}
+        </div>
+    }
+}
+
+/// Hover buttons next to a bubble: quick reply, create task and the context menu.
+#[component]
+fn MessageActions(
+    message: Message,
+    reply_to: RwSignal<Option<Message>>,
+    menu: RwSignal<Option<(Message, i32, i32)>>,
+    on_create_task: Callback<Message>,
+) -> impl IntoView {
+    let menu_message = message.clone();
+    let task_message = message.clone();
+    view! {
+        <div class="flex flex-col gap-1 self-center opacity-0 group-hover:opacity-100 transition">
+            <button
+                class="w-7 h-7 rounded-full text-xs text-white/70 hover:text-white transition"
+                style="background: rgba(255,255,255,0.1)"
+                on:click=move |ev| {
+                    ev.stop_propagation();
+                    reply_to.set(Some(message.clone()));
+                }
+                title="Reply"
+            >
+                "↩"
+            </button>
+            <button
+                class="w-7 h-7 rounded-full text-xs text-white/70 hover:text-white transition"
+                style="background: rgba(255,255,255,0.1)"
+                on:click=move |ev| {
+                    ev.stop_propagation();
+                    on_create_task.run(task_message.clone());
+                }
+                title="Create task from this message"
+            >
+                "☑"
+            </button>
+            <button
+                class="w-7 h-7 rounded-full text-xs text-white/70 hover:text-white transition"
+                style="background: rgba(255,255,255,0.1)"
+                on:click=move |ev: web_sys::MouseEvent| {
+                    ev.stop_propagation();
+                    menu.set(Some((menu_message.clone(), ev.client_x(), ev.client_y())));
+                }
+                title="More"
+            >
+                "⋯"
+            </button>
+        </div>
+    }
+}
+
+/// Keeps a menu opened at (`x`, `y`) inside the window.
+fn menu_position(x: i32, y: i32) -> String {
+    const MENU_WIDTH: i32 = 208;
+    const MENU_HEIGHT: i32 = 260;
+    let window = web_sys::window();
+    let dimension = |value: Option<Result<JsValue, JsValue>>| {
+        value.and_then(Result::ok).and_then(|v| v.as_f64()).unwrap_or(f64::MAX) as i32
+    };
+    let width = dimension(window.as_ref().map(|w| w.inner_width()));
+    let height = dimension(window.as_ref().map(|w| w.inner_height()));
+    format!(
+        "left: {}px; top: {}px; width: {}px; background: #312f2f",
+        x.min(width.saturating_sub(MENU_WIDTH)).max(0),
+        y.min(height.saturating_sub(MENU_HEIGHT)).max(0),
+        MENU_WIDTH
+    )
+}
+
+/// Context menu for a bubble, closed by any click outside it.
+#[component]
+fn MessageMenu(
+    message: Message,
+    x: i32,
+    y: i32,
+    on_close: impl Fn() + Copy + 'static,
+    on_reply: impl Fn(Message) + 'static,
+    on_select: impl Fn(i32) + 'static,
+    on_create_task: impl Fn(Message) + 'static,
+    on_edit: impl Fn(Message) + 'static,
+    on_delete: impl Fn((i32, bool)) + Copy + 'static,
+) -> impl IntoView {
+    let id = message.id;
+    let is_outgoing = message.is_outgoing;
+    let reply_message = message.clone();
+    let task_message = message.clone();
+    let item_class = "w-full text-left px-4 py-2 hover:bg-white/10 transition";
+
+    let confirm_delete = move |revoke: bool| {
+        let prompt = if revoke {
+            "Delete this message for everyone?"
+        } else {
+            "Delete this message for you?"
+        };
+        let confirmed = web_sys::window()
+            .and_then(|w| w.confirm_with_message(prompt).ok())
+            .unwrap_or(false);
+        if confirmed {
+            on_delete((id, revoke));
+        }
+        on_close();
+    };
+
+    view! {
+        <div
+            class="fixed inset-0 z-40"
+            on:click=move |_| on_close()
+            on:contextmenu=move |ev| {
+                ev.prevent_default();
+                on_close();
+            }
+        ></div>
+        <div class="fixed z-50 py-1 rounded-xl text-sm text-white shadow-lg" style=menu_position(x, y)>
+            <button
+                class=item_class
+                on:click=move |_| {
+                    on_reply(reply_message.clone());
+                    on_close();
+                }
+            >
+                "↩ Reply"
+            </button>
+            <button
+                class=item_class
+                on:click=move |_| {
+                    on_select(id);
+                    on_close();
+                }
+            >
+                "↪ Select to forward"
+            </button>
+            <button
+                class=item_class
+                on:click=move |_| {
+                    on_create_task(task_message.clone());
+                    on_close();
+                }
+            >
+                "☑ Create task"
+            </button>
+            {is_outgoing.then(|| view! {
+                <button
+                    class=item_class
+                    on:click=move |_| {
+                        on_edit(message.clone());
+                        on_close();
+                    }
+                >
+                    "✏️ Edit"
+                </button>
+            })}
+            <button class=item_class on:click=move |_| confirm_delete(false)>
+                "🗑️ Delete for me"
+            </button>
+            {is_outgoing.then(|| view! {
+                <button class=format!("{} text-rose-400", item_class) on:click=move |_| confirm_delete(true)>
+                    "🗑️ Delete for everyone"
+                </button>
+            })}
         </div>
     }
 }
 
+/// The quoted message above a reply; clicking it jumps to the original.
+#[component]
+fn ReplyQuote(reply_id: i32, preview: Option<WsReplyPreview>) -> impl IntoView {
+    let (sender_name, text) = match preview {
+        Some(preview) => (preview.sender_name, preview.text),
+        None => (String::new(), "Deleted message".to_string()),
+    };
+    view! {
+        <div
+            class="mb-1.5 pl-2 border-l-2 text-xs cursor-pointer hover:opacity-80 transition"
+            style="border-color: #21ff5f"
+            on:click=move |ev| {
+                ev.stop_propagation();
+                scroll_to_message(reply_id);
+            }
+        >
+            {(!sender_name.is_empty()).then(|| view! {
+                <div class="font-semibold" style="color: #21ff5f">{sender_name}</div>
+            })}
+            <div class="truncate text-white/60">{text}</div>
+        </div>
+    }
+}
@@ -0,0 +1,186 @@
+use leptos::prelude::*;
+use crate::shared::api::WsMedia;
+use crate::shared::utils::{format_duration, format_file_size};
+
+/// Caps previews at 18rem wide while keeping the media's aspect ratio.
+fn frame_style(width: Option<i32>, height: Option<i32>) -> String {
+    match (width, height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => {
+            format!("width: 18rem; max-width: 100%; aspect-ratio: {} / {}", w, h)
+        }
+        _ => "width: 18rem; max-width: 100%; aspect-ratio: 4 / 3".to_string(),
+    }
+}
+
+fn details(parts: Vec<Option<String>>) -> String {
+    parts.into_iter().flatten().collect::<Vec<_>>().join(" · ")
+}
+
+/// Renders a message's media. `src` serves the full file and `thumb_src` its
+/// preview-sized thumbnail.
+#[component]
+pub fn MediaPreview(media: WsMedia, src: String, thumb_src: String) -> impl IntoView {
+    match media {
+        WsMedia::Photo { width, height, .. } => view! {
+            <a href=src.clone() target="_blank" rel="noopener noreferrer" class="block">
+                <img
+                    src=src.clone()
+                    loading="lazy"
+                    class="rounded-2xl object-cover"
+                    style=format!("{}; background: rgba(255,255,255,0.06)", frame_style(width, height))
+                />
+            </a>
+        }
+        .into_any(),
+        WsMedia::Document {
+            file_name,
+            mime_type,
+            size,
+        } => view! {
+            <div class="flex items-center gap-3">
+                <div class="w-10 h-10 rounded-xl flex items-center justify-center text-lg flex-shrink-0" style="background: rgba(255,255,255,0.1)">
+                    "📄"
+                </div>
+                <div class="min-w-0 flex-1">
+                    <div class="font-medium truncate">{file_name.unwrap_or_else(|| "File".to_string())}</div>
+                    <div class="text-xs text-white/50">{details(vec![size.map(format_file_size), mime_type])}</div>
+                </div>
+                <a
+                    href=src
+                    download=""
+                    target="_blank"
+                    rel="noopener noreferrer"
+                    class="px-3 py-1.5 rounded-lg text-xs hover:opacity-80 transition flex-shrink-0"
+                    style="background: rgba(255,255,255,0.1)"
+                >
+                    "Download"
+                </a>
+            </div>
+        }
+        .into_any(),
+        WsMedia::Audio {
+            file_name,
+            size,
+            duration,
+            title,
+            performer,
+            voice,
+            ..
+        } => {
+            let label = if voice {
+                "Voice message".to_string()
+            } else {
+                match (title, performer) {
+                    (Some(title), Some(performer)) => format!("{} — {}", performer, title),
+                    (Some(title), None) => title,
+                    _ => file_name.unwrap_or_else(|| "Audio".to_string()),
+                }
+            };
+            view! {
+                <div class="min-w-[16rem]">
+                    <div class="flex items-center gap-3">
+                        <div class="w-10 h-10 rounded-full flex items-center justify-center text-lg flex-shrink-0" style="background: rgba(255,255,255,0.1)">
+                            {if voice { "🎤" } else { "🎵" }}
+                        </div>
+                        <div class="min-w-0">
+                            <div class="font-medium truncate">{label}</div>
+                            <div class="text-xs text-white/50">
+                                {details(vec![duration.map(format_duration), size.map(format_file_size)])}
+                            </div>
+                        </div>
+                    </div>
+                    <audio src=src controls preload="none" class="w-full mt-2"></audio>
+                </div>
+            }
+            .into_any()
+        }
+        WsMedia::Video {
+            width,
+            height,
+            round,
+            ..
+        } => view! {
+            <video
+                src=src
+                poster=thumb_src
+                controls
+                preload="metadata"
+                class=if round { "rounded-full object-cover" } else { "rounded-2xl" }
+                style=format!(
+                    "{}; background: rgba(255,255,255,0.06)",
+                    if round { frame_style(Some(1), Some(1)) } else { frame_style(width, height) }
+                )
+            ></video>
+        }
+        .into_any(),
+        WsMedia::Sticker {
+            animated: false,
+            ..
+        } => view! {
+            <img src=src loading="lazy" class="w-40 h-40 object-contain"/>
+        }
+        .into_any(),
+        WsMedia::Sticker { emoji, animated, .. } => view! {
+            <div class="flex flex-col items-center">
+                <span class="text-6xl leading-none">{emoji.unwrap_or_else(|| "🏷️".to_string())}</span>
+                {animated.then(|| view! { <span class="text-[10px] text-white/40 mt-1">"animated sticker"</span> })}
+            </div>
+        }
+        .into_any(),
+        WsMedia::Geo {
+            latitude,
+            longitude,
+        } => view! {
+            <a
+                class="flex items-center gap-3 hover:opacity-80 transition"
+                href=format!(
+                    "https://www.openstreetmap.org/?mlat={lat}&mlon={lon}#map=15/{lat}/{lon}",
+                    lat = latitude,
+                    lon = longitude
+                )
+                target="_blank"
+                rel="noopener noreferrer"
+            >
+                <div class="w-10 h-10 rounded-xl flex items-center justify-center text-lg flex-shrink-0" style="background: rgba(255,255,255,0.1)">
+                    "📍"
+                </div>
+                <div>
+                    <div class="font-medium">"Location"</div>
+                    <div class="text-xs text-white/50">{format!("{:.5}, {:.5}", latitude, longitude)}</div>
+                </div>
+            </a>
+        }
+        .into_any(),
+        WsMedia::Contact {
+            first_name,
+            last_name,
+            phone_number,
+        } => view! {
+            <div class="flex items-center gap-3">
+                <div class="w-10 h-10 rounded-full flex items-center justify-center text-white font-semibold flex-shrink-0" style="background: linear-gradient(135deg, #667eea 0%, #764ba2 100%)">
+                    {first_name.chars().next().unwrap_or('?')}
+                </div>
+                <div class="min-w-0">
+                    <div class="font-medium truncate">{format!("{} {}", first_name, last_name).trim_end().to_string()}</div>
+                    <div class="text-xs text-white/50">{phone_number}</div>
+                </div>
+            </div>
+        }
+        .into_any(),
+        WsMedia::Poll { question, answers } => view! {
+            <div class="min-w-[14rem]">
+                <div class="text-xs text-white/50 mb-1">"📊 Poll"</div>
+                <div class="font-medium mb-2">{question}</div>
+                <div class="space-y-1">
+                    {answers
+                        .into_iter()
+                        .map(|answer| view! {
+                            <div class="px-3 py-1.5 rounded-lg text-sm" style="background: rgba(255,255,255,0.08)">{answer}</div>
+                        })
+                        .collect_view()}
+                </div>
+            </div>
+        }
+        .into_any(),
+    }
+}
@@ -1,10 +1,18 @@
 pub mod chat_list;
 pub mod chat_window;
+pub mod media_preview;
+pub mod rich_text;
+pub mod search_results;
 pub mod sidebar;
 pub mod task_list;
+pub mod task_reminders;
 
 pub use chat_list::ChatList;
 pub use chat_window::ChatWindow;
+pub use media_preview::MediaPreview;
+pub use rich_text::RichText;
+pub use search_results::SearchResults;
 pub use sidebar::Sidebar;
 pub use task_list::TaskList;
+pub use task_reminders::TaskReminders;
 
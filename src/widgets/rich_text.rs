@@ -0,0 +1,224 @@
+use leptos::prelude::*;
+use crate::shared::api::{WsEntity, WsEntityKind};
+
+/// A piece of text and the entities covering it, each with all the text it spans.
+type Segment<'a> = (String, Vec<(&'a WsEntityKind, String)>);
+
+/// Splits `text` wherever an entity starts or ends, pairing each piece with the
+/// entities covering it, outermost first. Links need the whole text of their entity
+/// when formatting splits them. Offsets are in UTF-16 code units, as Telegram sends them.
+fn segments<'a>(text: &str, entities: &'a [WsEntity]) -> Vec<Segment<'a>> {
+    let units: Vec<u16> = text.encode_utf16().collect();
+    let len = units.len();
+    let span = |e: &WsEntity| {
+        let start = (e.offset.max(0) as usize).min(len);
+        let end = (start + e.length.max(0) as usize).min(len);
+        (start, end)
+    };
+
+    let mut bounds: Vec<usize> = vec![0, len];
+    for entity in entities {
+        let (start, end) = span(entity);
+        bounds.extend([start, end]);
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    // Longer entities first so they wrap the shorter ones nested inside them.
+    let mut ordered: Vec<&WsEntity> = entities.iter().collect();
+    ordered.sort_by_key(|e| (e.offset, std::cmp::Reverse(e.length)));
+
+    bounds
+        .windows(2)
+        .map(|w| {
+            let (start, end) = (w[0], w[1]);
+            let covering = ordered
+                .iter()
+                .filter(|e| {
+                    let (s, e) = span(e);
+                    s <= start && end <= e
+                })
+                .map(|entity| {
+                    let (s, e) = span(entity);
+                    (&entity.kind, String::from_utf16_lossy(&units[s..e]))
+                })
+                .collect();
+            (String::from_utf16_lossy(&units[start..end]), covering)
+        })
+        .collect()
+}
+
+/// Links keep only schemes that cannot run script; bare domains get `https://`.
+fn safe_href(url: &str) -> Option<String> {
+    let url = url.trim();
+    let lower = url.to_ascii_lowercase();
+    if ["http://", "https://", "tg://", "mailto:", "tel:"]
+        .iter()
+        .any(|scheme| lower.starts_with(scheme))
+    {
+        Some(url.to_string())
+    } else if lower.contains("://") {
+        None
+    } else {
+        Some(format!("https://{}", url))
+    }
+}
+
+/// Where a link entity points. `text` is everything the entity spans, not just the
+/// piece being rendered.
+fn href(kind: &WsEntityKind, text: &str) -> Option<String> {
+    match kind {
+        WsEntityKind::Url => safe_href(text),
+        WsEntityKind::TextUrl { url } => safe_href(url),
+        WsEntityKind::Email => Some(format!("mailto:{}", text)),
+        WsEntityKind::Phone => Some(format!("tel:{}", text)),
+        WsEntityKind::Mention => Some(format!("https://t.me/{}", text.trim_start_matches('@'))),
+        WsEntityKind::MentionName { user_id } => Some(format!("tg://user?id={}", user_id)),
+        _ => None,
+    }
+}
+
+fn link(href: Option<String>, inner: AnyView) -> AnyView {
+    match href {
+        Some(href) => view! {
+            <a
+                href=href
+                target="_blank"
+                rel="noopener noreferrer"
+                class="underline underline-offset-2 hover:opacity-80"
+                style="color: #21ff5f"
+                on:click=|ev| ev.stop_propagation()
+            >
+                {inner}
+            </a>
+        }
+        .into_any(),
+        None => inner,
+    }
+}
+
+fn wrap(kind: &WsEntityKind, text: &str, inner: AnyView) -> AnyView {
+    match kind {
+        WsEntityKind::Bold => view! { <strong>{inner}</strong> }.into_any(),
+        WsEntityKind::Italic => view! { <em>{inner}</em> }.into_any(),
+        WsEntityKind::Underline => view! { <u>{inner}</u> }.into_any(),
+        WsEntityKind::Strikethrough => view! { <s>{inner}</s> }.into_any(),
+        WsEntityKind::Spoiler => {
+            let revealed = RwSignal::new(false);
+            view! {
+                <span
+                    class="rounded transition cursor-pointer"
+                    style=move || if revealed.get() {
+                        "background: rgba(255,255,255,0.1)"
+                    } else {
+                        "background: rgba(255,255,255,0.3); color: transparent; filter: blur(3px)"
+                    }
+                    title="Spoiler"
+                    on:click=move |ev| {
+                        ev.stop_propagation();
+                        revealed.set(true);
+                    }
+                >
+                    {inner}
+                </span>
+            }
+            .into_any()
+        }
+        WsEntityKind::Code => view! {
+            <code class="px-1 rounded font-mono text-[0.9em]" style="background: rgba(0,0,0,0.35)">{inner}</code>
+        }
+        .into_any(),
+        WsEntityKind::Pre { language } => view! {
+            <pre class="my-1 p-3 rounded-xl overflow-x-auto font-mono text-sm whitespace-pre" style="background: rgba(0,0,0,0.35)">
+                {language.clone().map(|language| view! {
+                    <div class="text-[10px] uppercase tracking-wide text-white/40 mb-1 font-sans">{language}</div>
+                })}
+                <code>{inner}</code>
+            </pre>
+        }
+        .into_any(),
+        WsEntityKind::Blockquote => view! {
+            <blockquote class="my-1 pl-3 border-l-2 italic text-white/80" style="border-color: #21ff5f">{inner}</blockquote>
+        }
+        .into_any(),
+        WsEntityKind::Url
+        | WsEntityKind::TextUrl { .. }
+        | WsEntityKind::Email
+        | WsEntityKind::Phone
+        | WsEntityKind::Mention
+        | WsEntityKind::MentionName { .. } => link(href(kind, text), inner),
+        WsEntityKind::Hashtag | WsEntityKind::Cashtag | WsEntityKind::BotCommand => view! {
+            <span style="color: #21ff5f">{inner}</span>
+        }
+        .into_any(),
+        // Custom emoji images are not fetched; the fallback emoji is shown instead.
+        WsEntityKind::CustomEmoji { .. } => view! {
+            <span title="Custom emoji">{inner}</span>
+        }
+        .into_any(),
+    }
+}
+
+/// Message text with its Telegram formatting. Everything is built as DOM nodes
+/// from plain strings, never as HTML, so message content cannot inject markup.
+#[component]
+pub fn RichText(text: String, entities: Vec<WsEntity>) -> impl IntoView {
+    if entities.is_empty() {
+        return view! { <span>{text}</span> }.into_any();
+    }
+
+    segments(&text, &entities)
+        .into_iter()
+        .map(|(piece, kinds)| {
+            kinds
+                .iter()
+                .rev()
+                .fold(piece.into_any(), |inner, (kind, spanned)| wrap(kind, spanned, inner))
+        })
+        .collect_view()
+        .into_any()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(offset: i32, length: i32, kind: WsEntityKind) -> WsEntity {
+        WsEntity {
+            offset,
+            length,
+            kind,
+        }
+    }
+
+    #[test]
+    fn links_keep_their_whole_text_when_formatting_splits_them() {
+        let text = "see example.com/docs or @alice";
+        let entities = [
+            entity(4, 16, WsEntityKind::Url),
+            entity(16, 4, WsEntityKind::Bold),
+            entity(24, 6, WsEntityKind::Mention),
+            entity(25, 3, WsEntityKind::Italic),
+        ];
+
+        let links: Vec<(String, Option<String>)> = segments(text, &entities)
+            .into_iter()
+            .filter_map(|(piece, kinds)| {
+                let (kind, spanned) = kinds.first()?;
+                Some((piece, href(kind, spanned)))
+            })
+            .collect();
+        let docs = Some("https://example.com/docs".to_string());
+        let alice = Some("https://t.me/alice".to_string());
+        assert_eq!(
+            links,
+            vec![
+                ("example.com/".to_string(), docs.clone()),
+                ("docs".to_string(), docs),
+                ("@".to_string(), alice.clone()),
+                ("ali".to_string(), alice.clone()),
+                ("ce".to_string(), alice),
+            ]
+        );
+    }
+}
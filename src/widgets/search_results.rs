@@ -0,0 +1,119 @@
+use leptos::prelude::*;
+use crate::features::use_message_search;
+use crate::shared::api::SearchFilter;
+use crate::shared::utils::format_date_time;
+use crate::shared::{Chat, Message};
+
+const FILTERS: [(SearchFilter, &str); 7] = [
+    (SearchFilter::All, "All"),
+    (SearchFilter::Photos, "Photos"),
+    (SearchFilter::Videos, "Videos"),
+    (SearchFilter::Documents, "Files"),
+    (SearchFilter::Links, "Links"),
+    (SearchFilter::Voice, "Voice"),
+    (SearchFilter::Music, "Music"),
+];
+
+/// Messages matching the search box, found by Telegram across all chats or only the
+/// open one.
+#[component]
+pub fn SearchResults(
+    query: RwSignal<String>,
+    chats: RwSignal<Vec<Chat>>,
+    selected_chat: RwSignal<Option<i64>>,
+    /// Chat and message id of the result clicked.
+    #[prop(into)] on_open: Callback<(i64, i32)>,
+) -> impl IntoView {
+    let filter = RwSignal::new(SearchFilter::All);
+    let this_chat_only = RwSignal::new(false);
+    let scope = Signal::derive(move || selected_chat.get().filter(|_| this_chat_only.get()));
+    let search = use_message_search(query, scope, filter);
+
+    let chat_name = move |chat_id: i64| {
+        chats.with(|chats_list| {
+            chats_list
+                .iter()
+                .find(|c| c.id == chat_id)
+                .map(|c| c.name.clone())
+                .unwrap_or_else(|| "Unknown chat".to_string())
+        })
+    };
+
+    view! {
+        <div class="px-2 pb-2">
+            <div class="flex items-center justify-between px-1 mb-2">
+                <div class="text-white/60 text-xs font-semibold">"Messages"</div>
+                <Show when=move || selected_chat.get().is_some()>
+                    <label class="flex items-center gap-1 text-xs text-white/60 cursor-pointer">
+                        <input
+                            type="checkbox"
+                            prop:checked=this_chat_only
+                            on:change=move |ev| this_chat_only.set(event_target_checked(&ev))
+                        />
+                        "This chat only"
+                    </label>
+                </Show>
+            </div>
+
+            <div class="flex flex-wrap gap-1 px-1 mb-2">
+                {FILTERS.into_iter().map(|(value, label)| view! {
+                    <button
+                        class="px-2 py-0.5 rounded-full text-[11px] transition"
+                        style=move || if filter.get() == value {
+                            "background: #21ff5f; color: black"
+                        } else {
+                            "background: rgba(84,54,57,0.48); color: rgba(255,255,255,0.7)"
+                        }
+                        on:click=move |_| filter.set(value)
+                    >
+                        {label}
+                    </button>
+                }).collect_view()}
+            </div>
+
+            <For
+                each=move || search.results.get()
+                key=|result| (result.chat_id, result.message.id)
+                let:result
+            >
+                {
+                    let chat_id = result.chat_id;
+                    let message_id = result.message.id;
+                    let timestamp = result.message.timestamp;
+                    let preview = Message::from(result.message).preview();
+                    view! {
+                        <div
+                            class="mb-1 p-3 rounded-lg cursor-pointer transition-colors hover:bg-[#312f2f]"
+                            on:click=move |_| on_open.run((chat_id, message_id))
+                        >
+                            <div class="flex justify-between items-baseline mb-1">
+                                <div class="font-semibold text-white truncate text-xs">{move || chat_name(chat_id)}</div>
+                                <div class="text-xs ml-2 flex-shrink-0" style="color: #767876">{format_date_time(timestamp)}</div>
+                            </div>
+                            <div class="text-xs truncate" style="color: rgba(33,255,95,0.93)">{preview}</div>
+                        </div>
+                    }
+                }
+            </For>
+
+            {move || {
+                if search.is_searching.get() {
+                    view! { <div class="text-center text-xs text-white/40 py-2">"Searching..."</div> }.into_any()
+                } else if search.has_more() {
+                    view! {
+                        <button
+                            class="w-full text-center text-xs text-white/60 hover:text-white py-2 transition"
+                            on:click=move |_| search.load_more()
+                        >
+                            "Show more"
+                        </button>
+                    }.into_any()
+                } else if search.results.with(Vec::is_empty) {
+                    view! { <div class="text-center text-xs text-white/40 py-2">"No messages found"</div> }.into_any()
+                } else {
+                    ().into_any()
+                }
+            }}
+        </div>
+    }
+}
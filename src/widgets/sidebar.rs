@@ -1,8 +1,15 @@
 use leptos::prelude::*;
-use crate::shared::ViewMode;
+use crate::shared::{StoredAccount, ViewMode};
 
 #[component]
-pub fn Sidebar(view_mode: RwSignal<ViewMode>) -> impl IntoView {
+pub fn Sidebar(
+    view_mode: RwSignal<ViewMode>,
+    accounts: RwSignal<Vec<StoredAccount>>,
+    active_session: String,
+    #[prop(into)] on_switch_account: Callback<String>,
+    #[prop(into)] on_add_account: Callback<()>,
+    #[prop(into)] on_logout: Callback<()>,
+) -> impl IntoView {
     view! {
         <div class="w-[68px] flex flex-col items-center py-6 gap-4" style="background: #1f1d1d; border-right: 7px solid black">
             <div class="mb-4">
@@ -55,6 +62,57 @@ pub fn Sidebar(view_mode: RwSignal<ViewMode>) -> impl IntoView {
             </div>
 
             <div class="flex-1"></div>
+
+            <For
+                each=move || accounts.get()
+                key=|account| account.session_id.clone()
+                let:account
+            >
+                {
+                    let is_active = account.session_id == active_session;
+                    let session_id = account.session_id.clone();
+                    let digits: String = account.phone.chars().filter(|c| c.is_ascii_digit()).collect();
+                    let label = digits[digits.len().saturating_sub(2)..].to_string();
+                    view! {
+                        <button
+                            class="w-[35px] h-[35px] rounded-full flex items-center justify-center text-white text-xs font-semibold"
+                            style=if is_active {
+                                "background: #48736f; box-shadow: 0 0 0 2px white"
+                            } else {
+                                "background: rgba(255,255,255,0.1)"
+                            }
+                            on:click=move |_| {
+                                if !is_active {
+                                    on_switch_account.run(session_id.clone());
+                                }
+                            }
+                            title=account.phone.clone()
+                        >
+                            {label}
+                        </button>
+                    }
+                }
+            </For>
+
+            <button
+                class="w-[35px] h-[35px] rounded-full flex items-center justify-center transition text-xl text-white/70 hover:text-white"
+                style="background: rgba(255,255,255,0.1)"
+                on:click=move |_| on_add_account.run(())
+                title="Add account"
+            >
+                "+"
+            </button>
+
+            <button
+                class="w-[35px] h-[35px] rounded-lg flex items-center justify-center transition text-xl text-white/70 hover:text-white"
+                on:click=move |_| on_logout.run(())
+                title="Log out"
+            >
+                "🚪"
+            </button>
+            <div class="text-[12px]" style="color: white">
+                "Log out"
+            </div>
         </div>
     }
 }
@@ -1,40 +1,572 @@
 use leptos::prelude::*;
+use wasm_bindgen_futures::spawn_local;
+use crate::features::{
+    complete_tasks, delete_task, delete_tasks, fetch_participants, group_tasks,
+    set_task_completed, task_chats, task_matches, update_task, TaskGrouping, TaskStatus,
+};
 use crate::shared::Task;
+use crate::shared::api::{TaskChanges, TaskPriority};
+use crate::shared::utils::{
+    format_date_time, parse_datetime_local, request_notification_permission, to_datetime_local,
+};
 
+const PRIORITIES: [(TaskPriority, &str); 3] = [
+    (TaskPriority::Low, "Low"),
+    (TaskPriority::Normal, "Normal"),
+    (TaskPriority::High, "High"),
+];
+
+const FIELD_CLASS: &str = "w-full px-3 py-1.5 rounded-lg text-white text-xs outline-none";
+const FIELD_STYLE: &str = "background: rgba(84,54,57,0.48)";
+
+/// How often overdue markers and the Overdue filter catch up with the clock.
+const CLOCK_TICK_MS: u32 = 30_000;
+
+fn now() -> i64 {
+    (js_sys::Date::now() / 1000.0) as i64
+}
+
+/// The current time, refreshed every `CLOCK_TICK_MS` until the signal is disposed.
+fn use_clock() -> RwSignal<i64> {
+    let clock = RwSignal::new(now());
+    spawn_local(async move {
+        loop {
+            gloo_timers::future::TimeoutFuture::new(CLOCK_TICK_MS).await;
+            if clock.try_set(now()).is_some() {
+                break;
+            }
+        }
+    });
+    clock
+}
+
+/// Asks before deleting, the same for one task as for a selection.
+fn confirm_delete(count: usize) -> bool {
+    let prompt = match count {
+        1 => "Delete this task?".to_string(),
+        n => format!("Delete {} selected tasks?", n),
+    };
+    web_sys::window()
+        .and_then(|w| w.confirm_with_message(&prompt).ok())
+        .unwrap_or(false)
+}
+
+/// Identifies a rendered row; it changes whenever the task is edited, including by
+/// other clients, so the row re-renders.
+fn row_key(task: &Task) -> (i64, i64, bool) {
+    (task.id, task.updated_at, task.completed)
+}
+
+/// Inline style of a toggle chip, lit up when `active`.
+fn chip_style(active: bool) -> &'static str {
+    if active {
+        "background: #21ff5f; color: black"
+    } else {
+        "background: rgba(84,54,57,0.48); color: rgba(255,255,255,0.7)"
+    }
+}
+
+/// Shows the tasks matching `query` and the chosen filters. `on_open` gets the chat
+/// and message a task was made from when it is clicked.
 #[component]
-pub fn TaskList(tasks: RwSignal<Vec<Task>>) -> impl IntoView {
+pub fn TaskList(
+    tasks: RwSignal<Vec<Task>>,
+    query: RwSignal<String>,
+    #[prop(into)] on_open: Callback<(i64, i32)>,
+) -> impl IntoView {
+    let grouping = RwSignal::new(TaskGrouping::None);
+    let status = RwSignal::new(TaskStatus::All);
+    let chat_filter = RwSignal::new(None::<i64>);
+    let show_archive = RwSignal::new(false);
+    // The task whose edit panel is open.
+    let editing = RwSignal::new(None::<i64>);
+    // Rows picked for a bulk action; `None` outside selection mode.
+    let selected = RwSignal::new(None::<Vec<i64>>);
+    let clock = use_clock();
+
+    let chat_options = Memo::new(move |_| tasks.with(|tasks| task_chats(tasks)));
+    let filtered = Memo::new(move |_| {
+        let query = query.get();
+        let status = status.get();
+        let chat_filter = chat_filter.get();
+        let now = clock.get();
+        tasks.with(|tasks| {
+            tasks
+                .iter()
+                .filter(|task| {
+                    status.matches(task, now)
+                        && chat_filter.is_none_or(|chat_id| task.chat_id == Some(chat_id))
+                        && task_matches(task, &query)
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+    });
+    // Completed tasks leave the main list for the archive unless they are asked for.
+    let sections = Memo::new(move |_| {
+        let archiving = status.get() == TaskStatus::All;
+        filtered.with(|filtered| {
+            let shown: Vec<Task> = filtered
+                .iter()
+                .filter(|task| !(archiving && task.completed))
+                .cloned()
+                .collect();
+            group_tasks(&shown, grouping.get(), clock.get())
+        })
+    });
+    let archived = Memo::new(move |_| {
+        if status.get() != TaskStatus::All {
+            return Vec::new();
+        }
+        let mut archived: Vec<Task> = filtered.with(|filtered| {
+            filtered.iter().filter(|task| task.completed).cloned().collect()
+        });
+        archived.sort_by_key(|task| std::cmp::Reverse(task.completed_at));
+        archived
+    });
+
+    // Only rows on screen can be picked or acted on.
+    let visible_ids = move || {
+        let mut ids: Vec<i64> = sections.with(|sections| {
+            sections.iter().flat_map(|(_, _, tasks)| tasks.iter().map(|t| t.id)).collect()
+        });
+        if show_archive.get_untracked() {
+            archived.with(|archived| ids.extend(archived.iter().map(|t| t.id)));
+        }
+        ids
+    };
+    let picked = move || {
+        let visible = visible_ids();
+        selected
+            .get_untracked()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| visible.contains(id))
+            .collect::<Vec<_>>()
+    };
+    let complete_selected = move |_| {
+        complete_tasks(tasks, &picked());
+        selected.set(None);
+    };
+    let delete_selected = move |_| {
+        let ids = picked();
+        if ids.is_empty() {
+            return;
+        }
+        if confirm_delete(ids.len()) {
+            delete_tasks(&ids);
+            selected.set(None);
+        }
+    };
+
     view! {
+        <div class="flex flex-wrap items-center gap-1 px-4 pb-2">
+            {TaskStatus::ALL.into_iter().map(|value| view! {
+                <button
+                    class="px-2 py-0.5 rounded-full text-[11px] transition"
+                    style=move || chip_style(status.get() == value)
+                    on:click=move |_| status.set(value)
+                >
+                    {value.label()}
+                </button>
+            }).collect_view()}
+            <select
+                class="ml-auto max-w-[40%] px-2 py-0.5 rounded-full text-[11px] text-white/70 outline-none"
+                style=FIELD_STYLE
+                on:change=move |ev| chat_filter.set(event_target_value(&ev).parse::<i64>().ok())
+            >
+                <option value="" selected=move || chat_filter.get().is_none()>"All chats"</option>
+                <For
+                    each=move || chat_options.get()
+                    key=|(chat_id, _)| *chat_id
+                    let:chat
+                >
+                    <option
+                        value=chat.0.to_string()
+                        selected=move || chat_filter.get() == Some(chat.0)
+                    >
+                        {chat.1.clone()}
+                    </option>
+                </For>
+            </select>
+        </div>
+        <div class="flex flex-wrap items-center gap-1 px-4 pb-2">
+            <span class="text-white/60 text-xs mr-1">"Sort by"</span>
+            {TaskGrouping::ALL.into_iter().map(|value| view! {
+                <button
+                    class="px-2 py-0.5 rounded-full text-[11px] transition"
+                    style=move || chip_style(grouping.get() == value)
+                    on:click=move |_| grouping.set(value)
+                >
+                    {value.label()}
+                </button>
+            }).collect_view()}
+            <button
+                class="ml-auto text-white/60 hover:text-white text-xs"
+                on:click=move |_| selected.update(|selected| {
+                    *selected = if selected.is_some() { None } else { Some(Vec::new()) };
+                })
+            >
+                {move || if selected.with(Option::is_some) { "Cancel" } else { "Select" }}
+            </button>
+        </div>
+        <Show when=move || selected.with(Option::is_some)>
+            <div class="flex items-center gap-2 mx-4 mb-2 px-3 py-2 rounded-lg text-xs text-white" style="background: #312f2f">
+                <span class="flex-1">
+                    {move || format!("{} selected", selected.with(|s| s.as_ref().map_or(0, Vec::len)))}
+                </span>
+                <button class="text-white/60 hover:text-white" on:click=move |_| selected.set(Some(visible_ids()))>
+                    "All"
+                </button>
+                <button class="hover:text-white" style="color: #21ff5f" on:click=complete_selected>
+                    "✓ Complete"
+                </button>
+                <button class="text-rose-400 hover:text-rose-300" on:click=delete_selected>
+                    "🗑️ Delete"
+                </button>
+            </div>
+        </Show>
+        <Show when=move || filtered.with(Vec::is_empty)>
+            <div class="px-4 py-6 text-center text-xs text-white/50">
+                {move || if tasks.with(Vec::is_empty) {
+                    "No tasks yet. Make one from a message."
+                } else {
+                    "No tasks match."
+                }}
+            </div>
+        </Show>
         <For
-            each=move || tasks.get()
-            key=|task| task.id
-            let:task
-        >
-            <div class="px-4 py-3">
-                <div class="flex items-start gap-3 p-3 rounded-lg" style="background: #312f2f">
+            each=move || sections.get()
+            // A section re-renders when one of its rows changes, including edits from
+            // other clients; chats sharing a name are still told apart.
+            key=|(key, _, section_tasks)| {
+                (key.clone(), section_tasks.iter().map(row_key).collect::<Vec<_>>())
+            }
+            children=move |(_, title, section_tasks)| view! {
+                {(!title.is_empty()).then(|| view! {
+                    <div class="px-4 pt-3 text-xs font-semibold" style="color: #767876">{title}</div>
+                })}
+                {section_tasks
+                    .into_iter()
+                    .map(|task| view! { <TaskRow task tasks editing selected clock on_open/> })
+                    .collect_view()}
+            }
+        />
+        <Show when=move || archived.with(|archived| !archived.is_empty())>
+            <button
+                class="w-full px-4 pt-3 pb-1 text-left text-xs font-semibold hover:text-white"
+                style="color: #767876"
+                on:click=move |_| show_archive.update(|show| *show = !*show)
+            >
+                {move || format!(
+                    "{} Archive ({})",
+                    if show_archive.get() { "▾" } else { "▸" },
+                    archived.with(Vec::len)
+                )}
+            </button>
+            <Show when=move || show_archive.get()>
+                <For
+                    each=move || archived.get()
+                    key=row_key
+                    let:task
+                >
+                    <TaskRow task tasks editing selected clock on_open/>
+                </For>
+            </Show>
+        </Show>
+    }
+}
+
+#[component]
+fn TaskRow(
+    task: Task,
+    tasks: RwSignal<Vec<Task>>,
+    editing: RwSignal<Option<i64>>,
+    selected: RwSignal<Option<Vec<i64>>>,
+    clock: RwSignal<i64>,
+    on_open: Callback<(i64, i32)>,
+) -> impl IntoView {
+    let id = task.id;
+    let completed = task.completed;
+    let source = task.chat_id.zip(task.message_id);
+    let due = task.due_at;
+    let overdue = move || !completed && due.is_some_and(|due_at| due_at < clock.get());
+    let editor_task = task.clone();
+
+    view! {
+        <div class="px-4 py-2">
+            <div
+                class="group p-3 rounded-lg"
+                style=move || if selected.with(|s| s.as_ref().is_some_and(|s| s.contains(&id))) {
+                    "background: #312f2f; box-shadow: 0 0 0 2px #21ff5f"
+                } else {
+                    "background: #312f2f"
+                }
+            >
+                <div class="flex items-start gap-3">
                     <input
                         type="checkbox"
-                        checked=task.completed
+                        checked=completed
                         class="mt-1 w-5 h-5"
-                        on:change=move |_| {
-                            let task_id = task.id;
-                            tasks.update(|tasks_list| {
-                                if let Some(t) = tasks_list.iter_mut().find(|t| t.id == task_id) {
-                                    t.completed = !t.completed;
-                                }
-                            });
-                        }
+                        on:change=move |_| set_task_completed(tasks, id, !completed)
                     />
-                    <div class="flex-1 min-w-0">
-                        <div class="font-semibold text-sm text-white">{task.user_name.clone()}</div>
+                    <div
+                        class="flex-1 min-w-0"
+                        class:cursor-pointer=move || source.is_some() || selected.with(Option::is_some)
+                        title=move || if selected.with(Option::is_some) {
+                            Some("Select")
+                        } else {
+                            source.map(|_| "Open the message")
+                        }
+                        on:click=move |_| {
+                            // In selection mode a click picks the row instead of opening it.
+                            let picking = selected.with_untracked(Option::is_some);
+                            if picking {
+                                selected.update(|selected| {
+                                    if let Some(selected) = selected {
+                                        match selected.iter().position(|s| *s == id) {
+                                            Some(index) => {
+                                                selected.remove(index);
+                                            }
+                                            None => selected.push(id),
+                                        }
+                                    }
+                                });
+                            } else if let Some(source) = source {
+                                on_open.run(source);
+                            }
+                        }
+                    >
+                        <div class="flex items-center gap-2 text-sm">
+                            <span class="font-semibold text-white truncate">{task.user_name.clone()}</span>
+                            {task.assignee.clone().map(|assignee| view! {
+                                <span class="text-xs text-white/60 truncate">{format!("→ {}", assignee.name)}</span>
+                            })}
+                        </div>
                         <div class=format!(
                             "text-white mt-1 text-xs {}",
-                            if task.completed { "line-through opacity-50" } else { "" }
+                            if completed { "line-through opacity-50" } else { "" }
                         )>{task.text.clone()}</div>
-                        <div class="text-xs mt-1" style="color: #767876">{task.created_at.clone()}</div>
+                        {(!task.notes.is_empty()).then(|| view! {
+                            <div class="text-xs mt-1 text-white/60 whitespace-pre-wrap">{task.notes.clone()}</div>
+                        })}
+                        <div class="flex flex-wrap items-center gap-1 mt-1 text-[11px]">
+                            {match task.priority {
+                                TaskPriority::High => view! { <span class="text-rose-400">"‼ High"</span> }.into_any(),
+                                TaskPriority::Low => view! { <span class="text-white/40">"Low"</span> }.into_any(),
+                                TaskPriority::Normal => ().into_any(),
+                            }}
+                            {task.due_at.map(|due_at| view! {
+                                <span class=move || if overdue() { "text-rose-400" } else { "text-amber-300" }>
+                                    {format!("⏰ {}", format_date_time(due_at))}
+                                </span>
+                            })}
+                            {task.tags.iter().map(|tag| view! {
+                                <span class="px-1.5 rounded-full text-white/70" style="background: rgba(84,54,57,0.48)">
+                                    {format!("#{}", tag)}
+                                </span>
+                            }).collect_view()}
+                        </div>
+                        <div class="text-xs mt-1" style="color: #767876">
+                            {match task.completed_at.filter(|_| completed) {
+                                Some(completed_at) => format!("Done {}", format_date_time(completed_at)),
+                                None => format!("Created {}", format_date_time(task.created_at)),
+                            }}
+                        </div>
+                    </div>
+                    <div class="flex gap-1 opacity-0 group-hover:opacity-100 transition">
+                        <button
+                            class="text-white/50 hover:text-white text-xs"
+                            title="Edit task"
+                            on:click=move |_| editing.update(|editing| {
+                                *editing = if *editing == Some(id) { None } else { Some(id) };
+                            })
+                        >
+                            "✏️"
+                        </button>
+                        <button
+                            class="text-white/50 hover:text-white text-xs"
+                            title="Delete task"
+                            on:click=move |_| {
+                                if confirm_delete(1) {
+                                    delete_task(id);
+                                }
+                            }
+                        >
+                            "🗑️"
+                        </button>
                     </div>
                 </div>
+                {move || (editing.get() == Some(id)).then(|| view! {
+                    <TaskEditor task=editor_task.clone() on_close=move || editing.set(None)/>
+                })}
             </div>
-        </For>
+        </div>
     }
 }
 
+/// Edit panel under a task. Only fields that differ from `task` are sent on save.
+#[component]
+fn TaskEditor(task: Task, #[prop(into)] on_close: Callback<()>) -> impl IntoView {
+    let text = RwSignal::new(task.text.clone());
+    let due = RwSignal::new(task.due_at.map(to_datetime_local).unwrap_or_default());
+    let priority = RwSignal::new(task.priority);
+    let tags = RwSignal::new(task.tags.join(", "));
+    let notes = RwSignal::new(task.notes.clone());
+    let assignee = RwSignal::new(task.assignee.clone());
+    let remind_in_chat = RwSignal::new(task.remind_in_chat);
+    // Keep the current assignee selectable even if they left the chat.
+    let participants = RwSignal::new(task.assignee.clone().into_iter().collect::<Vec<_>>());
+
+    if let Some(chat_id) = task.chat_id {
+        spawn_local(async move {
+            let members = fetch_participants(chat_id).await;
+            // The panel may have been closed while the list was loading.
+            participants.try_update(|participants| {
+                for member in members {
+                    if !participants.iter().any(|p| p.user_id == member.user_id) {
+                        participants.push(member);
+                    }
+                }
+            });
+        });
+    }
+
+    let save = move |_| {
+        let new_text = text.get_untracked().trim().to_string();
+        let due_at = parse_datetime_local(&due.get_untracked());
+        let new_priority = priority.get_untracked();
+        let new_tags: Vec<String> = tags
+            .get_untracked()
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches('#').to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        let new_notes = notes.get_untracked();
+        let new_assignee = assignee.get_untracked();
+        let new_remind_in_chat = remind_in_chat.get_untracked();
+        // Saving is a click, the only moment a browser lets us ask.
+        if due_at.is_some() {
+            request_notification_permission();
+        }
+
+        update_task(
+            task.id,
+            TaskChanges {
+                text: (!new_text.is_empty() && new_text != task.text).then_some(new_text),
+                completed: None,
+                due_at: (due_at != task.due_at).then_some(due_at),
+                priority: (new_priority != task.priority).then_some(new_priority),
+                tags: (new_tags != task.tags).then_some(new_tags),
+                notes: (new_notes != task.notes).then_some(new_notes),
+                assignee: (new_assignee != task.assignee).then_some(new_assignee),
+                remind_in_chat: (new_remind_in_chat != task.remind_in_chat).then_some(new_remind_in_chat),
+            },
+        );
+        on_close.run(());
+    };
+
+    view! {
+        <div class="flex flex-col gap-2 mt-3 pt-3 border-t border-white/10 text-xs text-white/60">
+            <textarea
+                class=FIELD_CLASS
+                style=FIELD_STYLE
+                rows="2"
+                prop:value=text
+                on:input=move |ev| text.set(event_target_value(&ev))
+            ></textarea>
+            <label class="flex items-center gap-2">
+                <span class="w-16 flex-shrink-0">"Due"</span>
+                <input
+                    type="datetime-local"
+                    class=FIELD_CLASS
+                    style=FIELD_STYLE
+                    prop:value=due
+                    on:input=move |ev| due.set(event_target_value(&ev))
+                />
+                <button class="text-white/50 hover:text-white" title="No due date" on:click=move |_| due.set(String::new())>
+                    "✕"
+                </button>
+            </label>
+            {task.chat_id.is_some().then(|| view! {
+                <label class="flex items-center gap-2">
+                    <span class="w-16 flex-shrink-0"></span>
+                    <input
+                        type="checkbox"
+                        prop:checked=remind_in_chat
+                        on:change=move |ev| remind_in_chat.set(event_target_checked(&ev))
+                    />
+                    "Remind in the chat instead of Saved Messages"
+                </label>
+            })}
+            <div class="flex items-center gap-2">
+                <span class="w-16 flex-shrink-0">"Priority"</span>
+                {PRIORITIES.into_iter().map(|(value, label)| view! {
+                    <button
+                        class="px-2 py-0.5 rounded-full text-[11px] transition"
+                        style=move || chip_style(priority.get() == value)
+                        on:click=move |_| priority.set(value)
+                    >
+                        {label}
+                    </button>
+                }).collect_view()}
+            </div>
+            <label class="flex items-center gap-2">
+                <span class="w-16 flex-shrink-0">"Assignee"</span>
+                <select
+                    class=FIELD_CLASS
+                    style=FIELD_STYLE
+                    on:change=move |ev| {
+                        let user_id = event_target_value(&ev).parse::<i64>().ok();
+                        assignee.set(user_id.and_then(|user_id| {
+                            participants.with_untracked(|participants| {
+                                participants.iter().find(|p| p.user_id == user_id).cloned()
+                            })
+                        }));
+                    }
+                >
+                    <option value="" selected=move || assignee.get().is_none()>"Nobody"</option>
+                    <For
+                        each=move || participants.get()
+                        key=|participant| participant.user_id
+                        let:participant
+                    >
+                        <option
+                            value=participant.user_id.to_string()
+                            selected=move || assignee.get().is_some_and(|a| a.user_id == participant.user_id)
+                        >
+                            {participant.name.clone()}
+                        </option>
+                    </For>
+                </select>
+            </label>
+            <label class="flex items-center gap-2">
+                <span class="w-16 flex-shrink-0">"Tags"</span>
+                <input
+                    type="text"
+                    placeholder="billing, urgent"
+                    class=FIELD_CLASS
+                    style=FIELD_STYLE
+                    prop:value=tags
+                    on:input=move |ev| tags.set(event_target_value(&ev))
+                />
+            </label>
+            <textarea
+                class=FIELD_CLASS
+                style=FIELD_STYLE
+                rows="3"
+                placeholder="Notes"
+                prop:value=notes
+                on:input=move |ev| notes.set(event_target_value(&ev))
+            ></textarea>
+            <div class="flex justify-end gap-2">
+                <button class="px-3 py-1 rounded-full text-white/70 hover:text-white" on:click=move |_| on_close.run(())>
+                    "Cancel"
+                </button>
+                <button class="px-3 py-1 rounded-full text-black font-semibold" style="background: #21ff5f" on:click=save>
+                    "Save"
+                </button>
+            </div>
+        </div>
+    }
+}
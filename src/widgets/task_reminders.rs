@@ -0,0 +1,80 @@
+use leptos::prelude::*;
+use crate::features::{set_task_completed, snooze_task};
+use crate::shared::Task;
+use crate::shared::utils::format_date_time;
+
+const SNOOZE_OPTIONS: [(u32, &str); 3] = [(10, "10 min"), (60, "1 hour"), (24 * 60, "1 day")];
+
+/// Tasks the backend reminded us of, stacked in a corner over every view until
+/// they are snoozed, ticked off or dismissed.
+#[component]
+pub fn TaskReminders(
+    tasks: RwSignal<Vec<Task>>,
+    reminders: RwSignal<Vec<Task>>,
+    #[prop(into)] on_open: Callback<(i64, i32)>,
+) -> impl IntoView {
+    let dismiss = move |task_id: i64| reminders.update(|reminders| reminders.retain(|r| r.id != task_id));
+
+    view! {
+        <div class="fixed bottom-4 right-4 z-50 flex flex-col gap-2 w-80">
+            <For
+                each=move || reminders.get()
+                key=|task| (task.id, task.due_at)
+                let:task
+            >
+                {
+                    let id = task.id;
+                    let source = task.chat_id.zip(task.message_id);
+                    view! {
+                        <div class="p-3 rounded-xl text-white shadow-lg" style="background: #312f2f; border: 1px solid #fbbf24">
+                            <div class="flex items-center justify-between text-[11px] text-amber-300">
+                                <span>{task.due_at.map(|due_at| format!("⏰ Due {}", format_date_time(due_at)))}</span>
+                                <button class="text-white/50 hover:text-white" title="Dismiss" on:click=move |_| dismiss(id)>
+                                    "✕"
+                                </button>
+                            </div>
+                            <div
+                                class="mt-1 text-sm"
+                                class:cursor-pointer=source.is_some()
+                                title=source.map(|_| "Open the message")
+                                on:click=move |_| {
+                                    if let Some(source) = source {
+                                        on_open.run(source);
+                                    }
+                                }
+                            >
+                                {task.text.clone()}
+                            </div>
+                            <div class="text-xs mt-0.5" style="color: #767876">{task.user_name.clone()}</div>
+                            <div class="flex flex-wrap items-center gap-1 mt-2 text-[11px]">
+                                <button
+                                    class="px-2 py-0.5 rounded-full text-black font-semibold"
+                                    style="background: #21ff5f"
+                                    on:click=move |_| {
+                                        set_task_completed(tasks, id, true);
+                                        dismiss(id);
+                                    }
+                                >
+                                    "✓ Done"
+                                </button>
+                                <span class="ml-1 text-white/60">"Snooze"</span>
+                                {SNOOZE_OPTIONS.into_iter().map(|(minutes, label)| view! {
+                                    <button
+                                        class="px-2 py-0.5 rounded-full text-white/70 hover:text-white"
+                                        style="background: rgba(84,54,57,0.48)"
+                                        on:click=move |_| {
+                                            snooze_task(id, minutes);
+                                            dismiss(id);
+                                        }
+                                    >
+                                        {label}
+                                    </button>
+                                }).collect_view()}
+                            </div>
+                        </div>
+                    }
+                }
+            </For>
+        </div>
+    }
+}